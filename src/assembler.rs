@@ -0,0 +1,342 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+
+use crate::memory::{PROGRAM_START, RAM_SIZE};
+use crate::opcode::{Opcode, Register};
+
+///Describes why a source file could not be assembled.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AssemblerError {
+    ///The line the error is on, starting from 1.
+    pub line: usize,
+    pub message: String,
+}
+
+impl Display for AssemblerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for AssemblerError {}
+
+///A statement that produces bytes, waiting for its operands to be resolved once every label is known.
+struct Statement<'a> {
+    line: usize,
+    mnemonic: String,
+    operands: Vec<&'a str>,
+}
+
+///Assembles a source file into a ROM that is loaded at the start of program memory.
+///
+///The syntax is the one the disassembler produces:
+///
+///```text
+///SPRITE_HEIGHT equ 5        ; constants
+///
+///start:                     ; labels, optionally followed by an instruction on the same line
+///    LD I, digit
+///    DRW V0, V1, SPRITE_HEIGHT
+///    JP start
+///digit:
+///    db 0xF0, 0x90, 0xF0, 0x90, 0xF0
+///    dw 0x1234              ; big endian words
+///```
+///
+///Mnemonics, registers and directives are case insensitive, labels and constants are not.
+///Numbers can be decimal, `0x` hexadecimal or `0b` binary.
+///
+///The ROM has to fit in CHIP-8's program space, [`assemble_to_fit`] takes XO-CHIP's larger one.
+pub fn assemble(source: &str) -> Result<Vec<u8>, AssemblerError> {
+    assemble_to_fit(source, RAM_SIZE - PROGRAM_START)
+}
+
+///Assembles a source file like [`assemble`], into a ROM of at most `program_space` bytes.
+pub fn assemble_to_fit(source: &str, program_space: usize) -> Result<Vec<u8>, AssemblerError> {
+    let mut symbols: HashMap<String, u32> = HashMap::new();
+    let mut statements = Vec::new();
+    let mut address = PROGRAM_START as u32;
+
+    for (i, line) in source.lines().enumerate() {
+        let line_number = i + 1;
+        let error = |message: String| AssemblerError {
+            line: line_number,
+            message,
+        };
+        let mut text = match line.split_once(';') {
+            Some((code, _comment)) => code.trim(),
+            None => line.trim(),
+        };
+
+        if let Some((label, rest)) = text.split_once(':') {
+            let label = label.trim();
+            check_symbol_name(label).map_err(error)?;
+            if symbols.insert(label.to_string(), address).is_some() {
+                return Err(error(format!("'{}' is defined more than once.", label)));
+            }
+            text = rest.trim();
+        }
+        if text.is_empty() {
+            continue;
+        }
+
+        let (mnemonic, operands) = match text.split_once(char::is_whitespace) {
+            Some((mnemonic, operands)) => (mnemonic, operands.trim()),
+            None => (text, ""),
+        };
+        if let Some((_, value)) = operands
+            .split_once(char::is_whitespace)
+            .filter(|(directive, _)| directive.eq_ignore_ascii_case("equ"))
+        {
+            check_symbol_name(mnemonic).map_err(error)?;
+            let value = parse_value(value.trim(), &symbols).map_err(error)?;
+            if symbols.insert(mnemonic.to_string(), value).is_some() {
+                return Err(error(format!("'{}' is defined more than once.", mnemonic)));
+            }
+            continue;
+        }
+
+        let mnemonic = mnemonic.to_uppercase();
+        let operands: Vec<&str> = match operands.is_empty() {
+            true => Vec::new(),
+            false => operands.split(',').map(str::trim).collect(),
+        };
+        address += match mnemonic.as_str() {
+            "DB" => operands.len() as u32,
+            "DW" => operands.len() as u32 * 2,
+            _ if operands.iter().any(|operand| is_long(operand)) => 4,
+            _ => 2,
+        };
+        if address as usize - PROGRAM_START > program_space {
+            return Err(error(format!(
+                "The program doesn't fit in the {} bytes of program space.",
+                program_space
+            )));
+        }
+        statements.push(Statement {
+            line: line_number,
+            mnemonic,
+            operands,
+        });
+    }
+
+    let mut rom = Vec::new();
+    for statement in statements {
+        let bytes = encode(&statement, &symbols).map_err(|message| AssemblerError {
+            line: statement.line,
+            message,
+        })?;
+        rom.extend(bytes);
+    }
+    Ok(rom)
+}
+
+///Names are used for labels and constants. They can't clash with registers or the other operand keywords.
+fn check_symbol_name(name: &str) -> Result<(), String> {
+    let valid = name
+        .chars()
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid {
+        return Err(format!(
+            "'{}' isn't a valid name, names are made of letters, digits and underscores.",
+            name
+        ));
+    }
+    if parse_register(name).is_some()
+        || ["I", "DT", "ST", "K", "F", "HF", "B", "R", "LONG"]
+            .contains(&name.to_uppercase().as_str())
+    {
+        return Err(format!(
+            "'{}' is reserved and can't be used as a name.",
+            name
+        ));
+    }
+    Ok(())
+}
+
+fn is_long(operand: &str) -> bool {
+    operand
+        .get(..5)
+        .is_some_and(|prefix| prefix.eq_ignore_ascii_case("long "))
+}
+
+///Parses a register name like `V3` or `vA`.
+fn parse_register(operand: &str) -> Option<Register> {
+    let digit = operand
+        .strip_prefix('V')
+        .or_else(|| operand.strip_prefix('v'))?;
+    match digit.len() {
+        1 => u8::from_str_radix(digit, 16).ok(),
+        _ => None,
+    }
+}
+
+///Parses a number or the name of a label or constant.
+fn parse_value(operand: &str, symbols: &HashMap<String, u32>) -> Result<u32, String> {
+    let lowercase = operand.to_lowercase();
+    let parsed = match (lowercase.strip_prefix("0x"), lowercase.strip_prefix("0b")) {
+        (Some(hex), _) => u32::from_str_radix(hex, 16).ok(),
+        (_, Some(binary)) => u32::from_str_radix(binary, 2).ok(),
+        _ if operand.starts_with(|c: char| c.is_ascii_digit()) => operand.parse().ok(),
+        _ => {
+            return symbols
+                .get(operand)
+                .copied()
+                .ok_or_else(|| format!("'{}' isn't defined.", operand))
+        }
+    };
+    parsed.ok_or_else(|| format!("'{}' isn't a valid number.", operand))
+}
+
+fn encode(statement: &Statement, symbols: &HashMap<String, u32>) -> Result<Vec<u8>, String> {
+    let operands = &statement.operands;
+    let value = |operand: &str, max: u32, what: &str| -> Result<u16, String> {
+        let value = parse_value(operand, symbols)?;
+        match value <= max {
+            true => Ok(value as u16),
+            false => Err(format!(
+                "{} is too large for {}, which can be at most 0x{:X}.",
+                operand, what, max
+            )),
+        }
+    };
+    let byte =
+        |operand: &str, max: u32, what: &str| value(operand, max, what).map(|value| value as u8);
+    let register = |operand: &str| -> Result<Register, String> {
+        parse_register(operand)
+            .ok_or_else(|| format!("Expected a register like V3, found '{}'.", operand))
+    };
+    let upper: Vec<String> = operands
+        .iter()
+        .map(|operand| operand.to_uppercase())
+        .collect();
+    let upper: Vec<&str> = upper.iter().map(String::as_str).collect();
+    let is_register = |index: usize| parse_register(operands[index]).is_some();
+
+    if statement.mnemonic == "DB" || statement.mnemonic == "DW" {
+        if operands.is_empty() {
+            return Err(format!("{} needs at least one value.", statement.mnemonic));
+        }
+        let mut bytes = Vec::new();
+        for operand in operands {
+            match statement.mnemonic.as_str() {
+                "DB" => bytes.push(value(operand, 0xFF, "a byte")? as u8),
+                _ => bytes.extend(value(operand, 0xFFFF, "a word")?.to_be_bytes()),
+            }
+        }
+        return Ok(bytes);
+    }
+
+    let opcode = match (statement.mnemonic.as_str(), upper.as_slice()) {
+        ("CLS", []) => Opcode::Cls,
+        ("RET", []) => Opcode::Ret,
+        ("SCD", [_]) => Opcode::Scd(byte(operands[0], 0xF, "a scroll distance")?),
+        ("SCR", []) => Opcode::Scr,
+        ("SCL", []) => Opcode::Scl,
+        ("EXIT", []) => Opcode::Exit,
+        ("LOW", []) => Opcode::Low,
+        ("HIGH", []) => Opcode::High,
+        ("JP", ["V0", _]) => Opcode::JpV0(value(operands[1], 0xFFF, "an address")?),
+        ("JP", [_]) => Opcode::Jp(value(operands[0], 0xFFF, "an address")?),
+        ("CALL", [_]) => Opcode::Call(value(operands[0], 0xFFF, "an address")?),
+        ("SE", [_, _]) if is_register(1) => {
+            Opcode::Se(register(operands[0])?, register(operands[1])?)
+        }
+        ("SE", [_, _]) => {
+            Opcode::SeImmediate(register(operands[0])?, byte(operands[1], 0xFF, "a byte")?)
+        }
+        ("SNE", [_, _]) if is_register(1) => {
+            Opcode::Sne(register(operands[0])?, register(operands[1])?)
+        }
+        ("SNE", [_, _]) => {
+            Opcode::SneImmediate(register(operands[0])?, byte(operands[1], 0xFF, "a byte")?)
+        }
+        ("SAVE", [_]) | ("LOAD", [_]) => {
+            let (first, last) = operands[0]
+                .split_once('-')
+                .ok_or_else(|| "Expected a register range like V2 - V5.".to_string())?;
+            let (first, last) = (register(first.trim())?, register(last.trim())?);
+            match statement.mnemonic.as_str() {
+                "SAVE" => Opcode::Save(first, last),
+                _ => Opcode::Load(first, last),
+            }
+        }
+        ("LD", ["I", address]) if is_long(address) => {
+            let address = value(operands[1][5..].trim(), 0xFFFF, "a long address")?;
+            let mut bytes = Opcode::LdILong.encode().to_be_bytes().to_vec();
+            bytes.extend(address.to_be_bytes());
+            return Ok(bytes);
+        }
+        ("LD", ["I", _]) => Opcode::LdI(value(operands[1], 0xFFF, "an address")?),
+        ("LD", [_, "DT"]) => Opcode::LdVxDt(register(operands[0])?),
+        ("LD", [_, "K"]) => Opcode::LdVxK(register(operands[0])?),
+        ("LD", ["DT", _]) => Opcode::LdDtVx(register(operands[1])?),
+        ("LD", ["ST", _]) => Opcode::LdStVx(register(operands[1])?),
+        ("LD", ["F", _]) => Opcode::LdF(register(operands[1])?),
+        ("LD", ["HF", _]) => Opcode::LdHf(register(operands[1])?),
+        ("LD", ["B", _]) => Opcode::LdB(register(operands[1])?),
+        ("LD", ["[I]", _]) => Opcode::LdIVx(register(operands[1])?),
+        ("LD", [_, "[I]"]) => Opcode::LdVxI(register(operands[0])?),
+        ("LD", ["R", _]) => Opcode::LdRVx(register(operands[1])?),
+        ("LD", [_, "R"]) => Opcode::LdVxR(register(operands[0])?),
+        ("LD", [_, _]) if is_register(1) => {
+            Opcode::Ld(register(operands[0])?, register(operands[1])?)
+        }
+        ("LD", [_, _]) => {
+            Opcode::LdImmediate(register(operands[0])?, byte(operands[1], 0xFF, "a byte")?)
+        }
+        ("ADD", ["I", _]) => Opcode::AddI(register(operands[1])?),
+        ("ADD", [_, _]) if is_register(1) => {
+            Opcode::Add(register(operands[0])?, register(operands[1])?)
+        }
+        ("ADD", [_, _]) => {
+            Opcode::AddImmediate(register(operands[0])?, byte(operands[1], 0xFF, "a byte")?)
+        }
+        ("OR", [_, _])
+        | ("AND", [_, _])
+        | ("XOR", [_, _])
+        | ("SUB", [_, _])
+        | ("SHR", [_, _])
+        | ("SUBN", [_, _])
+        | ("SHL", [_, _]) => {
+            let (x, y) = (register(operands[0])?, register(operands[1])?);
+            match statement.mnemonic.as_str() {
+                "OR" => Opcode::Or(x, y),
+                "AND" => Opcode::And(x, y),
+                "XOR" => Opcode::Xor(x, y),
+                "SUB" => Opcode::Sub(x, y),
+                "SHR" => Opcode::Shr(x, y),
+                "SUBN" => Opcode::Subn(x, y),
+                _ => Opcode::Shl(x, y),
+            }
+        }
+        //Shifting a register into itself behaves the same with or without the shift quirk.
+        ("SHR", [_]) => {
+            let x = register(operands[0])?;
+            Opcode::Shr(x, x)
+        }
+        ("SHL", [_]) => {
+            let x = register(operands[0])?;
+            Opcode::Shl(x, x)
+        }
+        ("RND", [_, _]) => Opcode::Rnd(register(operands[0])?, byte(operands[1], 0xFF, "a byte")?),
+        ("DRW", [_, _, _]) => Opcode::Drw(
+            register(operands[0])?,
+            register(operands[1])?,
+            byte(operands[2], 0xF, "a sprite height")?,
+        ),
+        ("SKP", [_]) => Opcode::Skp(register(operands[0])?),
+        ("SKNP", [_]) => Opcode::Sknp(register(operands[0])?),
+        ("PLANE", [_]) => Opcode::Plane(byte(operands[0], 0x3, "a plane mask")?),
+        _ => {
+            return Err(format!(
+                "Unknown instruction '{} {}'.",
+                statement.mnemonic,
+                operands.join(", ")
+            ))
+        }
+    };
+    Ok(opcode.encode().to_be_bytes().to_vec())
+}
@@ -0,0 +1,220 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use crate::cpu::Cpu;
+use crate::opcode::Register;
+
+///How a register is compared in a [`Condition`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl Comparison {
+    ///The operators in the order they have to be looked for, so `<=` isn't mistaken for `<`.
+    const OPERATORS: [(&'static str, Comparison); 6] = [
+        ("==", Comparison::Equal),
+        ("!=", Comparison::NotEqual),
+        ("<=", Comparison::LessOrEqual),
+        (">=", Comparison::GreaterOrEqual),
+        ("<", Comparison::Less),
+        (">", Comparison::Greater),
+    ];
+
+    fn operator(&self) -> &'static str {
+        Self::OPERATORS
+            .iter()
+            .find(|(_, comparison)| comparison == self)
+            .map(|(operator, _)| *operator)
+            .expect("Every comparison has an operator.")
+    }
+}
+
+///A comparison of a data register against a value, e.g. `V5 == 3`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Condition {
+    pub register: Register,
+    pub comparison: Comparison,
+    pub value: u8,
+}
+
+impl Condition {
+    ///Returns whether the condition is true for the CPU's current registers.
+    pub fn holds(&self, cpu: &Cpu) -> bool {
+        let register_value = cpu.data_registers[self.register as usize];
+        match self.comparison {
+            Comparison::Equal => register_value == self.value,
+            Comparison::NotEqual => register_value != self.value,
+            Comparison::Less => register_value < self.value,
+            Comparison::LessOrEqual => register_value <= self.value,
+            Comparison::Greater => register_value > self.value,
+            Comparison::GreaterOrEqual => register_value >= self.value,
+        }
+    }
+}
+
+impl Display for Condition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "V{:X} {} 0x{:02X}",
+            self.register,
+            self.comparison.operator(),
+            self.value
+        )
+    }
+}
+
+impl FromStr for Condition {
+    type Err = String;
+
+    ///Parses conditions like `V5 == 3` or `va>=0x10`. Values are decimal, or hexadecimal with a 0x prefix.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "Invalid condition '{}', expected a register, one of == != < <= > >= and a value, e.g. V5 == 3.",
+                text
+            )
+        };
+        let (register, comparison, value) = Comparison::OPERATORS
+            .iter()
+            .find_map(|(operator, comparison)| {
+                text.split_once(operator)
+                    .map(|(register, value)| (register.trim(), *comparison, value.trim()))
+            })
+            .ok_or_else(invalid)?;
+
+        let register = register
+            .strip_prefix('V')
+            .or_else(|| register.strip_prefix('v'))
+            .filter(|digit| digit.len() == 1)
+            .and_then(|digit| u8::from_str_radix(digit, 16).ok())
+            .ok_or_else(invalid)?;
+        let value = match value
+            .strip_prefix("0x")
+            .or_else(|| value.strip_prefix("0X"))
+        {
+            Some(hex) => u8::from_str_radix(hex, 16),
+            None => value.parse(),
+        }
+        .map_err(|_| invalid())?;
+        Ok(Condition {
+            register,
+            comparison,
+            value,
+        })
+    }
+}
+
+///The kinds of memory access a watchpoint stops on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+impl Access {
+    ///Returns whether a watchpoint for this kind of access stops on the given one.
+    pub fn includes(&self, access: Access) -> bool {
+        *self == Access::ReadWrite || *self == access
+    }
+}
+
+impl Display for Access {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Access::Read => write!(f, "read"),
+            Access::Write => write!(f, "write"),
+            Access::ReadWrite => write!(f, "read/write"),
+        }
+    }
+}
+
+///Something that pauses execution when it is reached. Breakpoints are checked after every instruction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Breakpoint {
+    ///Stops before the instruction at the address is executed, as long as the condition holds if there is one.
+    Address {
+        address: u16,
+        condition: Option<Condition>,
+    },
+    ///Stops after the instruction that makes the condition true.
+    Condition(Condition),
+    ///Stops after an instruction reads or writes RAM between the two addresses, inclusive. Instruction fetches
+    ///don't count.
+    Watch {
+        start: u16,
+        end: u16,
+        access: Access,
+    },
+}
+
+impl Breakpoint {
+    ///Returns whether the watchpoint covers any of the given range of addresses. Always false for other breakpoints.
+    pub fn watches(&self, address: usize, num_bytes: usize, access: Access) -> bool {
+        match self {
+            Breakpoint::Watch {
+                start,
+                end,
+                access: watched_access,
+            } => {
+                watched_access.includes(access)
+                    && address <= *end as usize
+                    && address + num_bytes > *start as usize
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Display for Breakpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Breakpoint::Address {
+                address,
+                condition: None,
+            } => write!(f, "0x{:04X}", address),
+            Breakpoint::Address {
+                address,
+                condition: Some(condition),
+            } => write!(f, "0x{:04X} if {}", address, condition),
+            Breakpoint::Condition(condition) => write!(f, "when {}", condition),
+            Breakpoint::Watch { start, end, access } if start == end => {
+                write!(f, "{} 0x{:04X}", access, start)
+            }
+            Breakpoint::Watch { start, end, access } => {
+                write!(f, "{} 0x{:04X}-0x{:04X}", access, start, end)
+            }
+        }
+    }
+}
+
+///Reported when execution pauses because of a breakpoint.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BreakpointHit {
+    pub breakpoint: Breakpoint,
+    ///The address of the instruction that triggered the breakpoint. For address breakpoints this is the
+    ///instruction before the one the breakpoint is on.
+    pub instruction_address: u16,
+    ///Where execution paused, which is the next instruction to be executed.
+    pub program_counter: u16,
+}
+
+impl Display for BreakpointHit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.breakpoint {
+            Breakpoint::Address { .. } => write!(f, "Reached breakpoint {}.", self.breakpoint),
+            _ => write!(
+                f,
+                "Stopped on {} after the instruction at 0x{:04X}.",
+                self.breakpoint, self.instruction_address
+            ),
+        }
+    }
+}
@@ -0,0 +1,410 @@
+use std::path::PathBuf;
+
+use chip8::quirks::Quirks;
+
+pub const USAGE: &str = "\
+Usage: chip8 <rom> [options]
+       chip8 disasm <rom>
+       chip8 asm <source> [-o <rom>] [--xo-chip]
+       chip8 bench [<rom>...] [options]
+
+Commands:
+  disasm <rom>              Print the ROM as assembly, separating code from data
+  asm <source>              Assemble a source file into a ROM, written next to the source with a
+                            .ch8 extension unless -o is given. --xo-chip lets it fill XO-CHIP's memory
+  bench [<rom>...]          Run each ROM or every ROM in each directory as fast as possible and report
+                            the instructions per second, using the roms directory if none are given.
+                            Takes -s, -q and --instructions, which defaults to 10000000
+
+Options:
+  -s, --speed <ips>         Instructions executed per second (default 700)
+  -q, --quirks <preset>     Quirks preset: vip, chip48, schip or xo-chip (default vip)
+                            xo-chip also enables XO-CHIP's 64 KiB of memory
+  -x, --scale <factor>      Window and PNG scale, each CHIP-8 pixel is this many pixels wide (default 10)
+  -p, --palette <colours>   Four comma separated hex colours for the background, plane 1,
+                            plane 2 and both planes, e.g. 10159e,000000,ffaa00,783c00
+  -k, --keymap <file>       Keymap config file (default keymap.toml if it exists)
+      --seed <n>            Seed for the random numbers of Cxkk, so runs can be repeated exactly
+                            (default a different seed every run)
+      --headless            Run without a window as fast as possible and print the screen when the
+                            program exits, fails, gets stuck in a loop or reaches a limit
+      --frames <n>          Stop a headless run after this many 60 Hz frames
+      --instructions <n>    Stop a headless run after this many instructions
+  -o, --output <file>       Write the final screen of a headless run to this file instead of stdout,
+                            as a PNG if the file name ends in .png and as text otherwise
+      --record <file>       Record the keys pressed into a movie file, written when the window is closed
+                            or the headless run stops
+      --play <file>         Play back a movie recorded with the same ROM, reporting any point where the
+                            screen differs from the recording. Headless runs stop when the movie ends
+      --debugger            Start paused with an interactive debugger on the terminal, alongside the
+                            window or on its own with --headless
+  -d, --debug               Print every executed instruction
+  -h, --help                Print this message
+
+In the window, Shift+F1 to Shift+F9 save the state to slots 1 to 9, stored next to the ROM, and F1 to F9
+load them again. Holding Backspace rewinds up to 10 seconds.
+";
+
+///What the program was asked to do.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    ///Run a ROM.
+    Run(Options),
+    ///Print the disassembly of a ROM.
+    Disassemble { rom_path: PathBuf },
+    ///Assemble a source file into a ROM.
+    Assemble {
+        source_path: PathBuf,
+        output_path: PathBuf,
+        ///Whether the ROM can be as large as XO-CHIP's program space.
+        xo_chip: bool,
+    },
+    ///Time how fast ROMs run.
+    Benchmark(BenchmarkOptions),
+}
+
+///The options the emulator was started with.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Options {
+    pub rom_path: PathBuf,
+    pub clock_speed_hz: u16,
+    pub quirks: Quirks,
+    pub xo_chip: bool,
+    pub scale: u32,
+    pub palette: Option<[[u8; 4]; 4]>,
+    pub keymap_path: Option<PathBuf>,
+    ///The random number generator's seed, or `None` to pick one at random.
+    pub seed: Option<u64>,
+    pub headless: bool,
+    pub frame_limit: Option<u64>,
+    pub instruction_limit: Option<u64>,
+    pub output_path: Option<PathBuf>,
+    ///Where to write a movie of the run.
+    pub record_path: Option<PathBuf>,
+    ///A movie to play back.
+    pub movie_path: Option<PathBuf>,
+    pub debugger: bool,
+    pub debug: bool,
+}
+
+///The options for benchmarking ROMs.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BenchmarkOptions {
+    ///The ROMs, or directories of ROMs, to run. Empty if none were given.
+    pub rom_paths: Vec<PathBuf>,
+    pub clock_speed_hz: u16,
+    pub quirks: Quirks,
+    pub xo_chip: bool,
+    ///How many instructions each ROM runs for.
+    pub instructions: u64,
+}
+
+///Why the command line couldn't be turned into options.
+#[derive(Debug, PartialEq, Eq)]
+pub enum CliError {
+    ///Help was asked for, so the usage should be printed and nothing else done.
+    HelpRequested,
+    ///The arguments were invalid.
+    Invalid(String),
+}
+
+impl Command {
+    ///Parses the arguments passed to the program, not including the program name itself.
+    pub fn parse<I>(args: I) -> Result<Command, CliError>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut args = args.into_iter().peekable();
+        match args.peek().map(String::as_str) {
+            Some("disasm") => {
+                args.next();
+                let (rom_path, _) = parse_file_arguments(args, false)?;
+                Ok(Command::Disassemble { rom_path })
+            }
+            Some("asm") => {
+                args.next();
+                let mut xo_chip = false;
+                let args = args.filter(|arg| match arg == "--xo-chip" {
+                    true => {
+                        xo_chip = true;
+                        false
+                    }
+                    false => true,
+                });
+                let (source_path, output_path) = parse_file_arguments(args, true)?;
+                let output_path = output_path.unwrap_or_else(|| source_path.with_extension("ch8"));
+                if output_path == source_path {
+                    return Err(CliError::Invalid(
+                        "The ROM would overwrite the source file, use -o to write it elsewhere."
+                            .to_string(),
+                    ));
+                }
+                Ok(Command::Assemble {
+                    source_path,
+                    output_path,
+                    xo_chip,
+                })
+            }
+            Some("bench") => {
+                args.next();
+                BenchmarkOptions::parse(args).map(Command::Benchmark)
+            }
+            _ => Options::parse(args).map(Command::Run),
+        }
+    }
+}
+
+impl Options {
+    ///Parses the arguments passed to the program, not including the program name itself.
+    pub fn parse<I>(args: I) -> Result<Options, CliError>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut rom_path = None;
+        let mut options = Options {
+            rom_path: PathBuf::new(),
+            clock_speed_hz: 700,
+            quirks: Quirks::default(),
+            xo_chip: false,
+            scale: 10,
+            palette: None,
+            keymap_path: None,
+            seed: None,
+            headless: false,
+            frame_limit: None,
+            instruction_limit: None,
+            output_path: None,
+            record_path: None,
+            movie_path: None,
+            debugger: false,
+            debug: false,
+        };
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| {
+                args.next()
+                    .ok_or_else(|| CliError::Invalid(format!("{} needs a value.", name)))
+            };
+            match arg.as_str() {
+                "-h" | "--help" => return Err(CliError::HelpRequested),
+                "-s" | "--speed" => options.clock_speed_hz = parse_speed(&value(&arg)?)?,
+                "-q" | "--quirks" => {
+                    let preset = value(&arg)?;
+                    let (quirks, xo_chip) = parse_quirks_preset(&preset)?;
+                    options.quirks = quirks;
+                    options.xo_chip = xo_chip;
+                }
+                "-x" | "--scale" => {
+                    let scale = value(&arg)?;
+                    options.scale = match scale.parse() {
+                        Ok(scale @ 1..=64) => scale,
+                        _ => {
+                            return Err(CliError::Invalid(format!(
+                                "Invalid scale '{}', expected a number from 1 to 64.",
+                                scale
+                            )))
+                        }
+                    };
+                }
+                "-p" | "--palette" => {
+                    options.palette = Some(parse_palette(&value(&arg)?)?);
+                }
+                "-k" | "--keymap" => {
+                    options.keymap_path = Some(PathBuf::from(value(&arg)?));
+                }
+                "--seed" => options.seed = Some(parse_seed(&value(&arg)?)?),
+                "--headless" => options.headless = true,
+                "--frames" => options.frame_limit = Some(parse_limit(&arg, &value(&arg)?)?),
+                "--instructions" => {
+                    options.instruction_limit = Some(parse_limit(&arg, &value(&arg)?)?)
+                }
+                "-o" | "--output" => {
+                    options.output_path = Some(PathBuf::from(value(&arg)?));
+                }
+                "--record" => options.record_path = Some(PathBuf::from(value(&arg)?)),
+                "--play" => options.movie_path = Some(PathBuf::from(value(&arg)?)),
+                "--debugger" => options.debugger = true,
+                "-d" | "--debug" => options.debug = true,
+                _ if arg.starts_with('-') => {
+                    return Err(CliError::Invalid(format!("Unknown option '{}'.", arg)));
+                }
+                _ => {
+                    if rom_path.is_some() {
+                        return Err(CliError::Invalid(format!(
+                            "Unexpected argument '{}', only one ROM can be run at a time.",
+                            arg
+                        )));
+                    }
+                    rom_path = Some(PathBuf::from(arg));
+                }
+            }
+        }
+
+        options.rom_path =
+            rom_path.ok_or_else(|| CliError::Invalid("No ROM was given.".to_string()))?;
+        let has_headless_options = options.frame_limit.is_some()
+            || options.instruction_limit.is_some()
+            || options.output_path.is_some();
+        if has_headless_options && (!options.headless || options.debugger) {
+            return Err(CliError::Invalid(
+                "--frames, --instructions and --output only apply to --headless runs without --debugger."
+                    .to_string(),
+            ));
+        }
+        if options.record_path.is_some() && (options.movie_path.is_some() || options.debugger) {
+            return Err(CliError::Invalid(
+                "--record can't be combined with --play or --debugger.".to_string(),
+            ));
+        }
+        Ok(options)
+    }
+}
+
+impl BenchmarkOptions {
+    ///Parses the arguments following `bench`.
+    pub fn parse<I>(args: I) -> Result<BenchmarkOptions, CliError>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut options = BenchmarkOptions {
+            rom_paths: Vec::new(),
+            clock_speed_hz: 700,
+            quirks: Quirks::default(),
+            xo_chip: false,
+            instructions: 10_000_000,
+        };
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| {
+                args.next()
+                    .ok_or_else(|| CliError::Invalid(format!("{} needs a value.", name)))
+            };
+            match arg.as_str() {
+                "-h" | "--help" => return Err(CliError::HelpRequested),
+                "-s" | "--speed" => options.clock_speed_hz = parse_speed(&value(&arg)?)?,
+                "-q" | "--quirks" => {
+                    let (quirks, xo_chip) = parse_quirks_preset(&value(&arg)?)?;
+                    options.quirks = quirks;
+                    options.xo_chip = xo_chip;
+                }
+                "--instructions" => options.instructions = parse_limit(&arg, &value(&arg)?)?,
+                _ if arg.starts_with('-') => {
+                    return Err(CliError::Invalid(format!("Unknown option '{}'.", arg)));
+                }
+                _ => options.rom_paths.push(PathBuf::from(arg)),
+            }
+        }
+        Ok(options)
+    }
+}
+
+///Parses the arguments of a command that works on a single file, optionally taking an output file with `-o`.
+fn parse_file_arguments<I>(
+    mut args: I,
+    takes_output: bool,
+) -> Result<(PathBuf, Option<PathBuf>), CliError>
+where
+    I: Iterator<Item = String>,
+{
+    let mut path = None;
+    let mut output_path = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Err(CliError::HelpRequested),
+            "-o" | "--output" if takes_output => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| CliError::Invalid(format!("{} needs a value.", arg)))?;
+                output_path = Some(PathBuf::from(value));
+            }
+            _ if arg.starts_with('-') => {
+                return Err(CliError::Invalid(format!("Unknown option '{}'.", arg)));
+            }
+            _ if path.is_some() => {
+                return Err(CliError::Invalid(format!(
+                    "Unexpected argument '{}', only one file can be given.",
+                    arg
+                )));
+            }
+            _ => path = Some(PathBuf::from(arg)),
+        }
+    }
+    let path = path.ok_or_else(|| CliError::Invalid("No file was given.".to_string()))?;
+    Ok((path, output_path))
+}
+
+///Parses a clock speed in instructions per second.
+fn parse_speed(speed: &str) -> Result<u16, CliError> {
+    match speed.parse() {
+        Ok(0) | Err(_) => Err(CliError::Invalid(format!(
+            "Invalid speed '{}', expected a number from 1 to {}.",
+            speed,
+            u16::MAX
+        ))),
+        Ok(speed) => Ok(speed),
+    }
+}
+
+///Parses the value of a frame or instruction limit, which has to be at least 1.
+fn parse_limit(name: &str, limit: &str) -> Result<u64, CliError> {
+    match limit.parse() {
+        Ok(0) | Err(_) => Err(CliError::Invalid(format!(
+            "Invalid value '{}' for {}, expected a positive number.",
+            limit, name
+        ))),
+        Ok(limit) => Ok(limit),
+    }
+}
+
+fn parse_seed(seed: &str) -> Result<u64, CliError> {
+    seed.parse().map_err(|_| {
+        CliError::Invalid(format!(
+            "Invalid seed '{}', expected a number from 0 to {}.",
+            seed,
+            u64::MAX
+        ))
+    })
+}
+
+///Returns the quirks for the named preset and whether it is the XO-CHIP one.
+fn parse_quirks_preset(preset: &str) -> Result<(Quirks, bool), CliError> {
+    match preset.to_lowercase().as_str() {
+        "vip" | "cosmac-vip" | "chip8" | "chip-8" => Ok((Quirks::cosmac_vip(), false)),
+        "chip48" | "chip-48" => Ok((Quirks::chip48(), false)),
+        "schip" | "superchip" | "super-chip" => Ok((Quirks::super_chip(), false)),
+        "xo-chip" | "xochip" => Ok((Quirks::xo_chip(), true)),
+        _ => Err(CliError::Invalid(format!(
+            "Unknown quirks preset '{}', expected vip, chip48, schip or xo-chip.",
+            preset
+        ))),
+    }
+}
+
+///Parses four comma separated `rrggbb` colours.
+fn parse_palette(palette: &str) -> Result<[[u8; 4]; 4], CliError> {
+    let invalid = || {
+        CliError::Invalid(format!(
+            "Invalid palette '{}', expected four comma separated colours like 10159e.",
+            palette
+        ))
+    };
+    let colours: Vec<&str> = palette.split(',').map(str::trim).collect();
+    if colours.len() != 4 {
+        return Err(invalid());
+    }
+
+    let mut parsed = [[0, 0, 0, 255]; 4];
+    for (colour, parsed_colour) in colours.into_iter().zip(parsed.iter_mut()) {
+        let colour = colour.trim_start_matches('#');
+        if colour.len() != 6 || !colour.is_ascii() {
+            return Err(invalid());
+        }
+        for channel in 0..3 {
+            parsed_colour[channel] = u8::from_str_radix(&colour[channel * 2..channel * 2 + 2], 16)
+                .map_err(|_| invalid())?;
+        }
+    }
+    Ok(parsed)
+}
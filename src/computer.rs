@@ -1,18 +1,79 @@
+use crate::breakpoint::{Access, Breakpoint, BreakpointHit};
 use crate::cpu::Cpu;
+use crate::decode_cache::DecodeCache;
+use crate::disassembler::disassemble;
+use crate::error::{ExecutionError, RomTooLarge};
 use crate::frame_buffer::FrameBuffer;
 use crate::input::Input;
-use crate::memory::Memory;
+use crate::memory::{Memory, BIG_FONT_ADDRESS, XO_CHIP_RAM_SIZE};
+use crate::movie::{rom_checksum, Movie, MovieError, Playback, Player};
+use crate::opcode::{Opcode, Register};
+use crate::quirks::Quirks;
+use crate::rewind::RewindBuffer;
+use crate::rng::Chip8Rng;
+use crate::save_state::{SaveState, SaveStateError};
+use crate::threading::{EmulatorCommand, EmulatorResponse, ThreadedEmulator};
+use crate::undo::{UndoLog, UndoRecord};
 use crate::Instruction;
+use std::convert::TryFrom;
 use std::fmt::Display;
-use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::mpsc::Sender;
+use std::time::{Duration, Instant};
+
+///How long a single 60 Hz frame lasts.
+const FRAME_PERIOD: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+///Decides when a 60 Hz frame has passed, which is when the timers count down.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimingMode {
+    ///Frames follow the host's wall clock, independently of how fast instructions are executed.
+    RealTime,
+    ///A frame passes after every given number of instructions, so runs are reproducible regardless of the host.
+    InstructionsPerFrame(u32),
+}
 
 pub struct Chip8Computer {
     pub cpu: Cpu,
     pub memory: Memory,
     pub frame_buffer: FrameBuffer,
     pub input: Input,
+    pub quirks: Quirks,
+    pub timing_mode: TimingMode,
+    ///Where Cxkk gets its random numbers from.
+    rng: Chip8Rng,
     response_sender: Sender<EmulatorResponse>,
     clock_speed_hz: u16,
+    running: bool,
+    next_tick: Instant,
+    next_frame: Instant,
+    drawn_this_frame: bool,
+    instructions_this_frame: u32,
+    ///How many vertical blanks there have been, including those of frames that were later rewound or undone.
+    vertical_blanks: u64,
+    debug_output: bool,
+    decode_cache: DecodeCache,
+    breakpoints: Vec<Breakpoint>,
+    ///The breakpoint triggered by the last instruction executed, if any.
+    breakpoint_hit: Option<BreakpointHit>,
+    ///RAM read or written by the current instruction as (address, number of bytes, access). Only recorded while
+    ///there are breakpoints, for the watchpoints among them, or the undo log is enabled, for watchpoints added
+    ///before going backwards.
+    memory_accesses: Vec<(usize, usize, Access)>,
+    ///The state at the start of each recent frame.
+    rewind_buffer: RewindBuffer,
+    ///What each recent instruction overwrote.
+    undo_log: UndoLog,
+    ///The previous contents of RAM written by the current instruction, while the undo log is enabled.
+    overwritten_ram: Vec<(usize, Vec<u8>)>,
+    ///The CRC-32 of the loaded ROM, which movies identify it by.
+    rom_checksum: u32,
+    ///The movie being recorded, if any.
+    recording: Option<Movie>,
+    ///The keys pressed while recording, which only take effect once the next frame starts so that the movie can
+    ///record the keys per frame.
+    latched_keys: [bool; 16],
+    ///The movie being played back, if any.
+    player: Option<Player>,
 }
 
 impl Chip8Computer {
@@ -20,15 +81,256 @@ impl Chip8Computer {
         Chip8Computer {
             cpu: Cpu::new(),
             memory: Memory::new(),
-            frame_buffer: FrameBuffer::new(response_sender),
+            frame_buffer: FrameBuffer::new(response_sender.clone()),
             response_sender,
             input: Input::new(),
-            clock_speed_hz: 10,
+            quirks: Quirks::default(),
+            timing_mode: TimingMode::RealTime,
+            rng: Chip8Rng::from_entropy(),
+            clock_speed_hz: 700,
+            running: false,
+            next_tick: Instant::now(),
+            next_frame: Instant::now(),
+            drawn_this_frame: false,
+            instructions_this_frame: 0,
+            vertical_blanks: 0,
+            debug_output: false,
+            decode_cache: DecodeCache::new(),
+            breakpoints: Vec::new(),
+            breakpoint_hit: None,
+            memory_accesses: Vec::new(),
+            rewind_buffer: RewindBuffer::new(0),
+            undo_log: UndoLog::new(0),
+            overwritten_ram: Vec::new(),
+            rom_checksum: rom_checksum(&[]),
+            recording: None,
+            latched_keys: [false; 16],
+            player: None,
+        }
+    }
+
+    ///Signals the start of a new 60 Hz frame, counting down the delay and sound timers.
+    ///The frame that ended is added to the movie being recorded or checked against the one being played back,
+    ///and the new frame's state is recorded for rewinding if that is enabled.
+    ///
+    ///In real time frames start in between instructions, so undoing the instruction before one undoes it too.
+    pub fn vertical_blank(&mut self) {
+        self.start_frame();
+        self.undo_log.frame_started();
+    }
+
+    ///Starts a new frame for [`Chip8Computer::vertical_blank`], leaving the undo log to the caller.
+    fn start_frame(&mut self) {
+        if let Some(movie) = &mut self.recording {
+            movie.record_frame(
+                &self.input.keys,
+                self.instructions_this_frame,
+                &self.frame_buffer,
+            );
+            self.input.keys = self.latched_keys;
+        }
+        if let Some(player) = &mut self.player {
+            let was_playing = player.frame_length().is_some();
+            if let Some(desync) = player.end_frame(&self.frame_buffer) {
+                let _ = self
+                    .response_sender
+                    .send(EmulatorResponse::MovieDesync(desync));
+            }
+            match player.keys() {
+                Some(keys) => self.input.keys = keys,
+                None if was_playing => {
+                    let _ = self
+                        .response_sender
+                        .send(EmulatorResponse::MovieFinished(player.progress()));
+                }
+                None => {}
+            }
+        }
+        self.drawn_this_frame = false;
+        self.instructions_this_frame = 0;
+        self.vertical_blanks += 1;
+        self.cpu.sound_timer = self.cpu.sound_timer.saturating_sub(1);
+        self.cpu.delay_timer = self.cpu.delay_timer.saturating_sub(1);
+        if self.rewind_buffer.capacity() > 0 {
+            let state = self.save_state();
+            self.rewind_buffer.push(state);
+        }
+    }
+
+    ///Sets how many frames back the machine can be rewound. 0, the default, turns off recording them.
+    pub fn set_rewind_frames(&mut self, frames: usize) {
+        self.rewind_buffer.set_capacity(frames);
+    }
+
+    ///Goes back to the start of the frame `frames` frames ago, where 1 is the start of the current frame.
+    ///The states passed over are dropped, so rewinding again goes further back.
+    ///
+    ///Returns how many frames were rewound, which is fewer than asked for if the history doesn't go back as far.
+    pub fn rewind(&mut self, frames: usize) -> usize {
+        let mut rewound = 0;
+        let mut state = None;
+        while rewound < frames {
+            match self.rewind_buffer.pop() {
+                Some(popped) => state = Some(popped),
+                None => break,
+            }
+            rewound += 1;
+        }
+        if let Some(state) = state {
+            self.restore(
+                SaveState::from_bytes(&state).expect("Rewind states are written by save_state."),
+            );
+            //The frames after the one rewound to haven't happened anymore.
+            if let Some(movie) = &mut self.recording {
+                let frames = (movie.frames.len() + 1).saturating_sub(rewound);
+                movie.frames.truncate(frames);
+            }
+            if let Some(player) = &mut self.player {
+                player.go_back(rewound - 1);
+            }
+        }
+        rewound
+    }
+
+    ///Starts recording a movie, see [`Movie`]. Playing a movie back starts with the ROM just loaded, so recording
+    ///should start right after loading it.
+    ///
+    ///Keys pressed with [`Chip8Computer::set_key`] while recording only take effect once the next frame starts.
+    ///Changes made any other way, such as loading a save state or editing registers, aren't recorded.
+    ///Rewinding and stepping back are, by dropping the frames that are undone.
+    pub fn start_recording(&mut self) {
+        self.player = None;
+        self.latched_keys = self.input.keys;
+        self.recording = Some(Movie::new(
+            self.rom_checksum,
+            self.seed(),
+            self.quirks,
+            self.memory.ram.len() == XO_CHIP_RAM_SIZE,
+        ));
+    }
+
+    ///Stops recording, returning the movie of every frame that has ended since recording started.
+    pub fn stop_recording(&mut self) -> Option<Movie> {
+        self.recording.take()
+    }
+
+    ///Plays back a movie recorded with the loaded ROM, which should have just been loaded.
+    ///
+    ///The movie's seed and quirks are used, its keys replace the keypad and its frames replace the timing mode
+    ///until it is over. Keys pressed in the meantime are ignored. See [`Chip8Computer::playback`] for how far it
+    ///has got and whether it has desynced.
+    pub fn play_movie(&mut self, movie: Movie) -> Result<(), MovieError> {
+        if movie.rom_checksum != self.rom_checksum {
+            return Err(MovieError::WrongRom {
+                recorded: movie.rom_checksum,
+                loaded: self.rom_checksum,
+            });
+        }
+        match (movie.xo_chip, self.memory.ram.len() == XO_CHIP_RAM_SIZE) {
+            (true, false) => self.enable_xo_chip(),
+            (false, true) => return Err(MovieError::XoChipEnabled),
+            _ => {}
+        }
+        self.quirks = movie.quirks;
+        self.set_seed(movie.seed);
+        self.recording = None;
+
+        let player = Player::new(movie);
+        if let Some(keys) = player.keys() {
+            self.input.keys = keys;
+        }
+        self.player = Some(player);
+        //Frames without any instructions, which can be recorded in real time, end straight away.
+        while self.frame_ended() {
+            self.start_frame();
+        }
+        Ok(())
+    }
+
+    ///Returns how far the movie being played back has got, or `None` if there isn't one.
+    pub fn playback(&self) -> Option<Playback> {
+        self.player.as_ref().map(Player::progress)
+    }
+
+    ///Returns whether a movie is being played back and decides when frames end.
+    fn is_playing(&self) -> bool {
+        self.player
+            .as_ref()
+            .and_then(Player::frame_length)
+            .is_some()
+    }
+
+    ///Records a key on the hex keypad as held down or released. While recording a movie the change takes effect
+    ///when the next frame starts, and while playing one back it is ignored.
+    pub fn set_key(&mut self, key: u8, pressed: bool) {
+        match (self.recording.is_some(), self.is_playing()) {
+            (true, _) => self.latched_keys[(key & 0x0F) as usize] = pressed,
+            (false, false) => self.input.set_key(key, pressed),
+            (false, true) => {}
+        }
+    }
+
+    ///Returns whether the current frame is over, which while playing back a movie is decided by the movie.
+    fn frame_ended(&self) -> bool {
+        let length = self.player.as_ref().and_then(Player::frame_length);
+        match (length, self.timing_mode) {
+            (Some(length), _) => self.instructions_this_frame >= length,
+            (None, TimingMode::InstructionsPerFrame(length)) => {
+                self.instructions_this_frame >= length.max(1)
+            }
+            (None, TimingMode::RealTime) => false,
         }
     }
 
-    pub fn execute_loop(&mut self) {
-        let instruction = self.tick();
+    ///Sets whether every executed instruction is printed to stdout.
+    pub fn set_debug_output(&mut self, debug_output: bool) {
+        self.debug_output = debug_output;
+    }
+
+    ///Sets how many instructions are executed per second while running freely.
+    pub fn set_clock_speed(&mut self, clock_speed_hz: u16) {
+        self.clock_speed_hz = clock_speed_hz;
+    }
+
+    ///Returns how long a single instruction should take at the targeted clock speed.
+    fn tick_period(&self) -> Duration {
+        Duration::from_secs_f64(1.0 / self.clock_speed_hz.max(1) as f64)
+    }
+
+    fn send_response(&self, response: EmulatorResponse) -> Result<(), String> {
+        self.response_sender
+            .send(response)
+            .map_err(|_| "The receiver for emulator responses has been dropped.".to_string())
+    }
+
+    ///Executes a single instruction.
+    ///
+    ///If the instruction fails the emulator is paused and the error is reported back through the response channel,
+    ///as is a breakpoint being hit. Returns the executed instruction otherwise.
+    pub fn execute_loop(&mut self) -> Result<Instruction, ExecutionError> {
+        let program_counter = self.cpu.program_counter;
+        let instruction = match self.tick() {
+            Ok(instruction) => instruction,
+            Err(e) => {
+                self.running = false;
+                let _ = self.response_sender.send(EmulatorResponse::Halted(e));
+                return Err(e);
+            }
+        };
+        if let Some(hit) = self.breakpoint_hit {
+            self.running = false;
+            let _ = self
+                .response_sender
+                .send(EmulatorResponse::BreakpointHit(hit));
+        }
+        if self.debug_output {
+            println!(
+                "0x{:04X}: {:04X}  {}",
+                program_counter,
+                instruction.value,
+                disassemble(&instruction).unwrap_or_default()
+            );
+        }
 
         #[cfg(feature = "debug")]
         {
@@ -37,167 +339,371 @@ impl Chip8Computer {
             println!("{}", self);
             println!("{}", debug);
         }
+        Ok(instruction)
     }
 
-    pub fn tick(&mut self) -> Instruction {
-        let instruction = self
-            .memory
-            .read_instruction(self.cpu.program_counter)
-            .into();
-        self.cpu.program_counter += 2;
-        self.map_operation_to_function(&instruction);
+    ///Fetches and executes the instruction at the program counter.
+    ///
+    ///On failure the program counter is left pointing at the instruction that failed.
+    ///Afterwards the breakpoints are checked, see [`Chip8Computer::breakpoint_hit`].
+    pub fn tick(&mut self) -> Result<Instruction, ExecutionError> {
+        let program_counter = self.cpu.program_counter;
+        self.breakpoint_hit = None;
+        self.memory_accesses.clear();
+        self.overwritten_ram.clear();
+        let conditions_held = self.conditions_held();
+        if let Some(address) = self.memory.find_out_of_bounds(program_counter as usize, 2) {
+            return Err(ExecutionError::MemoryOutOfBounds {
+                program_counter,
+                address,
+            });
+        }
 
-        self.cpu.sound_timer = self.cpu.sound_timer.saturating_sub(0);
-        self.cpu.delay_timer = self.cpu.delay_timer.saturating_sub(0);
+        let opcode = self
+            .decode_cache
+            .decode(&self.memory, program_counter)
+            .map_err(|e| ExecutionError::UnknownOpcode {
+                program_counter,
+                instruction: e.0,
+            })?;
+        let undo_record = match self.undo_log.is_enabled() {
+            true => Some((self.start_undo_record(), self.frame_buffer.buffer)),
+            false => None,
+        };
+        self.cpu.program_counter = self.cpu.program_counter.wrapping_add(2);
+        if let Err(e) = self.execute_opcode(opcode) {
+            self.cpu.program_counter = program_counter;
+            return Err(e);
+        }
 
-        instruction
-    }
+        self.instructions_this_frame += 1;
+        let mut frames_started = 0;
+        while self.frame_ended() {
+            self.start_frame();
+            frames_started += 1;
+        }
 
-    pub fn map_operation_to_function(&mut self, operation: &Instruction) {
-        match operation.get_opcode() {
-            0x0 => match operation.value {
-                0x00E0 => {
-                    self.clear_frame_buffer();
-                }
-                0x00EE => {
-                    self.return_subroutine();
-                }
-                _ => {
-                    panic!(
-                        "sys addr (0x{:04X}) is not implemented in this emulator.",
-                        operation.value
-                    );
-                }
-            },
-            0x1 => {
-                self.jump(operation);
-            }
-            0x2 => {
-                self.call(operation);
-            }
-            0x3 => {
-                self.skip_equal_immediate(operation);
-            }
-            0x4 => {
-                self.skip_not_equal_immediate(operation);
-            }
-            0x5 => {
-                self.skip_equal_register(operation);
-            }
-            0x6 => {
-                self.load_immediate(operation);
-            }
-            0x7 => {
-                self.add_immediate(operation);
-            }
-            0x8 => match operation.value & 0x000F {
-                0x0 => {
-                    self.move_register(operation);
-                }
-                0x1 => {
-                    self.or_register(operation);
-                }
-                0x2 => {
-                    self.and_register(operation);
-                }
-                0x3 => {
-                    self.xor_register(operation);
+        if let Some((mut record, screen)) = undo_record {
+            record.ram = std::mem::take(&mut self.overwritten_ram);
+            for (plane, (old_rows, new_rows)) in
+                screen.iter().zip(&self.frame_buffer.buffer).enumerate()
+            {
+                for (row, (old, new)) in old_rows.iter().zip(new_rows).enumerate() {
+                    if old != new {
+                        record.rows.push((plane, row, *old));
+                    }
                 }
-                0x4 => {
-                    self.add_register(operation);
-                }
-                0x5 => {
-                    self.subtract_register(operation);
-                }
-                0x6 => {
-                    self.shift_right(operation);
-                }
-                0x7 => {
-                    self.subtract_register_not(operation);
-                }
-                0xE => {
-                    self.shift_left(operation);
-                }
-                _ => {
-                    println!("Instruction failed: 0x{:0x}", operation.value);
-                    self.memory.print_rom();
-                    panic!("Unsupported value!");
-                }
-            },
-            0x9 => match operation.value & 0x000F {
-                0x00 => {
-                    self.skip_not_equal_register(operation);
-                }
-                _ => {
-                    println!("Instruction failed: 0x{:0x}", operation.value);
-                    self.memory.print_rom();
-                    panic!("Unsupported value!");
-                }
-            },
-            0xA => {
-                self.load_address(operation);
             }
-            0xB => {
-                self.jump_register(operation);
-            }
-            0xC => {
-                self.random(operation);
+            record.accesses = self.memory_accesses.clone();
+            record.frames_started = frames_started;
+            self.undo_log.push(record);
+        }
+
+        self.breakpoint_hit =
+            self.find_breakpoint_hit(program_counter, &conditions_held, &self.memory_accesses);
+        Ok(opcode.into())
+    }
+
+    ///Returns for every breakpoint whether it is a condition breakpoint whose condition currently holds.
+    fn conditions_held(&self) -> Vec<bool> {
+        self.breakpoints
+            .iter()
+            .map(|breakpoint| match breakpoint {
+                Breakpoint::Condition(condition) => condition.holds(&self.cpu),
+                _ => false,
+            })
+            .collect()
+    }
+
+    ///Captures the state an instruction may overwrite besides RAM and the screen, which are filled in afterwards.
+    fn start_undo_record(&self) -> UndoRecord {
+        UndoRecord {
+            cpu: self.cpu.clone(),
+            stack: self.memory.stack,
+            drawn_this_frame: self.drawn_this_frame,
+            instructions_this_frame: self.instructions_this_frame,
+            high_resolution: self.frame_buffer.high_resolution,
+            selected_planes: self.frame_buffer.selected_planes,
+            rng: self.rng.state(),
+            keys: self.input.keys,
+            ram: Vec::new(),
+            rows: Vec::new(),
+            accesses: Vec::new(),
+            frames_started: 0,
+        }
+    }
+
+    ///Sets how many of the most recent instructions can be undone. 0, the default, turns off recording them.
+    pub fn set_undo_limit(&mut self, instructions: usize) {
+        self.undo_log.set_limit(instructions);
+    }
+
+    ///Undoes the last instruction executed, leaving the program counter on it. Returns what it had overwritten.
+    fn undo_instruction(&mut self) -> Option<UndoRecord> {
+        let record = self.undo_log.pop()?;
+        for (address, bytes) in record.ram.iter().rev() {
+            self.memory.ram[*address..*address + bytes.len()].copy_from_slice(bytes);
+            self.decode_cache.invalidate(*address, bytes.len());
+        }
+        for &(plane, row, pixels) in &record.rows {
+            self.frame_buffer.buffer[plane][row] = pixels;
+        }
+        self.cpu = record.cpu.clone();
+        self.memory.stack = record.stack;
+        self.drawn_this_frame = record.drawn_this_frame;
+        self.instructions_this_frame = record.instructions_this_frame;
+        self.frame_buffer.high_resolution = record.high_resolution;
+        self.frame_buffer.selected_planes = record.selected_planes;
+        self.rng = Chip8Rng::from_state(record.rng);
+        self.input.keys = record.keys;
+        //The frames the instruction started are undone as well, so their rewind states and movie frames are in the
+        //future now.
+        for _ in 0..record.frames_started {
+            self.rewind_buffer.pop();
+            if let Some(movie) = &mut self.recording {
+                movie.frames.pop();
             }
-            0xD => {
-                self.draw(operation);
+            if let Some(player) = &mut self.player {
+                player.go_back(1);
             }
-            0xE => match operation.value & 0x00FF {
-                0x9E => {
-                    self.skip_pressed(operation);
-                }
-                0xA1 => {
-                    self.skip_not_pressed(operation);
-                }
-                _ => {
-                    println!("Instruction failed: 0x{:0x}", operation.value);
-                    self.memory.print_rom();
-                    panic!("Unsupported value!");
-                }
-            },
-            0xF => match operation.value & 0x00FF {
-                0x07 => {
-                    self.load_delay(operation);
-                }
-                0x0A => {
-                    self.load_keypress(operation);
-                }
-                0x15 => {
-                    self.store_delay(operation);
-                }
-                0x18 => {
-                    self.store_sound(operation);
-                }
-                0x1E => {
-                    self.add_index(operation);
-                }
-                0x29 => {
-                    self.index_sprite(operation);
-                }
-                0x33 => {
-                    self.store_bcd(operation);
-                }
-                0x55 => {
-                    self.store_registers(operation);
-                }
-                0x65 => {
-                    self.load_registers(operation);
-                }
-                _ => {
-                    println!("Instruction failed: 0x{:0x}", operation.value);
-                    panic!("Unsupported value!");
+        }
+        Some(record)
+    }
+
+    ///Undoes up to `instructions` of the most recently executed instructions, see [`Chip8Computer::set_undo_limit`].
+    ///
+    ///Returns how many were undone, which is fewer than asked for if the undo log doesn't go back as far.
+    pub fn step_back(&mut self, instructions: usize) -> usize {
+        self.breakpoint_hit = None;
+        let mut undone = 0;
+        while undone < instructions && self.undo_instruction().is_some() {
+            undone += 1;
+        }
+        if undone > 0 {
+            self.frame_buffer.request_redraw();
+        }
+        undone
+    }
+
+    ///Undoes instructions until a breakpoint is hit going backwards, or the undo log runs out.
+    ///
+    ///Going backwards an address breakpoint is hit once its instruction has been undone, a condition breakpoint
+    ///when undoing makes its condition true and a watchpoint when an instruction accessing its range is undone.
+    ///The hit is available from [`Chip8Computer::breakpoint_hit`]. Returns how many instructions were undone.
+    pub fn reverse_continue(&mut self) -> usize {
+        self.breakpoint_hit = None;
+        let mut undone = 0;
+        while self.breakpoint_hit.is_none() {
+            let conditions_held = self.conditions_held();
+            let record = match self.undo_instruction() {
+                Some(record) => record,
+                None => break,
+            };
+            undone += 1;
+            self.breakpoint_hit = self.find_breakpoint_hit(
+                self.cpu.program_counter,
+                &conditions_held,
+                &record.accesses,
+            );
+        }
+        if undone > 0 {
+            self.frame_buffer.request_redraw();
+        }
+        undone
+    }
+
+    ///Returns the first breakpoint triggered by the instruction that was just executed or undone at the given address.
+    ///`conditions_held` says for every breakpoint whether its condition was already true before the instruction,
+    ///so condition breakpoints only trigger when their condition becomes true. `accesses` is the RAM the instruction
+    ///accessed.
+    fn find_breakpoint_hit(
+        &self,
+        instruction_address: u16,
+        conditions_held: &[bool],
+        accesses: &[(usize, usize, Access)],
+    ) -> Option<BreakpointHit> {
+        let program_counter = self.cpu.program_counter;
+        self.breakpoints
+            .iter()
+            .zip(conditions_held)
+            .find(|(breakpoint, held_before)| match breakpoint {
+                Breakpoint::Address { address, condition } => {
+                    *address == program_counter
+                        && condition.is_none_or(|condition| condition.holds(&self.cpu))
                 }
-            },
-            _ => {
-                println!("Instruction failed: 0x{:0x}", operation.value);
+                Breakpoint::Condition(condition) => !*held_before && condition.holds(&self.cpu),
+                Breakpoint::Watch { .. } => accesses.iter().any(|(address, num_bytes, access)| {
+                    breakpoint.watches(*address, *num_bytes, *access)
+                }),
+            })
+            .map(|(breakpoint, _)| BreakpointHit {
+                breakpoint: *breakpoint,
+                instruction_address,
+                program_counter,
+            })
+    }
 
-                panic!("Unsupported value!");
-            }
+    ///Returns the breakpoint triggered by the last instruction executed by [`Chip8Computer::tick`], if any.
+    ///
+    ///Breakpoints are checked after each instruction, so an address breakpoint is hit when the next instruction is
+    ///the one it is on. Continuing from there executes that instruction without stopping on it again.
+    pub fn breakpoint_hit(&self) -> Option<BreakpointHit> {
+        self.breakpoint_hit
+    }
+
+    ///Returns how many times [`Chip8Computer::vertical_blank`] has been called, going up even when going backwards.
+    pub fn vertical_blanks(&self) -> u64 {
+        self.vertical_blanks
+    }
+
+    ///Adds a breakpoint, unless the same one is already set.
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+        if !self.breakpoints.contains(&breakpoint) {
+            self.breakpoints.push(breakpoint);
+        }
+    }
+
+    ///Removes a breakpoint, returning whether it was set.
+    pub fn remove_breakpoint(&mut self, breakpoint: &Breakpoint) -> bool {
+        let count = self.breakpoints.len();
+        self.breakpoints.retain(|existing| existing != breakpoint);
+        self.breakpoints.len() != count
+    }
+
+    ///Notes that the current instruction accessed RAM, for watchpoints.
+    fn record_memory_access(&mut self, address: usize, num_bytes: usize, access: Access) {
+        if !self.breakpoints.is_empty() || self.undo_log.is_enabled() {
+            self.memory_accesses.push((address, num_bytes, access));
+        }
+    }
+
+    ///Reads bytes from RAM for the current instruction, noting the access for watchpoints.
+    fn read_ram(&mut self, address: usize, num_bytes: usize) -> Vec<u8> {
+        self.record_memory_access(address, num_bytes, Access::Read);
+        self.memory.read_bytes(address, num_bytes)
+    }
+
+    ///Returns an error if any of the given range of addresses lies outside of RAM.
+    fn check_memory_access(
+        &self,
+        starting_address: usize,
+        num_bytes: usize,
+    ) -> Result<(), ExecutionError> {
+        match self.memory.find_out_of_bounds(starting_address, num_bytes) {
+            Some(address) => Err(ExecutionError::MemoryOutOfBounds {
+                program_counter: self.cpu.current_instruction_address(),
+                address,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    ///Moves the program counter past the next instruction.
+    ///XO-CHIP's long load takes up 4 bytes, so that is skipped over entirely.
+    fn skip_next_instruction(&mut self) {
+        let program_counter = self.cpu.program_counter;
+        let is_long_load = self
+            .memory
+            .find_out_of_bounds(program_counter as usize, 2)
+            .is_none()
+            && self.memory.read_instruction(program_counter) == 0xF000;
+        let length = match is_long_load {
+            true => 4,
+            false => 2,
+        };
+        self.cpu.program_counter = program_counter.wrapping_add(length);
+    }
+
+    ///Writes bytes to RAM starting at the given address, forgetting any decoded instructions they overlap.
+    ///
+    ///Panics if the bytes don't fit in RAM, so emulated writes check the range with `check_memory_access` first.
+    pub fn write_ram(&mut self, address: usize, bytes: &[u8]) {
+        if self.undo_log.is_enabled() {
+            let overwritten = self.memory.ram[address..address + bytes.len()].to_vec();
+            self.overwritten_ram.push((address, overwritten));
+        }
+        self.memory.ram[address..address + bytes.len()].copy_from_slice(bytes);
+        self.decode_cache.invalidate(address, bytes.len());
+        self.record_memory_access(address, bytes.len(), Access::Write);
+    }
+
+    ///Forgets every decoded instruction. Needed after changing `memory.ram` directly instead of through
+    ///[`Chip8Computer::write_ram`].
+    pub fn clear_decode_cache(&mut self) {
+        self.decode_cache.clear();
+    }
+
+    ///Switches to XO-CHIP mode, growing RAM to 64 KiB and using XO-CHIP's quirks.
+    pub fn enable_xo_chip(&mut self) {
+        self.memory.extend_to_xo_chip();
+        self.decode_cache.clear();
+        self.quirks = Quirks::xo_chip();
+    }
+
+    ///Decodes and executes a single instruction.
+    pub fn map_operation_to_function(
+        &mut self,
+        operation: &Instruction,
+    ) -> Result<(), ExecutionError> {
+        let opcode = Opcode::try_from(*operation).map_err(|_| ExecutionError::UnknownOpcode {
+            program_counter: self.cpu.current_instruction_address(),
+            instruction: operation.value,
+        })?;
+        self.execute_opcode(opcode)
+    }
+
+    ///Executes an already decoded operation, as if the program counter had just moved past it.
+    pub fn execute_opcode(&mut self, opcode: Opcode) -> Result<(), ExecutionError> {
+        match opcode {
+            Opcode::Cls => self.clear_frame_buffer(),
+            Opcode::Ret => self.return_subroutine()?,
+            Opcode::Scd(n) => self.scroll_down(n),
+            Opcode::Scr => self.scroll_right(),
+            Opcode::Scl => self.scroll_left(),
+            Opcode::Exit => self.exit(),
+            Opcode::Low => self.low_resolution(),
+            Opcode::High => self.high_resolution(),
+            Opcode::Jp(nnn) => self.jump(nnn),
+            Opcode::Call(nnn) => self.call(nnn)?,
+            Opcode::SeImmediate(x, kk) => self.skip_equal_immediate(x, kk),
+            Opcode::SneImmediate(x, kk) => self.skip_not_equal_immediate(x, kk),
+            Opcode::Se(x, y) => self.skip_equal_register(x, y),
+            Opcode::Save(x, y) => self.store_register_range(x, y)?,
+            Opcode::Load(x, y) => self.load_register_range(x, y)?,
+            Opcode::LdImmediate(x, kk) => self.load_immediate(x, kk),
+            Opcode::AddImmediate(x, kk) => self.add_immediate(x, kk),
+            Opcode::Ld(x, y) => self.move_register(x, y),
+            Opcode::Or(x, y) => self.or_register(x, y),
+            Opcode::And(x, y) => self.and_register(x, y),
+            Opcode::Xor(x, y) => self.xor_register(x, y),
+            Opcode::Add(x, y) => self.add_register(x, y),
+            Opcode::Sub(x, y) => self.subtract_register(x, y),
+            Opcode::Shr(x, y) => self.shift_right(x, y),
+            Opcode::Subn(x, y) => self.subtract_register_not(x, y),
+            Opcode::Shl(x, y) => self.shift_left(x, y),
+            Opcode::Sne(x, y) => self.skip_not_equal_register(x, y),
+            Opcode::LdI(nnn) => self.load_address(nnn),
+            Opcode::JpV0(nnn) => self.jump_register(nnn),
+            Opcode::Rnd(x, kk) => self.random(x, kk),
+            Opcode::Drw(x, y, n) => self.draw(x, y, n)?,
+            Opcode::Skp(x) => self.skip_pressed(x),
+            Opcode::Sknp(x) => self.skip_not_pressed(x),
+            Opcode::LdILong => self.load_long_address()?,
+            Opcode::Plane(n) => self.select_planes(n),
+            Opcode::LdVxDt(x) => self.load_delay(x),
+            Opcode::LdVxK(x) => self.load_keypress(x),
+            Opcode::LdDtVx(x) => self.store_delay(x),
+            Opcode::LdStVx(x) => self.store_sound(x),
+            Opcode::AddI(x) => self.add_index(x),
+            Opcode::LdF(x) => self.index_sprite(x),
+            Opcode::LdHf(x) => self.index_big_sprite(x),
+            Opcode::LdB(x) => self.store_bcd(x)?,
+            Opcode::LdIVx(x) => self.store_registers(x)?,
+            Opcode::LdVxI(x) => self.load_registers(x)?,
+            Opcode::LdRVx(x) => self.store_flags(x),
+            Opcode::LdVxR(x) => self.load_flags(x),
         }
+        Ok(())
     }
 
     ///*CLS*:
@@ -207,290 +713,536 @@ impl Chip8Computer {
     }
     ///*RET*:
     ///Returns from the subroutine
-    pub fn return_subroutine(&mut self) {
-        self.cpu.program_counter = self.cpu.pop_stack(&mut self.memory);
+    pub fn return_subroutine(&mut self) -> Result<(), ExecutionError> {
+        self.cpu.program_counter = self.cpu.pop_stack(&mut self.memory)?;
+        Ok(())
+    }
+    ///*SCD*:
+    ///Scrolls the display down by the specified number of pixels.
+    ///0x00Cn
+    pub fn scroll_down(&mut self, n: u8) {
+        self.frame_buffer.scroll_down(n.into());
+    }
+    ///*SCR*:
+    ///Scrolls the display right by 4 pixels.
+    ///0x00FB
+    pub fn scroll_right(&mut self) {
+        self.frame_buffer.scroll_right(4);
+    }
+    ///*SCL*:
+    ///Scrolls the display left by 4 pixels.
+    ///0x00FC
+    pub fn scroll_left(&mut self) {
+        self.frame_buffer.scroll_left(4);
+    }
+    ///*EXIT*:
+    ///Exits the interpreter. The program counter stays on this instruction and execution is paused.
+    ///0x00FD
+    pub fn exit(&mut self) {
+        self.cpu.program_counter = self.cpu.current_instruction_address();
+        self.running = false;
+        let _ = self.response_sender.send(EmulatorResponse::Exited);
+    }
+    ///*LOW*:
+    ///Switches the display to the 64x32 low resolution mode.
+    ///0x00FE
+    pub fn low_resolution(&mut self) {
+        self.frame_buffer.set_high_resolution(false);
+    }
+    ///*HIGH*:
+    ///Switches the display to the 128x64 high resolution mode.
+    ///0x00FF
+    pub fn high_resolution(&mut self) {
+        self.frame_buffer.set_high_resolution(true);
     }
     ///*JP*:
     ///Jumps to the specified address.
-    pub fn jump(&mut self, operation: &Instruction) {
-        self.cpu.program_counter = operation.get_address_immediate();
+    pub fn jump(&mut self, nnn: u16) {
+        self.cpu.program_counter = nnn;
     }
     ///*CALL*:
     ///Calls a subroutine at the specified address
     ///0x2nnn: Puts current PC on the stack and sets PC to nnn.
-    pub fn call(&mut self, operation: &Instruction) {
+    pub fn call(&mut self, nnn: u16) -> Result<(), ExecutionError> {
         self.cpu
-            .push_stack(self.cpu.program_counter, &mut self.memory);
-        self.cpu.program_counter = operation.get_address_immediate();
+            .push_stack(self.cpu.program_counter, &mut self.memory)?;
+        self.cpu.program_counter = nnn;
+        Ok(())
     }
     ///*SE*:
     ///Skips the next instruction if the value in the specified register equals the specified value
     ///0x3xkk: Skips next instruction if Vx == kk.
-    pub fn skip_equal_immediate(&mut self, operation: &Instruction) {
-        let immediate_value = operation.get_immediate();
-        let register_value = self.cpu.data_registers[operation.get_register() as usize];
+    pub fn skip_equal_immediate(&mut self, x: Register, kk: u8) {
+        let immediate_value = kk;
+        let register_value = self.cpu.data_registers[x as usize];
         if immediate_value == register_value {
-            self.cpu.program_counter += 2;
+            self.skip_next_instruction();
         }
     }
     ///*SNE*:
     ///Skips the next instruction if the value in the specified register does not equal the specified value
     ///0x4xkk: Skips next instruction if Vx != kk.
-    pub fn skip_not_equal_immediate(&mut self, operation: &Instruction) {
-        let immediate_value = operation.get_immediate();
-        let register_value = self.cpu.data_registers[operation.get_register() as usize];
+    pub fn skip_not_equal_immediate(&mut self, x: Register, kk: u8) {
+        let immediate_value = kk;
+        let register_value = self.cpu.data_registers[x as usize];
 
         if immediate_value != register_value {
-            self.cpu.program_counter += 2;
+            self.skip_next_instruction();
         }
     }
     ///*SE*:
     ///Skips the next instruction if the values in the two specified registers are equal
     ///0x5xy0: Skips next instruction if Vx == Vy.
-    pub fn skip_equal_register(&mut self, operation: &Instruction) {
-        let register_one_value = self.cpu.data_registers[operation.get_register() as usize];
-        let register_two_value = self.cpu.data_registers[operation.get_second_register() as usize];
+    pub fn skip_equal_register(&mut self, x: Register, y: Register) {
+        let register_one_value = self.cpu.data_registers[x as usize];
+        let register_two_value = self.cpu.data_registers[y as usize];
 
         if register_one_value == register_two_value {
-            self.cpu.program_counter += 2;
+            self.skip_next_instruction();
+        }
+    }
+    ///*SAVE*:
+    ///Stores the values in Vx -> Vy in consecutive memory locations starting at the address in I. I is left unchanged.
+    ///If x is larger than y the registers are stored in descending order.
+    ///0x5xy2
+    pub fn store_register_range(&mut self, x: Register, y: Register) -> Result<(), ExecutionError> {
+        let registers = Self::register_range(x, y);
+        let address = self.cpu.index_register as usize;
+        self.check_memory_access(address, registers.len())?;
+        let values: Vec<u8> = registers
+            .into_iter()
+            .map(|register| self.cpu.data_registers[register])
+            .collect();
+        self.write_ram(address, &values);
+        Ok(())
+    }
+    ///*LOAD*:
+    ///Loads values into Vx -> Vy from consecutive memory locations starting at the address in I. I is left unchanged.
+    ///If x is larger than y the registers are loaded in descending order.
+    ///0x5xy3
+    pub fn load_register_range(&mut self, x: Register, y: Register) -> Result<(), ExecutionError> {
+        let registers = Self::register_range(x, y);
+        let address = self.cpu.index_register as usize;
+        self.check_memory_access(address, registers.len())?;
+        let values = self.read_ram(address, registers.len());
+        for (register, value) in registers.into_iter().zip(values) {
+            self.cpu.data_registers[register] = value;
+        }
+        Ok(())
+    }
+    ///Returns the registers from Vx to Vy, in descending order if x is larger than y.
+    fn register_range(x: Register, y: Register) -> Vec<usize> {
+        let first = x as usize;
+        let last = y as usize;
+        match first <= last {
+            true => (first..=last).collect(),
+            false => (last..=first).rev().collect(),
         }
     }
     ///*LD&:
     ///Loads the given value into the specified register.
     ///0x6xkk: Vx = kk.
-    pub fn load_immediate(&mut self, operation: &Instruction) {
-        self.cpu.data_registers[operation.get_register() as usize] = operation.get_immediate();
+    pub fn load_immediate(&mut self, x: Register, kk: u8) {
+        self.cpu.data_registers[x as usize] = kk;
     }
     ///*ADD*:
     ///Adds the given value to the specified register
     ///0x7xkk Vx = Vx + kk.
-    pub fn add_immediate(&mut self, operation: &Instruction) {
-        let immediate = operation.get_immediate();
-        let register_value = self.cpu.data_registers[operation.get_register() as usize];
+    pub fn add_immediate(&mut self, x: Register, kk: u8) {
+        let immediate = kk;
+        let register_value = self.cpu.data_registers[x as usize];
         let result = register_value.overflowing_add(immediate).0;
-        self.cpu.data_registers[operation.get_register() as usize] = result;
+        self.cpu.data_registers[x as usize] = result;
     }
     /// *LD*:
     ///Moves the value within the second specified register into the first.
     ///0x8xy0: Vx = Vy.
-    pub fn move_register(&mut self, operation: &Instruction) {
-        let moved_value = self.cpu.data_registers[operation.get_second_register() as usize];
-        self.cpu.data_registers[operation.get_register() as usize] = moved_value;
+    pub fn move_register(&mut self, x: Register, y: Register) {
+        let moved_value = self.cpu.data_registers[y as usize];
+        self.cpu.data_registers[x as usize] = moved_value;
     }
     /// *OR*:
     ///Bitwise ors Vx and Vy and stores the result in Vx
     ///0x8xy1: Vx |= Vy.
-    pub fn or_register(&mut self, operation: &Instruction) {
-        let or_value = self.cpu.data_registers[operation.get_second_register() as usize];
-        self.cpu.data_registers[operation.get_register() as usize] |= or_value;
+    pub fn or_register(&mut self, x: Register, y: Register) {
+        let or_value = self.cpu.data_registers[y as usize];
+        self.cpu.data_registers[x as usize] |= or_value;
+        if self.quirks.vf_reset {
+            self.cpu.data_registers[0xF] = 0;
+        }
     }
     /// *AND*:
     ///Bitwise ands Vx and Vy and stores the result in Vx
     ///0x8xy2: Vx &= Vy.
-    pub fn and_register(&mut self, operation: &Instruction) {
-        let and_value = self.cpu.data_registers[operation.get_second_register() as usize];
-        self.cpu.data_registers[operation.get_register() as usize] &= and_value;
+    pub fn and_register(&mut self, x: Register, y: Register) {
+        let and_value = self.cpu.data_registers[y as usize];
+        self.cpu.data_registers[x as usize] &= and_value;
+        if self.quirks.vf_reset {
+            self.cpu.data_registers[0xF] = 0;
+        }
     }
     /// *XOR*:
     ///Bitwise xors Vx and Vy and stores the result in Vx
     ///0x8xy3: Vx ^= Vy.
-    pub fn xor_register(&mut self, operation: &Instruction) {
-        let xor_value = self.cpu.data_registers[operation.get_second_register() as usize];
-        self.cpu.data_registers[operation.get_register() as usize] ^= xor_value;
+    pub fn xor_register(&mut self, x: Register, y: Register) {
+        let xor_value = self.cpu.data_registers[y as usize];
+        self.cpu.data_registers[x as usize] ^= xor_value;
+        if self.quirks.vf_reset {
+            self.cpu.data_registers[0xF] = 0;
+        }
     }
     /// *ADD*:
     ///Adds the contents of two registers together and stores the result in the first
     ///Sets VF to whether or not there is a carry.
     ///0x8xy4: Vx += Vy.
-    pub fn add_register(&mut self, operation: &Instruction) {
-        let first_value = self.cpu.data_registers[operation.get_register() as usize];
-        let second_value = self.cpu.data_registers[operation.get_second_register() as usize];
+    pub fn add_register(&mut self, x: Register, y: Register) {
+        let first_value = self.cpu.data_registers[x as usize];
+        let second_value = self.cpu.data_registers[y as usize];
         let result = first_value.overflowing_add(second_value);
-        self.cpu.data_registers[operation.get_register() as usize] = result.0;
+        self.cpu.data_registers[x as usize] = result.0;
         self.cpu.data_registers[0xF] = result.1 as u8;
     }
     /// *SUB*:
     ///Subtracts the content of register 2 from register 1 and stores the result in the first
     ///Sets VF to the OPPOSITE of whether or not there is a carry.
     ///0x8xy5: Vx -= Vy.
-    pub fn subtract_register(&mut self, operation: &Instruction) {
-        let first_value = self.cpu.data_registers[operation.get_register() as usize];
-        let second_value = self.cpu.data_registers[operation.get_second_register() as usize];
+    pub fn subtract_register(&mut self, x: Register, y: Register) {
+        let first_value = self.cpu.data_registers[x as usize];
+        let second_value = self.cpu.data_registers[y as usize];
         let result = first_value.overflowing_sub(second_value);
-        self.cpu.data_registers[operation.get_register() as usize] = result.0;
+        self.cpu.data_registers[x as usize] = result.0;
         self.cpu.data_registers[0xF] = !result.1 as u8;
     }
     /// *SHR*:
     ///Shifts the value in a register right by one and stores the result. VF is set to the bit that was consumed.
     ///0x8xy6: Vx = Vx >> 1.
-    pub fn shift_right(&mut self, operation: &Instruction) {
-        let second_value = self.cpu.data_registers[operation.get_second_register() as usize];
-
-        self.cpu.data_registers[operation.get_register() as usize] = second_value;
-        let last_bit = self.cpu.data_registers[operation.get_register() as usize] & 0x01;
-        self.cpu.data_registers[operation.get_register() as usize] >>= 1;
+    pub fn shift_right(&mut self, x: Register, y: Register) {
+        if !self.quirks.shift {
+            let second_value = self.cpu.data_registers[y as usize];
+            self.cpu.data_registers[x as usize] = second_value;
+        }
+        let last_bit = self.cpu.data_registers[x as usize] & 0x01;
+        self.cpu.data_registers[x as usize] >>= 1;
         self.cpu.data_registers[0xF] = (last_bit != 0) as u8;
     }
     /// *SUBN*:
     ///Subtracts the content of register 1 from register 2 and stores the result in the first.
     ///0x8xy7 Vx = Vy - Vx.
-    pub fn subtract_register_not(&mut self, operation: &Instruction) {
-        let first_value = self.cpu.data_registers[operation.get_register() as usize];
-        let second_value = self.cpu.data_registers[operation.get_second_register() as usize];
+    pub fn subtract_register_not(&mut self, x: Register, y: Register) {
+        let first_value = self.cpu.data_registers[x as usize];
+        let second_value = self.cpu.data_registers[y as usize];
         let result = second_value.overflowing_sub(first_value);
-        self.cpu.data_registers[operation.get_register() as usize] = result.0;
+        self.cpu.data_registers[x as usize] = result.0;
         self.cpu.data_registers[0xF] = !result.1 as u8;
     }
     /// *SHL*:
     ///Shifts the value in a register left by one and stores the result. VF is set to the bit that was consumed.
     ///0x8xyE: Vx = Vx << 1.
-    pub fn shift_left(&mut self, operation: &Instruction) {
-        let second_value = self.cpu.data_registers[operation.get_second_register() as usize];
-
-        self.cpu.data_registers[operation.get_register() as usize] = second_value;
-        let first_bit = self.cpu.data_registers[operation.get_register() as usize] & 0x80;
-        self.cpu.data_registers[operation.get_register() as usize] <<= 1;
+    pub fn shift_left(&mut self, x: Register, y: Register) {
+        if !self.quirks.shift {
+            let second_value = self.cpu.data_registers[y as usize];
+            self.cpu.data_registers[x as usize] = second_value;
+        }
+        let first_bit = self.cpu.data_registers[x as usize] & 0x80;
+        self.cpu.data_registers[x as usize] <<= 1;
         self.cpu.data_registers[0xF] = (first_bit != 0) as u8;
     }
     /// *SNE*:
     ///Skips the next instruction if the values in the two given registers are not equal
     ///0x9xy0: Skips next instruction if Vx != Vy.
-    pub fn skip_not_equal_register(&mut self, operation: &Instruction) {
-        let first_value = self.cpu.data_registers[operation.get_register() as usize];
-        let second_value = self.cpu.data_registers[operation.get_second_register() as usize];
+    pub fn skip_not_equal_register(&mut self, x: Register, y: Register) {
+        let first_value = self.cpu.data_registers[x as usize];
+        let second_value = self.cpu.data_registers[y as usize];
 
         if second_value != first_value {
-            self.cpu.program_counter += 2;
+            self.skip_next_instruction();
         }
     }
     ///*LD I*:
     ///Loads given large immediate value into I Register
     ///0xAnnn: I = nnn.
-    pub fn load_address(&mut self, operation: &Instruction) {
-        self.cpu.index_register = operation.get_address_immediate();
+    pub fn load_address(&mut self, nnn: u16) {
+        self.cpu.index_register = nnn;
     }
     ///*JP I*:
-    ///Sets PC to nnn + V0
+    ///Sets PC to nnn + V0, or to xnn + Vx with the jump quirk.
     ///0xBnnn: PC = nnn + V0.
-    pub fn jump_register(&mut self, operation: &Instruction) {
-        let jump_address = self.cpu.data_registers[0] as u16 + operation.get_address_immediate();
+    pub fn jump_register(&mut self, nnn: u16) {
+        let offset_register = match self.quirks.jump_with_vx {
+            true => (nnn >> 8) as usize,
+            false => 0,
+        };
+        let jump_address = self.cpu.data_registers[offset_register] as u16 + nnn;
         self.cpu.program_counter = jump_address;
     }
     /// *RND*:
     ///Generates a random 8-bit value, ANDs it with an immediate, and stores the result in Vx.
+    ///The value comes from the machine's seeded generator, see [`Chip8Computer::set_seed`].
     ///0xCxkk: Vx = 0x?? + kk.
-    pub fn random(&mut self, operation: &Instruction) {
-        let mut rng = rand::thread_rng();
-        let random_number: u8 = rand::Rng::gen_range(&mut rng, 0..255);
-        let result = random_number & operation.get_immediate();
-        self.cpu.data_registers[operation.get_register() as usize] = result;
+    pub fn random(&mut self, x: Register, kk: u8) {
+        let random_number = self.rng.next_byte();
+        let result = random_number & kk;
+        self.cpu.data_registers[x as usize] = result;
     }
     /// *DRW*:
     ///Reads n bytes from memory starting at the address in Register I and displays them starting at (Vx, Vy).
     ///Sprites are XORed onto the screen with existing pixels. VF is set to whether any pixels are erased because of this.
+    ///When n is 0 a 16x16 sprite is drawn from 32 bytes instead, two bytes per row.
+    ///When several planes are selected the sprite data for each one follows the previous plane's.
+    ///With the display wait quirk only one sprite is drawn per frame, later draws are retried on the next frame.
     ///0xDxyn
-    pub fn draw(&mut self, operation: &Instruction) {
-        let num_bytes = operation.get_small_immediate();
-        let starting_address = self.cpu.index_register;
-        let draw_bytes = self.memory.read_bytes(starting_address, num_bytes.into());
-        let start_x = self.cpu.data_registers[operation.get_register() as usize];
-        let start_y = self.cpu.data_registers[operation.get_second_register() as usize];
-
-        let result = self.frame_buffer.draw_sprite(start_x, start_y, draw_bytes);
+    pub fn draw(&mut self, x: Register, y: Register, n: u8) -> Result<(), ExecutionError> {
+        if self.quirks.display_wait && self.drawn_this_frame {
+            self.cpu.program_counter = self.cpu.current_instruction_address();
+            return Ok(());
+        }
+        let (num_bytes, sprite_width) = match n {
+            0 => (32, 16),
+            num_bytes => (num_bytes, 8),
+        };
+        let num_bytes = num_bytes as usize * self.frame_buffer.get_selected_planes().len();
+        let starting_address = self.cpu.index_register as usize;
+        self.check_memory_access(starting_address, num_bytes)?;
+        let draw_bytes = self.read_ram(starting_address, num_bytes);
+        let rows = match sprite_width {
+            16 => draw_bytes
+                .chunks_exact(2)
+                .map(|bytes| ((bytes[0] as u16) << 8) | bytes[1] as u16)
+                .collect(),
+            _ => draw_bytes.into_iter().map(u16::from).collect(),
+        };
+        let start_x = self.cpu.data_registers[x as usize];
+        let start_y = self.cpu.data_registers[y as usize];
+
+        let result = self.frame_buffer.draw_sprite(
+            start_x,
+            start_y,
+            rows,
+            sprite_width,
+            self.quirks.clipping,
+        );
         self.cpu.data_registers[0x0F] = result.into();
+        self.drawn_this_frame = true;
+        Ok(())
+    }
+    ///*LD I, long*:
+    ///Loads the 16-bit address stored in the word after this instruction into I.
+    ///0xF000 nnnn: I = nnnn.
+    pub fn load_long_address(&mut self) -> Result<(), ExecutionError> {
+        let address = self.cpu.program_counter;
+        self.check_memory_access(address as usize, 2)?;
+        self.cpu.index_register = self.memory.read_instruction(address);
+        self.cpu.program_counter = self.cpu.program_counter.wrapping_add(2);
+        Ok(())
+    }
+    ///*PLANE*:
+    ///Selects which bitplanes later draw, clear and scroll instructions operate on.
+    ///0xFn01
+    pub fn select_planes(&mut self, planes: u8) {
+        self.frame_buffer.selected_planes = planes;
     }
     /// *SKP*:
     ///Skips the next instruction if the key corresponding to the value in Vx is pressed.
     ///Ex9E
-    pub fn skip_pressed(&mut self, operation: &Instruction) {
-        let key_value = self.cpu.data_registers[operation.get_register() as usize];
+    pub fn skip_pressed(&mut self, x: Register) {
+        let key_value = self.cpu.data_registers[x as usize];
         if self.input.check_pressed(key_value) {
-            self.cpu.program_counter += 2;
+            self.skip_next_instruction();
         }
     }
     /// *SKNP*:
     ///Skips the next instruction if the key corresponding to the value in Vx is not pressed.
     ///ExA1
-    pub fn skip_not_pressed(&mut self, operation: &Instruction) {
-        let key_value = self.cpu.data_registers[operation.get_register() as usize];
+    pub fn skip_not_pressed(&mut self, x: Register) {
+        let key_value = self.cpu.data_registers[x as usize];
         if !self.input.check_pressed(key_value) {
-            self.cpu.program_counter += 2;
+            self.skip_next_instruction();
         }
     }
     /// *LD*:
     ///Loads the value from the delay timer and stores it in Vx
     ///0xFx07 Vx = Delay Timer.
-    pub fn load_delay(&mut self, operation: &Instruction) {
-        self.cpu.data_registers[operation.get_register() as usize] = self.cpu.delay_timer;
+    pub fn load_delay(&mut self, x: Register) {
+        self.cpu.data_registers[x as usize] = self.cpu.delay_timer;
     }
     /// *LD*:
     ///Stores the value of the next keypress in Vx. Execution stops until then.
+    ///Like on the COSMAC VIP, the key only counts once it has been pressed and then released.
+    ///While waiting the program counter stays on this instruction, so timers and commands keep being serviced.
     ///0xFx0A: Vx = Keypress.
-    pub fn load_keypress(&mut self, operation: &Instruction) {
-        self.cpu.data_registers[operation.get_register() as usize] = self.input.receive_input();
+    pub fn load_keypress(&mut self, x: Register) {
+        match self.cpu.waiting_for_key_release {
+            Some(key) if !self.input.check_pressed(key) => {
+                self.cpu.data_registers[x as usize] = key;
+                self.cpu.waiting_for_key_release = None;
+                return;
+            }
+            Some(_) => {}
+            None => self.cpu.waiting_for_key_release = self.input.receive_input(),
+        }
+        self.cpu.program_counter = self.cpu.current_instruction_address();
     }
     /// *LD*:
     ///Sets delay timer to value within specified register.
     ///0xFx15: Delay Timer = Vx.
-    pub fn store_delay(&mut self, operation: &Instruction) {
-        self.cpu.delay_timer = self.cpu.data_registers[operation.get_register() as usize];
+    pub fn store_delay(&mut self, x: Register) {
+        self.cpu.delay_timer = self.cpu.data_registers[x as usize];
     }
     /// *LD ST*:
     ///Sets sound timer to value within specified register.
     ///0xFx18: Sound Timer = Vx.
-    pub fn store_sound(&mut self, operation: &Instruction) {
-        self.cpu.sound_timer = self.cpu.data_registers[operation.get_register() as usize];
+    pub fn store_sound(&mut self, x: Register) {
+        self.cpu.sound_timer = self.cpu.data_registers[x as usize];
     }
     /// *ADD I, Vx*:
     ///Adds I with the value in Vx and stores it in I.
     ///0xFx1E: I += Vx.
-    pub fn add_index(&mut self, operation: &Instruction) {
-        self.cpu.index_register +=
-            self.cpu.data_registers[operation.get_register() as usize] as u16;
+    pub fn add_index(&mut self, x: Register) {
+        self.cpu.index_register = self
+            .cpu
+            .index_register
+            .wrapping_add(self.cpu.data_registers[x as usize] as u16);
     }
     /// *LD F, Vx*:
     ///Stores the address of the sprite in Vx into I.
     ///Practically speaking, this just stores Vx * 5 into I
     ///0xFx29: I = Vx * 5.
-    pub fn index_sprite(&mut self, operation: &Instruction) {
-        self.cpu.index_register =
-            self.cpu.data_registers[operation.get_register() as usize] as u16 * 5;
+    pub fn index_sprite(&mut self, x: Register) {
+        self.cpu.index_register = self.cpu.data_registers[x as usize] as u16 * 5;
+    }
+    /// *LD HF, Vx*:
+    ///Stores the address of the 10 byte tall sprite for the digit in Vx into I.
+    ///0xFx30: I = BIG_FONT_ADDRESS + Vx * 10.
+    pub fn index_big_sprite(&mut self, x: Register) {
+        let digit = self.cpu.data_registers[x as usize] & 0x0F;
+        self.cpu.index_register = BIG_FONT_ADDRESS + digit as u16 * 10;
     }
     /// *LD B, Vx*:
     ///Stores the BCD version of Vx in memory at address I, I+1, & I+2.
     ///0xFx33
-    pub fn store_bcd(&mut self, operation: &Instruction) {
-        let value = self.cpu.data_registers[operation.get_register() as usize];
+    pub fn store_bcd(&mut self, x: Register) -> Result<(), ExecutionError> {
+        let value = self.cpu.data_registers[x as usize];
         let hundreds = value / 100;
         let tens = (value / 10) % 10;
         let ones = value % 10;
 
         let address = self.cpu.index_register as usize;
-        self.memory.ram[address] = hundreds;
-        self.memory.ram[address + 1] = tens;
-        self.memory.ram[address + 2] = ones;
+        self.check_memory_access(address, 3)?;
+        self.write_ram(address, &[hundreds, tens, ones]);
+        Ok(())
     }
     /// *LD [I], Vx*:
     ///Stores values in V0 -> Vx registers in consecutive memory locations starting at the address in I.
+    ///With the memory increment quirk I is left at the address after the last register stored.
     ///0xFx55
-    pub fn store_registers(&mut self, operation: &Instruction) {
-        for i in 0..(operation.get_register() + 1) as usize {
-            let value = self.cpu.data_registers[i];
-            self.memory.ram[self.cpu.index_register as usize + i] = value;
+    pub fn store_registers(&mut self, x: Register) -> Result<(), ExecutionError> {
+        let count = x as usize + 1;
+        self.check_memory_access(self.cpu.index_register as usize, count)?;
+        let values = self.cpu.data_registers;
+        self.write_ram(self.cpu.index_register as usize, &values[..count]);
+        if self.quirks.memory_increment {
+            self.cpu.index_register = self.cpu.index_register.wrapping_add(x as u16 + 1);
         }
+        Ok(())
     }
     /// *LD Vx, [I]
     ///Loads values into V0 -> Vx registers from consecutive memory locations starting at the address in I.
+    ///With the memory increment quirk I is left at the address after the last register loaded.
     ///0xFx65
-    pub fn load_registers(&mut self, operation: &Instruction) {
-        for i in 0..(operation.get_register() + 1) as usize {
-            let value = self.memory.ram[self.cpu.index_register as usize + i];
-            self.cpu.data_registers[i] = value;
+    pub fn load_registers(&mut self, x: Register) -> Result<(), ExecutionError> {
+        let count = x as usize + 1;
+        self.check_memory_access(self.cpu.index_register as usize, count)?;
+        let values = self.read_ram(self.cpu.index_register as usize, count);
+        self.cpu.data_registers[..count].copy_from_slice(&values);
+        if self.quirks.memory_increment {
+            self.cpu.index_register = self.cpu.index_register.wrapping_add(x as u16 + 1);
         }
+        Ok(())
     }
 
-    pub fn load_rom(&mut self, rom_bytes: Vec<u8>) {
-        self.memory.store_rom(rom_bytes);
+    /// *LD R, Vx*:
+    ///Stores values in V0 -> Vx registers into the RPL user flags.
+    ///0xFx75
+    pub fn store_flags(&mut self, x: Register) {
+        let count = x as usize + 1;
+        self.cpu.rpl_flags[..count].copy_from_slice(&self.cpu.data_registers[..count]);
+    }
+    /// *LD Vx, R*:
+    ///Loads values into V0 -> Vx registers from the RPL user flags.
+    ///0xFx85
+    pub fn load_flags(&mut self, x: Register) {
+        let count = x as usize + 1;
+        self.cpu.data_registers[..count].copy_from_slice(&self.cpu.rpl_flags[..count]);
+    }
+
+    ///Copies a ROM into memory at the start of the program space. Nothing is changed if it doesn't fit.
+    pub fn load_rom(&mut self, rom_bytes: Vec<u8>) -> Result<(), RomTooLarge> {
+        let checksum = rom_checksum(&rom_bytes);
+        self.memory.store_rom(rom_bytes)?;
+        self.rom_checksum = checksum;
+        self.decode_cache.clear();
+        self.rewind_buffer.clear();
+        self.undo_log.clear();
+        Ok(())
+    }
+
+    ///Restarts the random number generator from a seed, so that the random numbers Cxkk generates are the same
+    ///every time the program is run with that seed. Without one a random seed is used.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = Chip8Rng::new(seed);
+    }
+
+    ///Returns the seed the random number generator was started with.
+    pub fn seed(&self) -> u64 {
+        self.rng.seed()
+    }
+
+    ///Captures the state of the machine, see [`SaveState`].
+    pub fn snapshot(&self) -> SaveState {
+        SaveState {
+            cpu: self.cpu.clone(),
+            ram: self.memory.ram.clone(),
+            stack: self.memory.stack,
+            frame_buffer: self.frame_buffer.buffer,
+            high_resolution: self.frame_buffer.high_resolution,
+            selected_planes: self.frame_buffer.selected_planes,
+            keys: self.input.keys,
+            quirks: self.quirks,
+            drawn_this_frame: self.drawn_this_frame,
+            instructions_this_frame: self.instructions_this_frame,
+            rng: self.rng.state(),
+        }
+    }
+
+    ///Puts the machine back into a captured state and redraws the screen.
+    pub fn restore(&mut self, state: SaveState) {
+        self.cpu = state.cpu;
+        self.memory.ram = state.ram;
+        self.memory.stack = state.stack;
+        self.frame_buffer.buffer = state.frame_buffer;
+        self.frame_buffer.high_resolution = state.high_resolution;
+        self.frame_buffer.selected_planes = state.selected_planes;
+        self.input.keys = state.keys;
+        self.quirks = state.quirks;
+        self.drawn_this_frame = state.drawn_this_frame;
+        self.instructions_this_frame = state.instructions_this_frame;
+        self.rng = Chip8Rng::from_state(state.rng);
+        self.decode_cache.clear();
+        self.breakpoint_hit = None;
+        self.undo_log.clear();
+        self.frame_buffer.request_redraw();
+    }
+
+    ///Serializes the state of the machine into the save state format.
+    pub fn save_state(&self) -> Vec<u8> {
+        self.snapshot().to_bytes()
+    }
+
+    ///Restores a state written by [`Chip8Computer::save_state`]. Nothing is changed if it can't be read.
+    pub fn load_state(&mut self, bytes: &[u8]) -> Result<(), SaveStateError> {
+        let state = SaveState::from_bytes(bytes)?;
+        self.restore(state);
+        Ok(())
     }
 }
 
@@ -510,17 +1262,165 @@ impl Display for Chip8Computer {
 
 impl ThreadedEmulator for Chip8Computer {
     fn new(sender_from_computer: Sender<EmulatorResponse>) -> Self {
-       Chip8Computer::new(sender_from_computer)
+        Chip8Computer::new(sender_from_computer)
     }
 
-    fn match_received_command(&self, command: EmulatorCommand) -> std::result::Result<(), String> {
+    fn match_received_command(&mut self, command: EmulatorCommand) -> Result<(), String> {
         match command {
-            EmulatorCommand::Go => todo!(),
-            EmulatorCommand::Step(step_count) => todo!(),
-            EmulatorCommand::GetMemory => todo!(),
-            EmulatorCommand::GetRegisters => todo!(),
-            EmulatorCommand::Pause => todo!(),
+            EmulatorCommand::Go => {
+                self.running = true;
+                self.next_tick = Instant::now();
+                self.next_frame = self.next_tick;
+            }
+            EmulatorCommand::Step(step_count) => {
+                self.running = false;
+                for _ in 0..step_count {
+                    self.execute_loop().map_err(|e| e.to_string())?;
+                    if self.breakpoint_hit.is_some() {
+                        break;
+                    }
+                }
+            }
+            EmulatorCommand::GetMemory => {
+                self.send_response(EmulatorResponse::Memory(Box::new(self.memory.clone())))?;
+            }
+            EmulatorCommand::GetRegisters => {
+                self.send_response(EmulatorResponse::Cpu(self.cpu.clone()))?;
+            }
+            EmulatorCommand::Pause => {
+                let was_running = self.running;
+                self.running = false;
+                self.send_response(EmulatorResponse::Paused {
+                    program_counter: self.cpu.program_counter,
+                    was_running,
+                })?;
+            }
+            EmulatorCommand::LoadRom(rom_bytes) => {
+                self.load_rom(rom_bytes).map_err(|e| e.to_string())?;
+            }
+            EmulatorCommand::SetQuirks(quirks) => {
+                self.quirks = quirks;
+            }
+            EmulatorCommand::EnableXoChip => {
+                self.enable_xo_chip();
+            }
+            EmulatorCommand::SetClockSpeed(clock_speed_hz) => {
+                self.set_clock_speed(clock_speed_hz);
+            }
+            EmulatorCommand::SetTimingMode(timing_mode) => {
+                self.timing_mode = timing_mode;
+            }
+            EmulatorCommand::SetSeed(seed) => {
+                self.set_seed(seed);
+            }
+            EmulatorCommand::SetKey { key, pressed } => {
+                self.set_key(key, pressed);
+            }
+            EmulatorCommand::SetPalette(palette) => {
+                self.frame_buffer.palette = palette;
+                self.frame_buffer.request_redraw();
+            }
+            EmulatorCommand::SetDebugOutput(debug_output) => {
+                self.set_debug_output(debug_output);
+            }
+            EmulatorCommand::SetCpu(cpu) => {
+                self.cpu = cpu;
+            }
+            EmulatorCommand::GetScreen => {
+                self.send_response(EmulatorResponse::Screen(
+                    self.frame_buffer.get_buffer_as_string(),
+                ))?;
+            }
+            EmulatorCommand::SaveState => {
+                self.send_response(EmulatorResponse::SaveState(self.save_state()))?;
+            }
+            EmulatorCommand::LoadState(bytes) => {
+                if self.recording.is_some() || self.is_playing() {
+                    return Err(
+                        "Save states can't be loaded while a movie is recorded or played back."
+                            .to_string(),
+                    );
+                }
+                self.load_state(&bytes).map_err(|e| e.to_string())?;
+            }
+            EmulatorCommand::SetRewindFrames(frames) => {
+                self.set_rewind_frames(frames);
+            }
+            EmulatorCommand::Rewind(frames) => {
+                let frames = self.rewind(frames);
+                self.send_response(EmulatorResponse::Rewound { frames })?;
+            }
+            EmulatorCommand::SetUndoLimit(instructions) => {
+                self.set_undo_limit(instructions);
+            }
+            EmulatorCommand::StepBack(instructions) => {
+                self.running = false;
+                let instructions = self.step_back(instructions);
+                self.send_response(EmulatorResponse::SteppedBack { instructions })?;
+            }
+            EmulatorCommand::ReverseContinue => {
+                self.running = false;
+                let instructions = self.reverse_continue();
+                if let Some(hit) = self.breakpoint_hit {
+                    self.send_response(EmulatorResponse::BreakpointHit(hit))?;
+                }
+                self.send_response(EmulatorResponse::SteppedBack { instructions })?;
+            }
+            EmulatorCommand::StartRecording => {
+                self.start_recording();
+            }
+            EmulatorCommand::StopRecording => {
+                let movie = self
+                    .stop_recording()
+                    .ok_or_else(|| "No movie is being recorded.".to_string())?;
+                self.send_response(EmulatorResponse::Movie(movie.to_bytes()))?;
+            }
+            EmulatorCommand::PlayMovie(bytes) => {
+                let movie = Movie::from_bytes(&bytes).map_err(|e| e.to_string())?;
+                self.play_movie(movie).map_err(|e| e.to_string())?;
+            }
+            EmulatorCommand::SetBreakpoint(breakpoint) => {
+                self.add_breakpoint(breakpoint);
+            }
+            EmulatorCommand::ClearBreakpoint(breakpoint) => {
+                self.remove_breakpoint(&breakpoint);
+            }
         }
+        Ok(())
     }
-}
 
+    fn is_running(&self) -> bool {
+        self.running
+    }
+
+    fn run_cycle(&mut self) -> Result<(), String> {
+        let now = Instant::now();
+        //While a movie is played back it decides when frames end instead.
+        if self.timing_mode == TimingMode::RealTime && !self.is_playing() && now >= self.next_frame
+        {
+            self.vertical_blank();
+            self.next_frame += FRAME_PERIOD;
+            if self.next_frame < now {
+                self.next_frame = now + FRAME_PERIOD;
+            }
+        }
+
+        if now < self.next_tick {
+            let wake_time = match self.timing_mode {
+                TimingMode::RealTime => std::cmp::min(self.next_tick, self.next_frame),
+                TimingMode::InstructionsPerFrame(_) => self.next_tick,
+            };
+            std::thread::sleep(wake_time - now);
+            return Ok(());
+        }
+
+        self.execute_loop().map_err(|e| e.to_string())?;
+        self.next_tick += self.tick_period();
+
+        // Don't try to catch up on time lost while the host was busy, just resume at the targeted pace.
+        if self.next_tick + self.tick_period() < now {
+            self.next_tick = now;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,121 @@
+use std::convert::TryInto;
+
+///The magic bytes, the version, the length of the contents and their CRC-32.
+const HEADER_LENGTH: usize = 14;
+
+///Describes why the framing around a file's contents, or the contents' values, couldn't be read.
+///
+///Each format turns these into its own error, so that messages name what was being read.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ContainerError {
+    ///The data doesn't start with the format's magic bytes.
+    WrongMagic,
+    ///The data was written in a format version this build doesn't know.
+    UnsupportedVersion(u16),
+    ///The data ends before the contents do.
+    Truncated,
+    ///The contents don't match their checksum.
+    ChecksumMismatch,
+    ///A value in the contents can't be read as what it should be.
+    Invalid(String),
+}
+
+///Frames the contents of a file with a header: the format's magic bytes, its version, and the length and CRC-32
+///of the contents. Numbers are big-endian.
+pub fn wrap(magic: [u8; 4], version: u16, contents: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(HEADER_LENGTH + contents.len());
+    bytes.extend_from_slice(&magic);
+    bytes.extend_from_slice(&version.to_be_bytes());
+    bytes.extend_from_slice(&(contents.len() as u32).to_be_bytes());
+    bytes.extend_from_slice(&crc32(contents).to_be_bytes());
+    bytes.extend_from_slice(contents);
+    bytes
+}
+
+///Checks the header written by [`wrap`] and the checksum, returning the contents.
+pub fn unwrap(magic: [u8; 4], version: u16, bytes: &[u8]) -> Result<&[u8], ContainerError> {
+    if bytes.len() < magic.len() || bytes[..magic.len()] != magic {
+        return Err(ContainerError::WrongMagic);
+    }
+    let mut header = Reader::new(&bytes[magic.len()..]);
+    let found_version = header.u16()?;
+    if found_version != version {
+        return Err(ContainerError::UnsupportedVersion(found_version));
+    }
+    let length = header.u32()? as usize;
+    let checksum = header.u32()?;
+    let contents = &bytes[HEADER_LENGTH..];
+    if contents.len() < length {
+        return Err(ContainerError::Truncated);
+    }
+    let contents = &contents[..length];
+    match crc32(contents) == checksum {
+        true => Ok(contents),
+        false => Err(ContainerError::ChecksumMismatch),
+    }
+}
+
+///Reads big-endian values from the front of a byte slice.
+pub struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Reader { bytes }
+    }
+
+    pub fn bytes(&mut self, count: usize) -> Result<&'a [u8], ContainerError> {
+        if self.bytes.len() < count {
+            return Err(ContainerError::Truncated);
+        }
+        let (bytes, rest) = self.bytes.split_at(count);
+        self.bytes = rest;
+        Ok(bytes)
+    }
+
+    pub fn array<const N: usize>(&mut self) -> Result<[u8; N], ContainerError> {
+        Ok(self
+            .bytes(N)?
+            .try_into()
+            .expect("Exactly N bytes were read."))
+    }
+
+    pub fn u8(&mut self) -> Result<u8, ContainerError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub fn bool(&mut self) -> Result<bool, ContainerError> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            value => Err(ContainerError::Invalid(format!(
+                "0x{:02X} where a flag was expected.",
+                value
+            ))),
+        }
+    }
+
+    pub fn u16(&mut self) -> Result<u16, ContainerError> {
+        Ok(u16::from_be_bytes(self.array()?))
+    }
+
+    pub fn u32(&mut self) -> Result<u32, ContainerError> {
+        Ok(u32::from_be_bytes(self.array()?))
+    }
+}
+
+///Computes the CRC-32 used by zip and PNG.
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = match crc & 1 {
+                1 => (crc >> 1) ^ 0xEDB8_8320,
+                _ => crc >> 1,
+            };
+        }
+    }
+    !crc
+}
@@ -1,7 +1,9 @@
 use std::fmt::Display;
 
+use crate::error::ExecutionError;
 use crate::memory::Memory;
 
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Cpu {
     pub data_registers: [u8; 16],
     pub index_register: u16,
@@ -9,6 +11,10 @@ pub struct Cpu {
     pub delay_timer: u8,
     pub sound_timer: u8,
     pub program_counter: u16,
+    ///The HP-48 RPL user flags that SUPER-CHIP programs can save registers to.
+    pub rpl_flags: [u8; 16],
+    ///The key that Fx0A saw pressed and is waiting to be released, if any.
+    pub waiting_for_key_release: Option<u8>,
 }
 impl Cpu {
     pub fn new() -> Self {
@@ -19,23 +25,39 @@ impl Cpu {
             delay_timer: 0,
             sound_timer: 0,
             program_counter: 0x200,
+            rpl_flags: [0; 16],
+            waiting_for_key_release: None,
         }
     }
 
+    ///Returns the address of the instruction currently being executed.
+    ///
+    ///The program counter is advanced as soon as an instruction is fetched, so this is the word before it.
+    pub fn current_instruction_address(&self) -> u16 {
+        self.program_counter.wrapping_sub(2)
+    }
+
     ///Pushes the given value to the stack and increments the stack pointer
-    pub fn push_stack(&mut self, value: u16, memory: &mut Memory) {
-        if self.stack_pointer >= 16 {
-            panic!("Stack overflow!");
-        } else {
-            memory.stack[self.stack_pointer as usize] = value;
-            self.stack_pointer += 1;
+    pub fn push_stack(&mut self, value: u16, memory: &mut Memory) -> Result<(), ExecutionError> {
+        if self.stack_pointer as usize >= memory.stack.len() {
+            return Err(ExecutionError::StackOverflow {
+                program_counter: self.current_instruction_address(),
+            });
         }
+        memory.stack[self.stack_pointer as usize] = value;
+        self.stack_pointer += 1;
+        Ok(())
     }
 
-    pub fn pop_stack(&mut self, memory: &mut Memory) -> u16 {
+    ///Decrements the stack pointer and returns the value it now points to
+    pub fn pop_stack(&mut self, memory: &mut Memory) -> Result<u16, ExecutionError> {
+        if self.stack_pointer == 0 {
+            return Err(ExecutionError::StackUnderflow {
+                program_counter: self.current_instruction_address(),
+            });
+        }
         self.stack_pointer -= 1;
-        let stack_frame = memory.stack[self.stack_pointer as usize];
-        stack_frame
+        Ok(memory.stack[self.stack_pointer as usize])
     }
 }
 
@@ -44,7 +66,7 @@ impl Display for Cpu {
         let mut string = "CPU: \n".to_string();
         string.push_str(&format!("PROGRAM COUNTER: {}\n", self.program_counter));
         string.push_str(&format!("STACK POINTER: {}\n", self.stack_pointer));
-        for (i, chunk) in self.data_registers.chunks(4).into_iter().enumerate() {
+        for (i, chunk) in self.data_registers.chunks(4).enumerate() {
             for (j, register) in chunk.iter().enumerate() {
                 let register_start = format!("V{}:", (i * 4 + j));
                 string.push_str(&format!(
@@ -59,6 +81,9 @@ impl Display for Cpu {
         string.push_str(&format!("INDEX REGISTER: {}\n", self.index_register));
         string.push_str(&format!("DELAY TIMER: {}\n", self.delay_timer));
         string.push_str(&format!("SOUND TIMER: {}\n", self.sound_timer));
+        if let Some(key) = self.waiting_for_key_release {
+            string.push_str(&format!("WAITING FOR KEY RELEASE: {:X}\n", key));
+        }
 
         write!(f, "{}", string)
     }
@@ -85,7 +85,7 @@ lazy_static! {
     pub static ref DEBUG_DATA: Mutex<DebugData> = Mutex::new(DebugData::default());
 }
 
-fn panic_with_debug_details(panic_info: &panic::PanicInfo) {
+fn panic_with_debug_details(panic_info: &panic::PanicHookInfo) {
     let debug = DEBUG_DATA.lock().unwrap();
     println!("{}", debug);
 
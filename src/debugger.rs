@@ -0,0 +1,542 @@
+use std::convert::TryFrom;
+use std::io::{BufRead, Write};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::time::Duration;
+
+use crate::breakpoint::{Access, Breakpoint, Condition};
+use crate::cpu::Cpu;
+use crate::instruction::Instruction;
+use crate::memory::Memory;
+use crate::opcode::Opcode;
+use crate::threading::{EmulatorCommand, EmulatorResponse};
+
+pub const HELP: &str = "\
+Commands:
+  b, break                  List the breakpoints and watchpoints with their numbers
+  b, break <addr> [if <c>]  Stop before the instruction at the address, only when the condition holds if
+                            one is given
+  b, break if <c>           Stop after the instruction that makes the condition true, e.g. b if V5 == 3
+  watch <addr> [<len>] [r|w|rw]
+                            Stop after an instruction reads or writes len bytes of RAM from the address,
+                            1 byte and either kind of access by default
+  delete <n>                Remove the breakpoint or watchpoint with the number
+  s, step [<n>]             Execute n instructions, 1 by default
+  c, continue               Run until a breakpoint is reached or the program stops, press Enter
+                            or type another command to pause sooner
+  rs, rstep [<n>]           Undo the last n instructions, 1 by default
+  rc, rcontinue             Undo instructions until a breakpoint is reached going backwards
+  r, regs                   Show the registers
+  m, mem <addr> <len>       Show len bytes of memory starting at the address
+  stack                     Show the return addresses on the stack, most recent first
+  d, disasm [<addr>] [<n>]  Disassemble n instructions from the address, 10 from the PC by default
+  set <reg> <value>         Set V0-VF, I, PC, SP, DT or ST
+  rewind [<n>]              Go back to the start of the frame n frames ago, 1 by default, which is
+                            the start of the current frame
+  screen                    Show the screen
+  h, help                   Print this message
+  q, quit                   Exit
+Conditions compare a register with a value using one of == != < <= > >=.
+Numbers are decimal, or hexadecimal with a 0x prefix.
+";
+
+const DISCONNECTED: &str = "The emulator has stopped.";
+
+///An interactive debugger that controls an emulator running on another thread.
+///
+///It only talks to the emulator through its command and response channels, so it works the same whether or not
+///a window is showing the screen as well.
+pub struct Debugger {
+    sender: Sender<EmulatorCommand>,
+    receiver: Receiver<EmulatorResponse>,
+    breakpoints: Vec<Breakpoint>,
+    ///Lines typed on the terminal. Any line typed while the program runs freely pauses it.
+    input: Option<Receiver<String>>,
+    ///A command typed while the program was running, which is executed once it has paused.
+    pending_line: Option<String>,
+    ///Things the emulator reported on its own, such as the program exiting, shown with the next output.
+    notices: Vec<String>,
+}
+
+impl Debugger {
+    ///Creates a debugger for the emulator behind the channels, which should be paused.
+    pub fn new(sender: Sender<EmulatorCommand>, receiver: Receiver<EmulatorResponse>) -> Self {
+        Debugger {
+            sender,
+            receiver,
+            breakpoints: Vec::new(),
+            input: None,
+            pending_line: None,
+            notices: Vec::new(),
+        }
+    }
+
+    ///Reads commands from stdin and executes them until `quit` or the end of input.
+    pub fn run(mut self) {
+        let (line_sender, lines) = channel();
+        std::thread::spawn(move || {
+            for line in std::io::stdin().lock().lines().map_while(Result::ok) {
+                if line_sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        self.input = Some(lines);
+
+        println!("Type help for a list of commands.");
+        match self.location() {
+            Ok(location) => print!("{}", location),
+            Err(message) => println!("{}", message),
+        }
+        loop {
+            print!("(chip8) ");
+            let line = match self.pending_line.take() {
+                Some(line) => {
+                    println!("{}", line);
+                    line
+                }
+                None => {
+                    let _ = std::io::stdout().flush();
+                    match self.input.as_ref().map(Receiver::recv) {
+                        Some(Ok(line)) => line,
+                        _ => break,
+                    }
+                }
+            };
+            match line.trim() {
+                "" => continue,
+                "q" | "quit" => break,
+                line => match self.execute(line) {
+                    Ok(output) => print!("{}", output),
+                    Err(message) => println!("{}", message),
+                },
+            }
+        }
+    }
+
+    ///Executes a single command, returning what it printed or why it failed.
+    pub fn execute(&mut self, line: &str) -> Result<String, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let output = match words.as_slice() {
+            ["h"] | ["help"] => Ok(HELP.to_string()),
+            ["b"] | ["break"] => Ok(self.list_breakpoints()),
+            ["b", "if", condition @ ..] | ["break", "if", condition @ ..] => {
+                let condition = condition.join(" ").parse()?;
+                self.add_breakpoint(Breakpoint::Condition(condition))
+            }
+            ["b", address] | ["break", address] => self.add_breakpoint(Breakpoint::Address {
+                address: parse_address(address)?,
+                condition: None,
+            }),
+            ["b", address, "if", condition @ ..] | ["break", address, "if", condition @ ..] => {
+                let condition: Condition = condition.join(" ").parse()?;
+                self.add_breakpoint(Breakpoint::Address {
+                    address: parse_address(address)?,
+                    condition: Some(condition),
+                })
+            }
+            ["watch", address, options @ ..] => {
+                let watchpoint = parse_watchpoint(address, options)?;
+                self.add_breakpoint(watchpoint)
+            }
+            ["delete", number] => self.delete_breakpoint(number),
+            ["s"] | ["step"] => self.step(1),
+            ["s", count] | ["step", count] => match parse_number(count)? {
+                count @ 1..=0xFFFF_FFFF => self.step(count),
+                _ => Err("The number of instructions to step has to be at least 1.".to_string()),
+            },
+            ["c"] | ["continue"] => self.continue_running(),
+            ["rs"] | ["rstep"] => self.step_back(1),
+            ["rs", count] | ["rstep", count] => match parse_number(count)? {
+                0 => Err("The number of instructions to undo has to be at least 1.".to_string()),
+                count => self.step_back(count as usize),
+            },
+            ["rc"] | ["rcontinue"] => self.reverse_continue(),
+            ["r"] | ["regs"] => Ok(format_registers(&self.get_cpu()?)),
+            ["m", address, length] | ["mem", address, length] => self.view_memory(address, length),
+            ["stack"] => self.view_stack(),
+            ["d"] | ["disasm"] => {
+                let program_counter = self.get_cpu()?.program_counter;
+                self.disassemble(program_counter, 10)
+            }
+            ["d", address] | ["disasm", address] => self.disassemble(parse_address(address)?, 10),
+            ["d", address, count] | ["disasm", address, count] => {
+                let count = parse_number(count)? as usize;
+                self.disassemble(parse_address(address)?, count)
+            }
+            ["set", register, value] => self.set_register(register, value),
+            ["rewind"] => self.rewind(1),
+            ["rewind", count] => match parse_number(count)? {
+                0 => Err("The number of frames to rewind has to be at least 1.".to_string()),
+                count => self.rewind(count as usize),
+            },
+            ["screen"] => self.request(EmulatorCommand::GetScreen, |response| match response {
+                EmulatorResponse::Screen(screen) => Ok(screen),
+                response => Err(response),
+            }),
+            _ => Err(format!(
+                "Unknown command '{}', type help for a list of commands.",
+                line
+            )),
+        };
+
+        let notices: String = self.notices.drain(..).map(|notice| notice + "\n").collect();
+        output.map(|output| notices + &output)
+    }
+
+    fn send(&self, command: EmulatorCommand) -> Result<(), String> {
+        self.sender
+            .send(command)
+            .map_err(|_| DISCONNECTED.to_string())
+    }
+
+    ///Sends a command and waits for the response that `extract` accepts.
+    ///Other responses are the emulator reporting things on its own, which are kept as notices.
+    fn request<T>(
+        &mut self,
+        command: EmulatorCommand,
+        extract: impl Fn(EmulatorResponse) -> Result<T, EmulatorResponse>,
+    ) -> Result<T, String> {
+        self.send(command)?;
+        loop {
+            let response = self.receiver.recv().map_err(|_| DISCONNECTED.to_string())?;
+            match extract(response) {
+                Ok(value) => return Ok(value),
+                Err(response) => self.notice(response),
+            }
+        }
+    }
+
+    fn notice(&mut self, response: EmulatorResponse) {
+        match response {
+            EmulatorResponse::Halted(e) => self.notices.push(format!("Halted: {}", e)),
+            EmulatorResponse::Exited => self.notices.push("The program exited.".to_string()),
+            EmulatorResponse::BreakpointHit(hit) => self.notices.push(hit.to_string()),
+            EmulatorResponse::MovieDesync(desync) => self.notices.push(desync.to_string()),
+            EmulatorResponse::MovieFinished(playback) => self.notices.push(playback.to_string()),
+            _ => {}
+        }
+    }
+
+    fn get_cpu(&mut self) -> Result<Cpu, String> {
+        self.request(EmulatorCommand::GetRegisters, |response| match response {
+            EmulatorResponse::Cpu(cpu) => Ok(cpu),
+            response => Err(response),
+        })
+    }
+
+    fn get_memory(&mut self) -> Result<Box<Memory>, String> {
+        self.request(EmulatorCommand::GetMemory, |response| match response {
+            EmulatorResponse::Memory(memory) => Ok(memory),
+            response => Err(response),
+        })
+    }
+
+    ///Describes the instruction that will be executed next.
+    fn location(&mut self) -> Result<String, String> {
+        let program_counter = self.get_cpu()?.program_counter;
+        self.disassemble(program_counter, 1)
+    }
+
+    fn step(&mut self, count: u32) -> Result<String, String> {
+        self.send(EmulatorCommand::Step(count))?;
+        self.location()
+    }
+
+    fn step_back(&mut self, count: usize) -> Result<String, String> {
+        let undone = self.request(
+            EmulatorCommand::StepBack(count),
+            |response| match response {
+                EmulatorResponse::SteppedBack { instructions } => Ok(instructions),
+                response => Err(response),
+            },
+        )?;
+        let location = self.location()?;
+        match undone {
+            0 => Err("There are no instructions to undo.".to_string()),
+            undone if undone < count => Ok(format!(
+                "Undid as many instructions as the undo log holds, {} of {}.\n{}",
+                undone, count, location
+            )),
+            _ => Ok(location),
+        }
+    }
+
+    ///Goes backwards until a breakpoint is reached, which is reported as a notice like when running forwards.
+    fn reverse_continue(&mut self) -> Result<String, String> {
+        self.send(EmulatorCommand::ReverseContinue)?;
+        let mut hit_breakpoint = false;
+        loop {
+            match self.receiver.recv().map_err(|_| DISCONNECTED.to_string())? {
+                EmulatorResponse::SteppedBack { .. } => break,
+                response => {
+                    hit_breakpoint |= matches!(response, EmulatorResponse::BreakpointHit(_));
+                    self.notice(response);
+                }
+            }
+        }
+        let location = self.location()?;
+        match hit_breakpoint {
+            true => Ok(location),
+            false => Ok(format!("Reached the start of the undo log.\n{}", location)),
+        }
+    }
+
+    fn rewind(&mut self, count: usize) -> Result<String, String> {
+        let frames = self.request(EmulatorCommand::Rewind(count), |response| match response {
+            EmulatorResponse::Rewound { frames } => Ok(frames),
+            response => Err(response),
+        })?;
+        let location = self.location()?;
+        match frames {
+            0 => Err("There is no history to rewind through.".to_string()),
+            frames if frames < count => Ok(format!(
+                "Rewound as far back as the history goes, {} of {} frames.\n{}",
+                frames, count, location
+            )),
+            _ => Ok(location),
+        }
+    }
+
+    ///Runs freely until a breakpoint is reached, the program exits or fails, or a line is typed.
+    fn continue_running(&mut self) -> Result<String, String> {
+        self.send(EmulatorCommand::Go)?;
+        loop {
+            match self.receiver.recv_timeout(Duration::from_millis(10)) {
+                Ok(EmulatorResponse::Paused { .. }) => break,
+                Ok(response @ EmulatorResponse::Halted(_))
+                | Ok(response @ EmulatorResponse::Exited)
+                | Ok(response @ EmulatorResponse::BreakpointHit(_)) => {
+                    self.notice(response);
+                    break;
+                }
+                Ok(_) | Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return Err(DISCONNECTED.to_string()),
+            }
+            if let Some(Ok(line)) = self.input.as_ref().map(Receiver::try_recv) {
+                self.send(EmulatorCommand::Pause)?;
+                if !line.trim().is_empty() {
+                    self.pending_line = Some(line);
+                }
+            }
+        }
+        self.location()
+    }
+
+    fn add_breakpoint(&mut self, breakpoint: Breakpoint) -> Result<String, String> {
+        if self.breakpoints.contains(&breakpoint) {
+            return Err(format!("Breakpoint {} is already set.", breakpoint));
+        }
+        self.send(EmulatorCommand::SetBreakpoint(breakpoint))?;
+        self.breakpoints.push(breakpoint);
+        Ok(format!(
+            "Breakpoint {} set: {}.\n",
+            self.breakpoints.len(),
+            breakpoint
+        ))
+    }
+
+    fn delete_breakpoint(&mut self, number: &str) -> Result<String, String> {
+        let index = match parse_number(number)? as usize {
+            number @ 1.. if number <= self.breakpoints.len() => number - 1,
+            _ => return Err(format!("There is no breakpoint {}.", number)),
+        };
+        let breakpoint = self.breakpoints.remove(index);
+        self.send(EmulatorCommand::ClearBreakpoint(breakpoint))?;
+        Ok(format!("Breakpoint {} removed: {}.\n", number, breakpoint))
+    }
+
+    fn list_breakpoints(&self) -> String {
+        match self.breakpoints.is_empty() {
+            true => "There are no breakpoints.\n".to_string(),
+            false => self
+                .breakpoints
+                .iter()
+                .enumerate()
+                .map(|(i, breakpoint)| format!("{}: {}\n", i + 1, breakpoint))
+                .collect(),
+        }
+    }
+
+    fn has_address_breakpoint(&self, address: u16) -> bool {
+        self.breakpoints.iter().any(|breakpoint| match breakpoint {
+            Breakpoint::Address {
+                address: breakpoint_address,
+                ..
+            } => *breakpoint_address == address,
+            _ => false,
+        })
+    }
+
+    fn view_memory(&mut self, address: &str, length: &str) -> Result<String, String> {
+        let address = parse_number(address)? as usize;
+        let length = parse_number(length)? as usize;
+        let memory = self.get_memory()?;
+        if length == 0 || address + length > memory.ram.len() {
+            return Err(format!(
+                "The range has to be inside the 0x{:X} bytes of RAM.",
+                memory.ram.len()
+            ));
+        }
+        Ok(memory.view_memory_section(address, address + length))
+    }
+
+    fn view_stack(&mut self) -> Result<String, String> {
+        let stack_pointer = self.get_cpu()?.stack_pointer as usize;
+        let stack = self.get_memory()?.get_stack();
+        match stack_pointer {
+            0 => Ok("The stack is empty.\n".to_string()),
+            _ => Ok(stack[..stack_pointer]
+                .iter()
+                .rev()
+                .map(|address| format!("0x{:04X}\n", address))
+                .collect()),
+        }
+    }
+
+    ///Lists `count` instructions from the address, marking the program counter with `>` and breakpoints with `*`.
+    fn disassemble(&mut self, address: u16, count: usize) -> Result<String, String> {
+        let program_counter = self.get_cpu()?.program_counter;
+        let memory = self.get_memory()?;
+        let mut listing = String::new();
+        let mut address = address as usize;
+        for _ in 0..count {
+            if memory.find_out_of_bounds(address, 2).is_some() {
+                break;
+            }
+            let value = memory.read_instruction(address as u16);
+            let (text, length) = match Opcode::try_from(Instruction::new(value)) {
+                Ok(Opcode::LdILong) if memory.find_out_of_bounds(address, 4).is_none() => {
+                    let long_address = memory.read_instruction(address as u16 + 2);
+                    (format!("LD I, long 0x{:04X}", long_address), 4)
+                }
+                Ok(opcode) => (opcode.to_string(), 2),
+                Err(_) => (format!("dw 0x{:04X}", value), 2),
+            };
+            let marker = match address == program_counter as usize {
+                true => '>',
+                false => ' ',
+            };
+            let breakpoint = match self.has_address_breakpoint(address as u16) {
+                true => '*',
+                false => ' ',
+            };
+            listing.push_str(&format!(
+                "{}{} 0x{:04X}: {:04X}  {}\n",
+                marker, breakpoint, address, value, text
+            ));
+            address += length;
+        }
+        Ok(listing)
+    }
+
+    fn set_register(&mut self, register: &str, value: &str) -> Result<String, String> {
+        let register = register.to_uppercase();
+        let data_register = register
+            .strip_prefix('V')
+            .filter(|digit| digit.len() == 1)
+            .and_then(|digit| usize::from_str_radix(digit, 16).ok());
+        let max = match (register.as_str(), data_register) {
+            ("I", _) | ("PC", _) => 0xFFFF,
+            ("SP", _) => 16,
+            ("DT", _) | ("ST", _) | (_, Some(_)) => 0xFF,
+            _ => {
+                return Err(format!(
+                    "Unknown register '{}', expected V0-VF, I, PC, SP, DT or ST.",
+                    register
+                ))
+            }
+        };
+        let value = parse_number(value)?;
+        if value > max {
+            return Err(format!(
+                "{} can be at most 0x{:X}, but was given 0x{:X}.",
+                register, max, value
+            ));
+        }
+
+        let mut cpu = self.get_cpu()?;
+        match (register.as_str(), data_register) {
+            ("I", _) => cpu.index_register = value as u16,
+            ("PC", _) => cpu.program_counter = value as u16,
+            ("SP", _) => cpu.stack_pointer = value as u8,
+            ("DT", _) => cpu.delay_timer = value as u8,
+            ("ST", _) => cpu.sound_timer = value as u8,
+            (_, Some(index)) => cpu.data_registers[index] = value as u8,
+            _ => unreachable!("The register was checked above."),
+        }
+        self.send(EmulatorCommand::SetCpu(cpu))?;
+        Ok(format!("{} = 0x{:X}\n", register, value))
+    }
+}
+
+fn format_registers(cpu: &Cpu) -> String {
+    let mut string = format!(
+        "PC 0x{:04X}  I 0x{:04X}  SP {}  DT {}  ST {}\n",
+        cpu.program_counter,
+        cpu.index_register,
+        cpu.stack_pointer,
+        cpu.delay_timer,
+        cpu.sound_timer
+    );
+    for (row, registers) in cpu.data_registers.chunks(8).enumerate() {
+        let registers: Vec<String> = registers
+            .iter()
+            .enumerate()
+            .map(|(i, value)| format!("V{:X} {:02X}", row * 8 + i, value))
+            .collect();
+        string.push_str(&registers.join("  "));
+        string.push('\n');
+    }
+    if let Some(key) = cpu.waiting_for_key_release {
+        string.push_str(&format!("Waiting for key {:X} to be released\n", key));
+    }
+    string
+}
+
+fn parse_number(text: &str) -> Result<u32, String> {
+    let parsed = match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => text.parse(),
+    };
+    parsed.map_err(|_| format!("Invalid number '{}'.", text))
+}
+
+///Parses the arguments of `watch`: an address, optionally followed by a length and the kind of access.
+fn parse_watchpoint(address: &str, options: &[&str]) -> Result<Breakpoint, String> {
+    let start = parse_address(address)?;
+    let (length, access) = match options {
+        [] => ("1", "rw"),
+        [access @ "r"] | [access @ "w"] | [access @ "rw"] => ("1", *access),
+        [length] => (*length, "rw"),
+        [length, access] => (*length, *access),
+        _ => return Err("Expected watch <addr> [<len>] [r|w|rw].".to_string()),
+    };
+    let access = match access {
+        "r" => Access::Read,
+        "w" => Access::Write,
+        "rw" => Access::ReadWrite,
+        _ => return Err(format!("Unknown access '{}', expected r, w or rw.", access)),
+    };
+    let end = match parse_number(length)? {
+        0 => return Err("The length of a watchpoint has to be at least 1.".to_string()),
+        length => (start as u32).saturating_add(length - 1),
+    };
+    if end > 0xFFFF {
+        return Err(format!(
+            "{} bytes from 0x{:04X} is past the end of RAM.",
+            length, start
+        ));
+    }
+    Ok(Breakpoint::Watch {
+        start,
+        end: end as u16,
+        access,
+    })
+}
+
+fn parse_address(text: &str) -> Result<u16, String> {
+    match parse_number(text)? {
+        address @ 0..=0xFFFF => Ok(address as u16),
+        _ => Err(format!("{} is not a valid address.", text)),
+    }
+}
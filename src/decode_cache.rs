@@ -0,0 +1,52 @@
+use std::convert::TryFrom;
+
+use crate::instruction::Instruction;
+use crate::memory::Memory;
+use crate::opcode::{InvalidInstruction, Opcode};
+
+///Remembers the decoded operation for every address that has been executed, so hot loops skip fetching and
+///decoding.
+///
+///Entries are keyed by the address of the instruction's first byte. Anything that writes to RAM must call
+///[`DecodeCache::invalidate`] for the bytes it changed, or [`DecodeCache::clear`] when it replaces RAM wholesale.
+#[derive(Clone, Debug, Default)]
+pub struct DecodeCache {
+    entries: Vec<Option<Opcode>>,
+}
+
+impl DecodeCache {
+    pub fn new() -> Self {
+        DecodeCache::default()
+    }
+
+    ///Returns the operation at the address, decoding it from memory if it isn't cached yet.
+    ///
+    ///The caller must have checked that both bytes of the instruction lie inside RAM.
+    pub fn decode(&mut self, memory: &Memory, address: u16) -> Result<Opcode, InvalidInstruction> {
+        let index = address as usize;
+        if self.entries.len() != memory.ram.len() {
+            self.entries = vec![None; memory.ram.len()];
+        }
+        if let Some(opcode) = self.entries[index] {
+            return Ok(opcode);
+        }
+        let opcode = Opcode::try_from(Instruction::new(memory.read_instruction(address)))?;
+        self.entries[index] = Some(opcode);
+        Ok(opcode)
+    }
+
+    ///Forgets every instruction that overlaps the given range of bytes.
+    pub fn invalidate(&mut self, starting_address: usize, num_bytes: usize) {
+        //The instruction starting one byte earlier has its second byte in the range.
+        let start = starting_address.saturating_sub(1);
+        let end = std::cmp::min(starting_address + num_bytes, self.entries.len());
+        for entry in self.entries.iter_mut().take(end).skip(start) {
+            *entry = None;
+        }
+    }
+
+    ///Forgets every decoded instruction.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
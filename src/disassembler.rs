@@ -0,0 +1,243 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryFrom;
+
+use crate::instruction::Instruction;
+use crate::memory::PROGRAM_START;
+use crate::opcode::Opcode;
+
+///How many data bytes are put on a single `db` line.
+const BYTES_PER_DATA_LINE: usize = 8;
+
+///Returns the assembly for a single instruction, e.g. `LD V3, 0x1F` or `DRW V0, V1, 5`.
+///
+///Returns `None` if the instruction doesn't decode to any operation. XO-CHIP's long load is followed by its address
+///in the next word, which isn't known here, so it is shown as `LD I, long` without one.
+pub fn disassemble(instruction: &Instruction) -> Option<String> {
+    Opcode::try_from(*instruction)
+        .ok()
+        .map(|opcode| opcode.to_string())
+}
+
+///What a label marks, which decides its name.
+///Later variants win when an address is referred to in several ways.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum LabelKind {
+    Data,
+    Jump,
+    Subroutine,
+}
+
+///A line of the listing, covering `length` bytes of the ROM from `offset`.
+enum Line {
+    Code { offset: usize, length: usize },
+    Data { offset: usize, length: usize },
+}
+
+///Disassembles a whole ROM into a listing that separates code from data.
+///
+///Code is found by following execution from the start of the program through jumps, calls and skips.
+///Everything that is never reached is shown as `db` data. Jump and call targets get `label_`/`sub_` labels
+///and addresses loaded into I get `data_` labels, as long as they point at the start of a line.
+pub fn disassemble_rom(rom: &[u8]) -> String {
+    let (code, labels) = trace_code(rom);
+    let lines = split_lines(rom, &code, &labels);
+
+    let line_starts: BTreeSet<u16> = lines
+        .iter()
+        .map(|line| match line {
+            Line::Code { offset, .. } | Line::Data { offset, .. } => address_of(*offset),
+        })
+        .collect();
+    let label_name = |address: u16| -> Option<String> {
+        let kind = labels.get(&address)?;
+        if !line_starts.contains(&address) {
+            return None;
+        }
+        Some(match kind {
+            LabelKind::Data => format!("data_{:03X}", address),
+            LabelKind::Jump => format!("label_{:03X}", address),
+            LabelKind::Subroutine => format!("sub_{:03X}", address),
+        })
+    };
+    let address_name =
+        |address: u16| label_name(address).unwrap_or_else(|| format!("0x{:03X}", address));
+
+    let mut rows = Vec::new();
+    for line in lines {
+        let (offset, length, text) = match line {
+            Line::Code { offset, length } => {
+                let opcode = read_opcode(rom, offset)
+                    .expect("Only instructions that decode are traced as code.");
+                let text = match opcode {
+                    Opcode::LdILong => format!(
+                        "LD I, long {}",
+                        address_name(read_instruction(rom, offset + 2).value)
+                    ),
+                    _ => opcode.to_assembly(&address_name),
+                };
+                (offset, length, text)
+            }
+            Line::Data { offset, length } => {
+                let bytes: Vec<String> = rom[offset..offset + length]
+                    .iter()
+                    .map(|byte| format!("0x{:02X}", byte))
+                    .collect();
+                (offset, length, format!("db {}", bytes.join(", ")))
+            }
+        };
+        let hex: String = rom[offset..offset + length]
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect();
+        rows.push((address_of(offset), text, hex));
+    }
+
+    //Line the comments up in a column after the longest line.
+    let width = rows
+        .iter()
+        .map(|(_, text, _)| text.len())
+        .max()
+        .unwrap_or(0);
+    let mut listing = String::new();
+    for (address, text, hex) in rows {
+        if let Some(label) = label_name(address) {
+            listing.push_str(&format!("{}:\n", label));
+        }
+        listing.push_str(&format!(
+            "    {:<width$}  ; 0x{:03X}: {}\n",
+            text,
+            address,
+            hex,
+            width = width
+        ));
+    }
+    listing
+}
+
+fn address_of(offset: usize) -> u16 {
+    (PROGRAM_START + offset) as u16
+}
+
+///Returns the offset into the ROM of the given address, if it lies inside the ROM.
+fn offset_of(rom: &[u8], address: u16) -> Option<usize> {
+    let offset = (address as usize).checked_sub(PROGRAM_START)?;
+    match offset < rom.len() {
+        true => Some(offset),
+        false => None,
+    }
+}
+
+fn read_instruction(rom: &[u8], offset: usize) -> Instruction {
+    Instruction::new(((rom[offset] as u16) << 8) | rom[offset + 1] as u16)
+}
+
+fn read_opcode(rom: &[u8], offset: usize) -> Option<Opcode> {
+    Opcode::try_from(read_instruction(rom, offset)).ok()
+}
+
+///Returns how many bytes the instruction at the offset takes up, or `None` if it isn't a valid instruction.
+fn instruction_length(rom: &[u8], offset: usize) -> Option<usize> {
+    if offset + 2 > rom.len() {
+        return None;
+    }
+    let length = read_opcode(rom, offset)?.length() as usize;
+    match offset + length > rom.len() {
+        true => None,
+        false => Some(length),
+    }
+}
+
+///Follows execution from the start of the program, returning the offsets of every instruction reached along with
+///the addresses that instructions refer to.
+fn trace_code(rom: &[u8]) -> (BTreeMap<usize, usize>, BTreeMap<u16, LabelKind>) {
+    let mut code = BTreeMap::new();
+    let mut labels = BTreeMap::new();
+    let add_label = |labels: &mut BTreeMap<u16, LabelKind>, address: u16, kind: LabelKind| {
+        let label = labels.entry(address).or_insert(kind);
+        *label = (*label).max(kind);
+    };
+
+    let mut to_visit = vec![address_of(0)];
+    while let Some(address) = to_visit.pop() {
+        let offset = match offset_of(rom, address) {
+            Some(offset) if !code.contains_key(&offset) => offset,
+            _ => continue,
+        };
+        let length = match instruction_length(rom, offset) {
+            Some(length) => length,
+            None => continue,
+        };
+        code.insert(offset, length);
+
+        //Nothing follows an instruction that ends at the top of memory.
+        let next = address.checked_add(length as u16);
+        match read_opcode(rom, offset).expect("The length was found, so it decodes.") {
+            Opcode::Ret | Opcode::Exit => {}
+            Opcode::Jp(target) => {
+                add_label(&mut labels, target, LabelKind::Jump);
+                to_visit.push(target);
+            }
+            Opcode::Call(target) => {
+                add_label(&mut labels, target, LabelKind::Subroutine);
+                to_visit.push(target);
+                to_visit.extend(next);
+            }
+            Opcode::SeImmediate(..)
+            | Opcode::SneImmediate(..)
+            | Opcode::Se(..)
+            | Opcode::Sne(..)
+            | Opcode::Skp(_)
+            | Opcode::Sknp(_) => {
+                let skipped_length = next
+                    .and_then(|next| offset_of(rom, next))
+                    .and_then(|next_offset| instruction_length(rom, next_offset))
+                    .unwrap_or(2);
+                to_visit.extend(next);
+                to_visit.extend(next.and_then(|next| next.checked_add(skipped_length as u16)));
+            }
+            Opcode::LdI(target) => {
+                add_label(&mut labels, target, LabelKind::Data);
+                to_visit.extend(next);
+            }
+            //The offset isn't known, but the base usually holds a table of jumps.
+            Opcode::JpV0(target) => {
+                add_label(&mut labels, target, LabelKind::Jump);
+                to_visit.push(target);
+            }
+            _ => to_visit.extend(next),
+        }
+    }
+    (code, labels)
+}
+
+///Splits the ROM into lines of code and data. Data lines are broken up wherever a label could go.
+fn split_lines(
+    rom: &[u8],
+    code: &BTreeMap<usize, usize>,
+    labels: &BTreeMap<u16, LabelKind>,
+) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let mut offset = 0;
+    while offset < rom.len() {
+        if let Some(&length) = code.get(&offset) {
+            lines.push(Line::Code { offset, length });
+            offset += length;
+            continue;
+        }
+
+        let start = offset;
+        offset += 1;
+        while offset < rom.len()
+            && offset - start < BYTES_PER_DATA_LINE
+            && !code.contains_key(&offset)
+            && !labels.contains_key(&address_of(offset))
+        {
+            offset += 1;
+        }
+        lines.push(Line::Data {
+            offset: start,
+            length: offset - start,
+        });
+    }
+    lines
+}
@@ -1,27 +1,52 @@
-use pixels::{Error, Pixels, SurfaceTexture};
-use std::{sync::mpsc::{self, Receiver}, thread};
-use computer::EmulatorResponse;
+use crate::frame_buffer::{ToRGB, ToRGBVec};
+use crate::keymap::Keymap;
+use crate::threading::{EmulatorCommand, EmulatorResponse};
+use pixels::{Pixels, SurfaceTexture};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Receiver, Sender};
+use std::time::{Duration, Instant};
 use winit::{
-    event::{Event, WindowEvent},
+    event::{ElementState, Event, KeyboardInput, ModifiersState, VirtualKeyCode, WindowEvent},
     event_loop::{ControlFlow, EventLoop},
-    window::WindowBuilder,
+    window::{Window, WindowBuilder},
 };
 
+/// How often a frame is rewound while the rewind key is held.
+const REWIND_PERIOD: Duration = Duration::from_nanos(1_000_000_000 / 60);
+/// How long closing the window waits for the emulator to hand over the movie being recorded.
+const RECORDING_TIMEOUT: Duration = Duration::from_secs(5);
+
 pub struct ProgramDisplay {
     event_loop: EventLoop<()>,
+    window: Window,
     pixels: Pixels,
     width: usize,
     height: usize,
-    receiver_from_emulator: Receiver<EmulatorResponse>
+    receiver_from_emulator: Receiver<EmulatorResponse>,
+    sender_to_emulator: Sender<EmulatorCommand>,
+    keymap: Keymap,
+    response_forwarder: Option<Sender<EmulatorResponse>>,
+    save_slots_for: Option<PathBuf>,
+    recording_to: Option<PathBuf>,
 }
 
- impl ProgramDisplay {
-
-    fn new(width: usize, height: usize, window_name: String, receiver_from_emulator: Receiver<EmulatorResponse>) -> Self {
+impl ProgramDisplay {
+    pub fn new(
+        width: usize,
+        height: usize,
+        scale: u32,
+        window_name: String,
+        receiver_from_emulator: Receiver<EmulatorResponse>,
+        sender_to_emulator: Sender<EmulatorCommand>,
+        keymap: Keymap,
+    ) -> Self {
         let event_loop = EventLoop::new();
         let window = WindowBuilder::new()
             .with_title(window_name)
-            .with_inner_size(winit::dpi::LogicalSize::new(640, 320))
+            .with_inner_size(winit::dpi::LogicalSize::new(
+                width as u32 * scale,
+                height as u32 * scale,
+            ))
             .build(&event_loop)
             .unwrap();
 
@@ -29,32 +54,210 @@ pub struct ProgramDisplay {
             let window_size = window.inner_size();
             SurfaceTexture::new(window_size.width, window_size.height, &window)
         };
-        let mut pixels = Pixels::new(width as u32, height as u32, surface_texture).unwrap();
+        let pixels = Pixels::new(width as u32, height as u32, surface_texture).unwrap();
 
         ProgramDisplay {
-            event_loop: EventLoop::new(),
+            event_loop,
+            window,
             pixels,
             width,
             height,
-            receiver_from_emulator
+            receiver_from_emulator,
+            sender_to_emulator,
+            keymap,
+            response_forwarder: None,
+            save_slots_for: None,
+            recording_to: None,
         }
+    }
 
+    /// Passes every response other than screen updates on to the given channel instead of dropping them,
+    /// so something else such as a debugger can talk to the emulator while the window is open.
+    pub fn forward_responses(mut self, response_forwarder: Sender<EmulatorResponse>) -> Self {
+        self.response_forwarder = Some(response_forwarder);
+        self
     }
-    
-    fn initialize(&self) {
-        let mut current_image = Self::start_image(self.width, self.height).to_rgb_vec();
 
-        // Run the event loop
-        self.event_loop.run(move |event, _, control_flow| {
+    /// Enables the save state hotkeys: Shift+F1 to Shift+F9 save to slots 1 to 9 and F1 to F9 load them.
+    /// Slots are files next to the ROM, e.g. `pong.ch8.state1`. Keys bound in the keymap take precedence.
+    pub fn save_slots(mut self, rom_path: PathBuf) -> Self {
+        self.save_slots_for = Some(rom_path);
+        self
+    }
+
+    /// Writes the movie being recorded to the given file when the window is closed.
+    /// Recording has to be started separately, with `EmulatorCommand::StartRecording`.
+    pub fn record_to(mut self, movie_path: PathBuf) -> Self {
+        self.recording_to = Some(movie_path);
+        self
+    }
+
+    /// Runs the window's event loop, drawing every frame buffer received from the emulator.
+    ///
+    /// Holding Backspace, unless it is bound in the keymap, pauses the program and rewinds it a frame at a time.
+    /// Releasing it resumes the program if it was running when Backspace was pressed, and leaves it stopped if it
+    /// was paused, halted or had exited.
+    ///
+    /// This never returns and must be called from the main thread.
+    pub fn run(self) -> ! {
+        let ProgramDisplay {
+            event_loop,
+            window,
+            mut pixels,
+            width,
+            height,
+            receiver_from_emulator,
+            sender_to_emulator,
+            keymap,
+            response_forwarder,
+            save_slots_for,
+            recording_to,
+        } = self;
+        let mut current_image = Self::start_image(width, height).to_rgb_vec();
+        let mut current_size = (width, height);
+        let mut modifiers = ModifiersState::empty();
+        // The slot and file to write the next save state received from the emulator to.
+        let mut saving_to = None;
+        // When the next frame should be rewound, while the rewind key is held.
+        let mut next_rewind = None;
+        // Whether the emulator has yet to answer the pause sent when the rewind key was pressed.
+        let mut awaiting_rewind_pause = false;
+        // Whether the program was running before the rewind key paused it, and so resumes once it is released.
+        let mut resume_after_rewind = false;
+
+        event_loop.run(move |event, _, control_flow| {
             *control_flow = ControlFlow::Poll;
             match event {
                 Event::WindowEvent {
                     event: WindowEvent::CloseRequested,
                     ..
-                } => *control_flow = ControlFlow::Exit,
+                } => {
+                    if let Some(movie_path) = &recording_to {
+                        Self::write_movie(&sender_to_emulator, &receiver_from_emulator, movie_path);
+                    }
+                    *control_flow = ControlFlow::Exit;
+                }
+                Event::WindowEvent {
+                    event: WindowEvent::ModifiersChanged(new_modifiers),
+                    ..
+                } => modifiers = new_modifiers,
+                Event::WindowEvent {
+                    event:
+                        WindowEvent::KeyboardInput {
+                            input:
+                                KeyboardInput {
+                                    virtual_keycode: Some(key_code),
+                                    state,
+                                    ..
+                                },
+                            ..
+                        },
+                    ..
+                } => {
+                    if let Some(key) = keymap.get_key(&format!("{:?}", key_code)) {
+                        let pressed = state == ElementState::Pressed;
+                        if sender_to_emulator
+                            .send(EmulatorCommand::SetKey { key, pressed })
+                            .is_err()
+                        {
+                            *control_flow = ControlFlow::Exit;
+                        }
+                    } else if key_code == VirtualKeyCode::Back {
+                        let command = match (state, next_rewind) {
+                            (ElementState::Pressed, None) => {
+                                next_rewind = Some(Instant::now());
+                                awaiting_rewind_pause = true;
+                                resume_after_rewind = false;
+                                Some(EmulatorCommand::Pause)
+                            }
+                            (ElementState::Released, Some(_)) => {
+                                next_rewind = None;
+                                match resume_after_rewind {
+                                    true => Some(EmulatorCommand::Go),
+                                    false => None,
+                                }
+                            }
+                            // Held keys repeat their presses.
+                            _ => None,
+                        };
+                        if let Some(command) = command {
+                            if sender_to_emulator.send(command).is_err() {
+                                *control_flow = ControlFlow::Exit;
+                            }
+                        }
+                    } else if let (Some(rom_path), Some(slot), ElementState::Pressed) =
+                        (&save_slots_for, Self::save_slot(key_code), state)
+                    {
+                        let command = match modifiers.shift() {
+                            true => {
+                                saving_to = Some((slot, Self::slot_path(rom_path, slot)));
+                                Some(EmulatorCommand::SaveState)
+                            }
+                            false => match std::fs::read(Self::slot_path(rom_path, slot)) {
+                                Ok(bytes) => Some(EmulatorCommand::LoadState(bytes)),
+                                Err(e) => {
+                                    eprintln!("Couldn't load save slot {}: {}", slot, e);
+                                    None
+                                }
+                            },
+                        };
+                        if let Some(command) = command {
+                            if sender_to_emulator.send(command).is_err() {
+                                *control_flow = ControlFlow::Exit;
+                            }
+                        }
+                    }
+                }
                 Event::RedrawRequested(_) => {
-                    if let Ok(new_image) = self.receiver_from_emulator.try_recv() {
-                        current_image = new_image
+                    while let Ok(response) = receiver_from_emulator.try_recv() {
+                        if let EmulatorResponse::Paused { was_running, .. } = response {
+                            if awaiting_rewind_pause {
+                                awaiting_rewind_pause = false;
+                                resume_after_rewind = was_running;
+                                // The rewind key was released before the pause was answered.
+                                if was_running
+                                    && next_rewind.is_none()
+                                    && sender_to_emulator.send(EmulatorCommand::Go).is_err()
+                                {
+                                    *control_flow = ControlFlow::Exit;
+                                }
+                            }
+                        }
+                        match response {
+                            EmulatorResponse::FrameBuffer {
+                                width: new_width,
+                                height: new_height,
+                                pixels: new_image,
+                            } => {
+                                if (new_width, new_height) != current_size {
+                                    pixels.resize_buffer(new_width as u32, new_height as u32);
+                                    current_size = (new_width, new_height);
+                                }
+                                current_image = new_image;
+                            }
+                            EmulatorResponse::SaveState(bytes) if saving_to.is_some() => {
+                                if let Some((slot, path)) = saving_to.take() {
+                                    match std::fs::write(&path, bytes) {
+                                        Ok(()) => eprintln!("Saved to slot {}.", slot),
+                                        Err(e) => {
+                                            eprintln!("Couldn't write {}: {}", path.display(), e)
+                                        }
+                                    }
+                                }
+                            }
+                            response => match (&response_forwarder, response) {
+                                (Some(response_forwarder), response) => {
+                                    let _ = response_forwarder.send(response);
+                                }
+                                (None, EmulatorResponse::MovieDesync(desync)) => {
+                                    eprintln!("{}", desync)
+                                }
+                                (None, EmulatorResponse::MovieFinished(playback)) => {
+                                    eprintln!("{}", playback)
+                                }
+                                _ => {}
+                            },
+                        }
                     }
 
                     for (i, pixel) in pixels.get_frame().chunks_exact_mut(4).enumerate() {
@@ -66,15 +269,75 @@ pub struct ProgramDisplay {
                         .is_err()
                     {
                         *control_flow = ControlFlow::Exit;
-                        return;
                     }
                 }
                 Event::MainEventsCleared => {
+                    if let Some(next) = next_rewind.filter(|&next| Instant::now() >= next) {
+                        next_rewind = Some(next + REWIND_PERIOD);
+                        if sender_to_emulator.send(EmulatorCommand::Rewind(1)).is_err() {
+                            *control_flow = ControlFlow::Exit;
+                        }
+                    }
                     window.request_redraw();
                 }
                 _ => {}
             }
-        });
+        })
+    }
+
+    /// Stops recording and writes the movie to a file, waiting for the emulator to send it.
+    fn write_movie(
+        sender_to_emulator: &Sender<EmulatorCommand>,
+        receiver_from_emulator: &Receiver<EmulatorResponse>,
+        movie_path: &Path,
+    ) {
+        if sender_to_emulator
+            .send(EmulatorCommand::StopRecording)
+            .is_err()
+        {
+            eprintln!("The emulator stopped before the movie could be written.");
+            return;
+        }
+        let deadline = Instant::now() + RECORDING_TIMEOUT;
+        let movie = loop {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            match receiver_from_emulator.recv_timeout(timeout) {
+                Ok(EmulatorResponse::Movie(bytes)) => break Some(bytes),
+                Ok(_) => {}
+                Err(_) => break None,
+            }
+        };
+        match movie.map(|bytes| std::fs::write(movie_path, bytes)) {
+            Some(Ok(())) => eprintln!("Wrote the movie to {}.", movie_path.display()),
+            Some(Err(e)) => eprintln!("Couldn't write {}: {}", movie_path.display(), e),
+            None => eprintln!("The emulator didn't hand over the movie to write."),
+        }
+    }
+
+    /// Returns the save slot a function key selects.
+    fn save_slot(key_code: VirtualKeyCode) -> Option<u8> {
+        let slots = [
+            VirtualKeyCode::F1,
+            VirtualKeyCode::F2,
+            VirtualKeyCode::F3,
+            VirtualKeyCode::F4,
+            VirtualKeyCode::F5,
+            VirtualKeyCode::F6,
+            VirtualKeyCode::F7,
+            VirtualKeyCode::F8,
+            VirtualKeyCode::F9,
+        ];
+        slots
+            .iter()
+            .position(|&slot_key| slot_key == key_code)
+            .map(|slot| slot as u8 + 1)
+    }
+
+    /// Returns the file a save slot is stored in, next to the ROM.
+    fn slot_path(rom_path: &Path, slot: u8) -> PathBuf {
+        let mut file_name = rom_path.as_os_str().to_owned();
+        file_name.push(format!(".state{}", slot));
+        PathBuf::from(file_name)
     }
 
     /// Takes in a width and height for a frame_buffer and generates a Vec<impl ToRGB> for a sample image.
@@ -86,7 +349,7 @@ pub struct ProgramDisplay {
             if ((i % width) % 8 < 4 && (i / width) % 8 < 4)
                 || ((i % width) % 8 > 3 && (i / width) % 8 > 3)
             {
-                buffer.push(255);
+                buffer.push(1);
             } else {
                 buffer.push(0);
             }
@@ -94,25 +357,3 @@ pub struct ProgramDisplay {
         buffer
     }
 }
-
-
-impl ToRGB for u8 {
-    fn to_rgb(&self) -> [u8; 4] {
-        match self {
-            0 => [16, 21, 158, 255],
-            _ => [0, 0, 0, 255],
-        }
-    }
-}
-
-pub trait ToRGB {
-    fn to_rgb(&self) -> [u8; 4];
-}
-pub trait ToRGBVec {
-    fn to_rgb_vec(&self) -> Vec<[u8; 4]>;
-}
-impl<T: ToRGB> ToRGBVec for Vec<T> {
-    fn to_rgb_vec(&self) -> Vec<[u8; 4]> {
-        self.into_iter().map(|item| item.to_rgb()).collect()
-    }
-}
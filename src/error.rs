@@ -0,0 +1,90 @@
+use std::fmt::Display;
+
+///Describes why the emulated CPU could not execute an instruction.
+///
+///Every variant carries the address of the instruction that failed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExecutionError {
+    ///The instruction does not decode to any operation this emulator supports.
+    UnknownOpcode {
+        program_counter: u16,
+        instruction: u16,
+    },
+    ///A subroutine was called while all 16 stack frames were in use.
+    StackOverflow { program_counter: u16 },
+    ///A subroutine returned while the stack was empty.
+    StackUnderflow { program_counter: u16 },
+    ///The instruction tried to access an address outside of RAM.
+    MemoryOutOfBounds {
+        program_counter: u16,
+        address: usize,
+    },
+}
+
+impl ExecutionError {
+    ///Returns the address of the instruction that caused the error.
+    pub fn program_counter(&self) -> u16 {
+        match self {
+            ExecutionError::UnknownOpcode {
+                program_counter, ..
+            }
+            | ExecutionError::StackOverflow { program_counter }
+            | ExecutionError::StackUnderflow { program_counter }
+            | ExecutionError::MemoryOutOfBounds {
+                program_counter, ..
+            } => *program_counter,
+        }
+    }
+}
+
+impl Display for ExecutionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExecutionError::UnknownOpcode {
+                program_counter,
+                instruction,
+            } => write!(
+                f,
+                "Unknown opcode 0x{:04X} at 0x{:04X}.",
+                instruction, program_counter
+            ),
+            ExecutionError::StackOverflow { program_counter } => {
+                write!(f, "Stack overflow at 0x{:04X}.", program_counter)
+            }
+            ExecutionError::StackUnderflow { program_counter } => {
+                write!(f, "Stack underflow at 0x{:04X}.", program_counter)
+            }
+            ExecutionError::MemoryOutOfBounds {
+                program_counter,
+                address,
+            } => write!(
+                f,
+                "Memory access out of bounds (0x{:04X}) at 0x{:04X}.",
+                address, program_counter
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ExecutionError {}
+
+///A ROM couldn't be loaded because it doesn't fit in the memory available for programs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RomTooLarge {
+    ///The size of the ROM in bytes.
+    pub size: usize,
+    ///How many bytes of program space there are, see [`crate::memory::Memory::program_space`].
+    pub program_space: usize,
+}
+
+impl Display for RomTooLarge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "The ROM is {} bytes, but only {} bytes of program space are available.",
+            self.size, self.program_space
+        )
+    }
+}
+
+impl std::error::Error for RomTooLarge {}
@@ -1,11 +1,31 @@
-use std::{fmt::Display, process::Command, sync::mpsc::Sender, time::SystemTime};
+use std::{cell::Cell, fmt::Display, process::Command, sync::mpsc::Sender, time::SystemTime};
 
-use crate::{computer::EmulatorResponse, display::{ProgramDisplay, ToRGB, ToRGBVec}};
+use crate::threading::EmulatorResponse;
 
+///The widest the screen can be, used by the SUPER-CHIP high resolution mode.
+pub const MAX_WIDTH: usize = 128;
+///The tallest the screen can be, used by the SUPER-CHIP high resolution mode.
+pub const MAX_HEIGHT: usize = 64;
+
+///How many bitplanes the screen has. XO-CHIP programs can draw to the second one.
+pub const PLANE_COUNT: usize = 2;
+
+///Holds the state of the screen.
+///
+///Each plane's rows are stored as the bits of a u128 with the leftmost pixel in the most significant bit.
+///In low resolution mode only the leftmost 64 pixels of the top 32 rows are used.
+///A pixel's colour comes from combining its bit in each plane, with the first plane as the least significant bit.
 pub struct FrameBuffer {
-    pub buffer: [u64; 32],
+    pub buffer: [[u128; MAX_HEIGHT]; PLANE_COUNT],
+    pub high_resolution: bool,
+    ///Bitmask of the planes that drawing, clearing and scrolling operate on.
+    pub selected_planes: u8,
+    ///The colours used for each palette index when converting the screen to RGB.
+    pub palette: [[u8; 4]; 4],
     _clear_command: String,
     redraw_sender: Sender<EmulatorResponse>,
+    ///Set once the receiver turns out to be dropped, after which the screen isn't converted to RGB anymore.
+    receiver_dropped: Cell<bool>,
     _last_update: SystemTime,
 }
 
@@ -17,40 +37,129 @@ impl FrameBuffer {
         };
 
         FrameBuffer {
-            buffer: [0; 32],
+            buffer: [[0; MAX_HEIGHT]; PLANE_COUNT],
+            high_resolution: false,
+            selected_planes: 0x01,
+            palette: PALETTE,
             _clear_command: clear_command,
-            redraw_sender: redraw_sender,
+            redraw_sender,
+            receiver_dropped: Cell::new(false),
             _last_update: SystemTime::now(),
         }
     }
 
+    ///Sends the screen to be drawn, unless the receiver has been dropped because nothing draws it.
     pub fn request_redraw(&self) {
-        let _ = self.redraw_sender.send(EmulatorResponse::FrameBuffer(self.to_rgb_vec()));
+        if self.receiver_dropped.get() {
+            return;
+        }
+        let sent = self.redraw_sender.send(EmulatorResponse::FrameBuffer {
+            width: self.width(),
+            height: self.height(),
+            pixels: self.to_rgb_vec(),
+        });
+        self.receiver_dropped.set(sent.is_err());
     }
 
-    pub fn get_frame_buffer(&self) -> [u64; 32] {
+    pub fn get_frame_buffer(&self) -> [[u128; MAX_HEIGHT]; PLANE_COUNT] {
         self.buffer
     }
 
-    ///XORs the bytes onto screen starting at the the given coordinates.
+    ///Returns the width of the screen in the current resolution.
+    pub fn width(&self) -> usize {
+        match self.high_resolution {
+            true => MAX_WIDTH,
+            false => MAX_WIDTH / 2,
+        }
+    }
+
+    ///Returns the height of the screen in the current resolution.
+    pub fn height(&self) -> usize {
+        match self.high_resolution {
+            true => MAX_HEIGHT,
+            false => MAX_HEIGHT / 2,
+        }
+    }
+
+    ///Returns the palette index of the pixel at the given coordinates.
+    pub fn get_pixel(&self, x: usize, y: usize) -> u8 {
+        self.buffer
+            .iter()
+            .enumerate()
+            .map(|(plane, rows)| (((rows[y] >> (MAX_WIDTH - 1 - x)) & 0x01) as u8) << plane)
+            .sum()
+    }
+
+    ///Returns the indices of the planes that are currently selected.
+    pub fn get_selected_planes(&self) -> Vec<usize> {
+        (0..PLANE_COUNT)
+            .filter(|plane| self.selected_planes >> plane & 0x01 == 1)
+            .collect()
+    }
+
+    ///Returns a row mask with a bit set for every pixel that is on screen in the current resolution.
+    fn row_mask(&self) -> u128 {
+        !0u128 << (MAX_WIDTH - self.width())
+    }
+
+    ///Switches between the 64x32 and 128x64 resolutions. Every plane is cleared when doing so.
+    pub fn set_high_resolution(&mut self, high_resolution: bool) {
+        self.high_resolution = high_resolution;
+        self.buffer = [[0; MAX_HEIGHT]; PLANE_COUNT];
+        self.request_redraw();
+    }
+
+    ///XORs the sprite onto the selected planes starting at the the given coordinates.
+    ///Each row holds `sprite_width` pixels in its least significant bits, with the leftmost pixel first.
+    ///The rows for each selected plane follow one another, so a sprite drawn to two planes has twice as many rows.
+    ///The starting coordinates wrap around the screen. Pixels past the edges are either clipped or wrapped around too.
     ///Returns whether or not any bits are erased because of this.
-    pub fn draw_sprite(&mut self, start_x: u8, start_y: u8, bytes: Vec<u8>) -> bool {
+    pub fn draw_sprite(
+        &mut self,
+        start_x: u8,
+        start_y: u8,
+        rows: Vec<u16>,
+        sprite_width: u32,
+        clip: bool,
+    ) -> bool {
         let mut result = false;
+        let width = self.width() as u32;
+        let height = self.height();
+        let start_x = start_x as u32 % width;
+        let start_y = start_y as usize % height;
+        let row_mask = self.row_mask();
+        let selected_planes = self.get_selected_planes();
+        if selected_planes.is_empty() {
+            return false;
+        }
+        let sprite_height = rows.len() / selected_planes.len();
 
-        for (i, byte) in bytes.into_iter().enumerate() {
-            let mut byte_u64 = (byte as u64) << 56;
-            byte_u64 = byte_u64.rotate_right(start_x as u32);
+        for (plane, plane_rows) in selected_planes
+            .into_iter()
+            .zip(rows.chunks(sprite_height.max(1)))
+        {
+            for (i, row) in plane_rows.iter().enumerate() {
+                let aligned_row = (*row as u128) << (MAX_WIDTH as u32 - sprite_width);
+                let mut row_bits = aligned_row >> start_x;
+                if !clip {
+                    row_bits |= aligned_row.checked_shl(width - start_x).unwrap_or(0);
+                }
+                row_bits &= row_mask;
 
-            let y_position = start_y as usize + i;
-            if y_position >= 32 {
-                break;
-            }
+                let mut y_position = start_y + i;
+                if y_position >= height {
+                    match clip {
+                        true => break,
+                        false => y_position %= height,
+                    }
+                }
 
-            if byte_u64 & self.buffer[y_position] != 0 {
-                result = true;
-            }
+                if row_bits & self.buffer[plane][y_position] != 0 {
+                    result = true;
+                }
 
-            self.buffer[y_position] ^= byte_u64;
+                self.buffer[plane][y_position] ^= row_bits;
+            }
         }
 
         self.request_redraw();
@@ -58,19 +167,56 @@ impl FrameBuffer {
         result
     }
 
+    ///Scrolls the selected planes down by the given number of pixels.
+    pub fn scroll_down(&mut self, num_pixels: usize) {
+        let height = self.height();
+        for plane in self.get_selected_planes() {
+            let rows = &mut self.buffer[plane];
+            for y in (0..height).rev() {
+                rows[y] = match y >= num_pixels {
+                    true => rows[y - num_pixels],
+                    false => 0,
+                };
+            }
+        }
+        self.request_redraw();
+    }
+
+    ///Scrolls the selected planes right by the given number of pixels.
+    pub fn scroll_right(&mut self, num_pixels: u32) {
+        let row_mask = self.row_mask();
+        for plane in self.get_selected_planes() {
+            for row in self.buffer[plane].iter_mut() {
+                *row = (*row >> num_pixels) & row_mask;
+            }
+        }
+        self.request_redraw();
+    }
+
+    ///Scrolls the selected planes left by the given number of pixels.
+    pub fn scroll_left(&mut self, num_pixels: u32) {
+        let row_mask = self.row_mask();
+        for plane in self.get_selected_planes() {
+            for row in self.buffer[plane].iter_mut() {
+                *row = (*row << num_pixels) & row_mask;
+            }
+        }
+        self.request_redraw();
+    }
+
+    ///Clears the selected planes.
     pub fn clear(&mut self) {
-        for i in 0..self.buffer.len() {
-            self.buffer[i] = 0
+        for plane in self.get_selected_planes() {
+            self.buffer[plane] = [0; MAX_HEIGHT];
         }
         self.request_redraw()
     }
 
     pub fn get_buffer_as_drawable_vec(&self) -> Vec<[u8; 4]> {
         let mut buffer = Vec::new();
-        for line in self.buffer {
-            for i in (0..64).rev() {
-                let bit = (line >> i) as u8 & 0x01;
-                buffer.push(bit.to_rgb());                
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                buffer.push(self.palette[self.get_pixel(x, y) as usize]);
             }
         }
         buffer
@@ -78,13 +224,13 @@ impl FrameBuffer {
 
     pub fn get_buffer_as_string(&self) -> String {
         let mut string_buffer = String::new();
-        for line in self.buffer {
-            for i in (0..64).rev() {
-                let bit = (line >> i) as u8 & 0x01;
-                if bit == 1 {
-                    string_buffer.push('\u{2588}');
-                } else {
-                    string_buffer.push(' ');
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                match self.get_pixel(x, y) {
+                    0 => string_buffer.push(' '),
+                    1 => string_buffer.push('\u{2588}'),
+                    2 => string_buffer.push('\u{2592}'),
+                    _ => string_buffer.push('\u{2593}'),
                 }
             }
             string_buffer.push('\n');
@@ -108,4 +254,30 @@ impl ToRGBVec for FrameBuffer {
     fn to_rgb_vec(&self) -> Vec<[u8; 4]> {
         self.get_buffer_as_drawable_vec()
     }
-}
\ No newline at end of file
+}
+
+///The colours used for each combination of the two bitplanes, indexed by the pixel's plane bits.
+pub const PALETTE: [[u8; 4]; 4] = [
+    [16, 21, 158, 255],
+    [0, 0, 0, 255],
+    [255, 170, 0, 255],
+    [120, 60, 0, 255],
+];
+
+impl ToRGB for u8 {
+    fn to_rgb(&self) -> [u8; 4] {
+        PALETTE[*self as usize % PALETTE.len()]
+    }
+}
+
+pub trait ToRGB {
+    fn to_rgb(&self) -> [u8; 4];
+}
+pub trait ToRGBVec {
+    fn to_rgb_vec(&self) -> Vec<[u8; 4]>;
+}
+impl<T: ToRGB> ToRGBVec for Vec<T> {
+    fn to_rgb_vec(&self) -> Vec<[u8; 4]> {
+        self.iter().map(|item| item.to_rgb()).collect()
+    }
+}
@@ -0,0 +1,197 @@
+use std::convert::TryFrom;
+use std::fmt::Display;
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+use crate::breakpoint::BreakpointHit;
+use crate::computer::{Chip8Computer, TimingMode};
+use crate::error::ExecutionError;
+use crate::frame_buffer::FrameBuffer;
+use crate::movie::Playback;
+use crate::opcode::Opcode;
+use crate::threading::{EmulatorCommand, ThreadedEmulator};
+
+///When a headless run should stop, besides the program exiting or failing.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RunLimits {
+    ///Stop once this many 60 Hz frames have passed.
+    pub frames: Option<u64>,
+    ///Stop once this many instructions have been executed.
+    pub instructions: Option<u64>,
+}
+
+///Why a headless run stopped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RunOutcome {
+    ///The frame limit was reached.
+    FrameLimit,
+    ///The instruction limit was reached.
+    InstructionLimit,
+    ///The program got stuck on a single instruction it can never leave without a key being pressed,
+    ///either a jump to itself or Fx0A.
+    Looped { program_counter: u16 },
+    ///The program exited through the SUPER-CHIP exit instruction.
+    Exited,
+    ///An instruction couldn't be executed.
+    Halted(ExecutionError),
+    ///A breakpoint added with [`Chip8Computer::add_breakpoint`] was reached.
+    Breakpoint(BreakpointHit),
+    ///The movie being played back with [`Chip8Computer::play_movie`] is over.
+    MovieFinished(Playback),
+}
+
+impl Display for RunOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RunOutcome::FrameLimit => write!(f, "Stopped after reaching the frame limit."),
+            RunOutcome::InstructionLimit => {
+                write!(f, "Stopped after reaching the instruction limit.")
+            }
+            RunOutcome::Looped { program_counter } => {
+                write!(
+                    f,
+                    "Stopped in an endless loop at 0x{:04X}.",
+                    program_counter
+                )
+            }
+            RunOutcome::Exited => write!(f, "The program exited."),
+            RunOutcome::Halted(e) => write!(f, "{}", e),
+            RunOutcome::Breakpoint(hit) => write!(f, "{}", hit),
+            RunOutcome::MovieFinished(playback) => write!(f, "{}", playback),
+        }
+    }
+}
+
+///Runs the loaded program as fast as possible on the current thread until it exits, fails, loops or hits a limit.
+///
+///A frame passes every `instructions_per_frame` instructions, so the result only depends on the ROM and settings,
+///never on how fast the host is. No keys are ever pressed, unless a movie is being played back, in which case the
+///movie decides when frames pass and the run stops once it is over.
+pub fn run(
+    computer: &mut Chip8Computer,
+    instructions_per_frame: u32,
+    limits: RunLimits,
+) -> RunOutcome {
+    let instructions_per_frame = instructions_per_frame.max(1);
+    computer.timing_mode = TimingMode::InstructionsPerFrame(instructions_per_frame);
+    let _ = computer.match_received_command(EmulatorCommand::Go);
+
+    //Frames are counted as they pass, since a movie being played back makes them last different lengths.
+    let first_vertical_blank = computer.vertical_blanks();
+    let mut instructions: u64 = 0;
+    loop {
+        //Nothing listens for redraws during a run, so callers drop the receiver rather than let them pile up.
+        if limits
+            .frames
+            .is_some_and(|frames| computer.vertical_blanks() - first_vertical_blank >= frames)
+        {
+            return RunOutcome::FrameLimit;
+        }
+        if limits
+            .instructions
+            .is_some_and(|limit| instructions >= limit)
+        {
+            return RunOutcome::InstructionLimit;
+        }
+
+        let program_counter = computer.cpu.program_counter;
+        let instruction = match computer.execute_loop() {
+            Ok(instruction) => instruction,
+            Err(e) => return RunOutcome::Halted(e),
+        };
+        instructions += 1;
+        if let Some(hit) = computer.breakpoint_hit() {
+            return RunOutcome::Breakpoint(hit);
+        }
+        if let Some(playback) = computer.playback().filter(Playback::is_finished) {
+            return RunOutcome::MovieFinished(playback);
+        }
+        if !computer.is_running() {
+            return RunOutcome::Exited;
+        }
+
+        let stuck = match Opcode::try_from(instruction) {
+            Ok(Opcode::Jp(address)) => address == program_counter,
+            //A movie being played back may still press a key.
+            Ok(Opcode::LdVxK(_)) => {
+                computer.cpu.program_counter == program_counter
+                    && computer
+                        .playback()
+                        .is_none_or(|playback| playback.is_finished())
+            }
+            _ => false,
+        };
+        if stuck {
+            return RunOutcome::Looped { program_counter };
+        }
+    }
+}
+
+///How long a benchmark took to execute its instructions.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Benchmark {
+    pub instructions: u64,
+    pub elapsed: Duration,
+    ///Whether the program exited before all of the instructions were executed.
+    pub exited: bool,
+}
+
+impl Benchmark {
+    pub fn instructions_per_second(&self) -> f64 {
+        self.instructions as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+///Executes the loaded program for the given number of instructions as fast as possible and times it.
+///
+///Unlike [`run`] this keeps going once the program settles into an endless loop, since that is where test ROMs
+///spend most of their time. It stops early if the program exits and fails if an instruction can't be executed.
+pub fn benchmark(
+    computer: &mut Chip8Computer,
+    instructions_per_frame: u32,
+    instructions: u64,
+) -> Result<Benchmark, ExecutionError> {
+    computer.timing_mode = TimingMode::InstructionsPerFrame(instructions_per_frame.max(1));
+    let _ = computer.match_received_command(EmulatorCommand::Go);
+
+    let start = Instant::now();
+    let mut executed = 0;
+    while executed < instructions && computer.is_running() {
+        computer.tick()?;
+        executed += 1;
+    }
+    Ok(Benchmark {
+        instructions: executed,
+        elapsed: start.elapsed(),
+        exited: !computer.is_running(),
+    })
+}
+
+///Writes the screen as a PNG, with every CHIP-8 pixel drawn as a `scale` by `scale` square in its palette colour.
+pub fn write_png<W: Write>(
+    frame_buffer: &FrameBuffer,
+    scale: u32,
+    writer: W,
+) -> Result<(), png::EncodingError> {
+    let scale = scale.max(1) as usize;
+    let (width, height) = (frame_buffer.width(), frame_buffer.height());
+    let pixels = frame_buffer.get_buffer_as_drawable_vec();
+
+    let mut data = Vec::with_capacity(width * height * scale * scale * 4);
+    for row in pixels.chunks_exact(width) {
+        let mut scaled_row = Vec::with_capacity(width * scale * 4);
+        for pixel in row {
+            for _ in 0..scale {
+                scaled_row.extend_from_slice(pixel);
+            }
+        }
+        for _ in 0..scale {
+            data.extend_from_slice(&scaled_row);
+        }
+    }
+
+    let mut encoder = png::Encoder::new(writer, (width * scale) as u32, (height * scale) as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()?.write_image_data(&data)
+}
@@ -1,13 +1,26 @@
-pub struct Input {}
+///The state of the 16-key hex keypad.
+#[derive(Clone, Default)]
+pub struct Input {
+    pub keys: [bool; 16],
+}
 
 impl Input {
     pub fn new() -> Self {
-        return Input {};
+        Input { keys: [false; 16] }
     }
+    ///Returns whether the given key is held down. Only the lowest 4 bits of the key are used.
     pub fn check_pressed(&self, key: u8) -> bool {
-        todo!()
+        self.keys[(key & 0x0F) as usize]
+    }
+    ///Returns the lowest key that is currently held down, if any.
+    pub fn receive_input(&self) -> Option<u8> {
+        self.keys
+            .iter()
+            .position(|pressed| *pressed)
+            .map(|key| key as u8)
     }
-    pub fn receive_input(&self) -> u8 {
-        todo!()
+    ///Records the given key as held down or released. Only the lowest 4 bits of the key are used.
+    pub fn set_key(&mut self, key: u8, pressed: bool) {
+        self.keys[(key & 0x0F) as usize] = pressed;
     }
 }
@@ -0,0 +1,291 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::path::Path;
+
+///Maps keys on the host keyboard to keys on the hex keypad.
+///
+///Host keys are identified by name, matching winit's `VirtualKeyCode` names (e.g. `Key1`, `Q`, `Numpad7`, `Up`).
+///Every hex key can have several host keys bound to it, but a host key can only be bound to one hex key.
+///
+///Keymaps are loaded from a small TOML file. The `[keymap]` table holds the bindings for all 16 keys used for every ROM,
+///and `[rom."<file name>"]` tables replace the bindings of the hex keys they mention when that ROM is loaded:
+///
+///```toml
+///[keymap]
+///0 = "X"
+///1 = ["Key1", "Numpad7"]
+///
+///[rom."3-corax+.ch8"]
+///5 = "Up"
+///```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Keymap {
+    bindings: [Vec<String>; 16],
+}
+
+///Describes why a keymap could not be loaded.
+#[derive(Debug, PartialEq, Eq)]
+pub enum KeymapError {
+    ///The config file could not be read.
+    Io(String),
+    ///A line in the config file could not be parsed.
+    Parse { line: usize, message: String },
+    ///A host key is bound to more than one hex key.
+    DuplicateBinding { host_key: String, keys: Vec<u8> },
+    ///Some hex keys have no host key bound to them.
+    MissingBindings(Vec<u8>),
+}
+
+impl Keymap {
+    ///Creates a keymap with nothing bound.
+    pub fn empty() -> Self {
+        Keymap {
+            bindings: Default::default(),
+        }
+    }
+
+    ///Returns the hex key bound to the named host key, if any.
+    pub fn get_key(&self, host_key: &str) -> Option<u8> {
+        self.bindings
+            .iter()
+            .position(|host_keys| host_keys.iter().any(|bound| bound == host_key))
+            .map(|key| key as u8)
+    }
+
+    ///Returns the host keys bound to the given hex key.
+    pub fn get_bindings(&self, key: u8) -> &[String] {
+        &self.bindings[(key & 0x0F) as usize]
+    }
+
+    ///Replaces the host keys bound to the given hex key.
+    pub fn bind(&mut self, key: u8, host_keys: Vec<String>) {
+        self.bindings[(key & 0x0F) as usize] = host_keys;
+    }
+
+    ///Checks that every hex key is bound and no host key is bound to more than one hex key.
+    pub fn validate(&self) -> Result<(), KeymapError> {
+        let mut keys_by_host_key: HashMap<&str, Vec<u8>> = HashMap::new();
+        for (key, host_keys) in self.bindings.iter().enumerate() {
+            for host_key in host_keys {
+                let keys = keys_by_host_key.entry(host_key).or_default();
+                if !keys.contains(&(key as u8)) {
+                    keys.push(key as u8);
+                }
+            }
+        }
+        let mut duplicates: Vec<(&str, Vec<u8>)> = keys_by_host_key
+            .into_iter()
+            .filter(|(_, keys)| keys.len() > 1)
+            .collect();
+        duplicates.sort();
+        if let Some((host_key, keys)) = duplicates.into_iter().next() {
+            return Err(KeymapError::DuplicateBinding {
+                host_key: host_key.to_string(),
+                keys,
+            });
+        }
+
+        let missing: Vec<u8> = (0..16)
+            .filter(|key| self.bindings[*key as usize].is_empty())
+            .collect();
+        if !missing.is_empty() {
+            return Err(KeymapError::MissingBindings(missing));
+        }
+        Ok(())
+    }
+
+    ///Parses a keymap config, applying the overrides for the given ROM file name if there are any.
+    ///
+    ///The `[keymap]` table has to bind every hex key. The result is validated before it is returned.
+    pub fn from_config_str(config: &str, rom_name: Option<&str>) -> Result<Self, KeymapError> {
+        let mut keymap = Keymap::empty();
+        let mut rom_overrides = Vec::new();
+        let mut section = Section::Keymap;
+        let mut keys_in_section: Vec<u8> = Vec::new();
+
+        for (i, line) in config.lines().enumerate() {
+            let line_number = i + 1;
+            let parse_error = |message: &str| KeymapError::Parse {
+                line: line_number,
+                message: message.to_string(),
+            };
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some(header) = line.strip_prefix('[') {
+                let header = header
+                    .strip_suffix(']')
+                    .ok_or_else(|| parse_error("Table headers must end with ']'."))?
+                    .trim();
+                section = match header {
+                    "keymap" => Section::Keymap,
+                    _ => match header.strip_prefix("rom.") {
+                        Some(name) => Section::Rom(
+                            parse_string(name.trim())
+                                .ok_or_else(|| parse_error("ROM names must be quoted strings."))?,
+                        ),
+                        None => {
+                            return Err(parse_error(
+                                "Unknown table, expected [keymap] or [rom.\"<file name>\"].",
+                            ))
+                        }
+                    },
+                };
+                keys_in_section.clear();
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| parse_error("Expected a binding like `5 = \"W\"`."))?;
+            let key = parse_hex_key(key.trim()).ok_or_else(|| {
+                parse_error("Keypad keys must be a single hex digit from 0 to F.")
+            })?;
+            if keys_in_section.contains(&key) {
+                return Err(parse_error(&format!(
+                    "Key {:X} is bound more than once in this table.",
+                    key
+                )));
+            }
+            keys_in_section.push(key);
+            let host_keys = parse_host_keys(value.trim()).ok_or_else(|| {
+                parse_error("Bindings must be a quoted key name or a list of quoted key names.")
+            })?;
+
+            match &section {
+                Section::Keymap => keymap.bind(key, host_keys),
+                Section::Rom(name) if Some(name.as_str()) == rom_name => {
+                    rom_overrides.push((key, host_keys))
+                }
+                Section::Rom(_) => {}
+            }
+        }
+
+        for (key, host_keys) in rom_overrides {
+            keymap.bind(key, host_keys);
+        }
+        keymap.validate()?;
+        Ok(keymap)
+    }
+
+    ///Loads a keymap config file, applying the overrides for the given ROM file name if there are any.
+    pub fn load(path: &Path, rom_name: Option<&str>) -> Result<Self, KeymapError> {
+        let config = std::fs::read_to_string(path)
+            .map_err(|e| KeymapError::Io(format!("{}: {}", path.display(), e)))?;
+        Keymap::from_config_str(&config, rom_name)
+    }
+}
+
+impl Default for Keymap {
+    ///The conventional QWERTY layout:
+    ///
+    ///```text
+    ///1 2 3 4      1 2 3 C
+    ///Q W E R  ->  4 5 6 D
+    ///A S D F      7 8 9 E
+    ///Z X C V      A 0 B F
+    ///```
+    fn default() -> Self {
+        let layout = [
+            ("X", 0x0),
+            ("Key1", 0x1),
+            ("Key2", 0x2),
+            ("Key3", 0x3),
+            ("Q", 0x4),
+            ("W", 0x5),
+            ("E", 0x6),
+            ("A", 0x7),
+            ("S", 0x8),
+            ("D", 0x9),
+            ("Z", 0xA),
+            ("C", 0xB),
+            ("Key4", 0xC),
+            ("R", 0xD),
+            ("F", 0xE),
+            ("V", 0xF),
+        ];
+        let mut keymap = Keymap::empty();
+        for (host_key, key) in layout {
+            keymap.bind(key, vec![host_key.to_string()]);
+        }
+        keymap
+    }
+}
+
+impl Display for KeymapError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KeymapError::Io(message) => write!(f, "Could not read keymap: {}", message),
+            KeymapError::Parse { line, message } => {
+                write!(f, "Keymap line {}: {}", line, message)
+            }
+            KeymapError::DuplicateBinding { host_key, keys } => {
+                let keys: Vec<String> = keys.iter().map(|key| format!("{:X}", key)).collect();
+                write!(
+                    f,
+                    "Host key {} is bound to more than one keypad key: {}.",
+                    host_key,
+                    keys.join(", ")
+                )
+            }
+            KeymapError::MissingBindings(keys) => {
+                let keys: Vec<String> = keys.iter().map(|key| format!("{:X}", key)).collect();
+                write!(f, "Keypad keys without a binding: {}.", keys.join(", "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for KeymapError {}
+
+enum Section {
+    Keymap,
+    Rom(String),
+}
+
+///Removes a trailing `#` comment, ignoring any `#` inside quotes.
+fn strip_comment(line: &str) -> &str {
+    let mut in_quotes = false;
+    for (i, character) in line.char_indices() {
+        match character {
+            '"' => in_quotes = !in_quotes,
+            '#' if !in_quotes => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+///Parses a hex digit, optionally quoted or prefixed with `0x`.
+fn parse_hex_key(text: &str) -> Option<u8> {
+    let text = parse_string(text).unwrap_or_else(|| text.to_string());
+    let digits = text.trim_start_matches("0x");
+    match digits.len() {
+        1 => u8::from_str_radix(digits, 16).ok(),
+        _ => None,
+    }
+}
+
+fn parse_string(text: &str) -> Option<String> {
+    let inner = text.strip_prefix('"')?.strip_suffix('"')?;
+    match inner.contains('"') {
+        true => None,
+        false => Some(inner.to_string()),
+    }
+}
+
+///Parses either a single quoted key name or a list of them.
+fn parse_host_keys(text: &str) -> Option<Vec<String>> {
+    match text.strip_prefix('[') {
+        Some(list) => list
+            .strip_suffix(']')?
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(parse_string)
+            .collect(),
+        None => parse_string(text).map(|host_key| vec![host_key]),
+    }
+}
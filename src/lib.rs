@@ -0,0 +1,76 @@
+//! A CHIP-8 interpreter with SUPER-CHIP and XO-CHIP extensions.
+//!
+//! The core ([`Chip8Computer`] and the [`cpu`], [`memory`], [`frame_buffer`] and [`input`] state it is made of)
+//! has no windowing dependencies, so it can be embedded in test harnesses and other programs.
+//! The winit/pixels front end lives in the `display` module behind the `window` feature, which is on by default.
+//!
+//! ```
+//! use chip8::Chip8Computer;
+//! use std::sync::mpsc::channel;
+//!
+//! let (response_sender, _response_receiver) = channel();
+//! let mut computer = Chip8Computer::new(response_sender);
+//! // CLS, then LD V0, 0x2A
+//! computer.load_rom(vec![0x00, 0xE0, 0x60, 0x2A]).unwrap();
+//! computer.tick().unwrap();
+//! computer.tick().unwrap();
+//! assert_eq!(computer.cpu.data_registers[0], 0x2A);
+//! ```
+//!
+//! To run the interpreter on its own thread, use [`ThreadedEmulator::initialize`] and drive it with
+//! [`EmulatorCommand`]s.
+
+#[cfg(feature = "debug")]
+extern crate lazy_static;
+#[cfg(feature = "window")]
+extern crate pixels;
+extern crate png;
+extern crate rand;
+extern crate rand_chacha;
+#[cfg(feature = "window")]
+extern crate winit;
+
+pub mod assembler;
+pub mod breakpoint;
+pub mod computer;
+mod container;
+pub mod cpu;
+#[cfg(feature = "debug")]
+mod debug;
+pub mod debugger;
+pub mod decode_cache;
+pub mod disassembler;
+#[cfg(feature = "window")]
+pub mod display;
+pub mod error;
+pub mod frame_buffer;
+pub mod headless;
+pub mod input;
+pub mod instruction;
+pub mod keymap;
+pub mod memory;
+pub mod movie;
+pub mod opcode;
+pub mod quirks;
+pub mod rewind;
+pub mod rng;
+pub mod save_state;
+pub mod threading;
+mod undo;
+
+pub use breakpoint::{Breakpoint, BreakpointHit};
+pub use computer::{Chip8Computer, TimingMode};
+pub use cpu::Cpu;
+pub use error::{ExecutionError, RomTooLarge};
+pub use frame_buffer::FrameBuffer;
+pub use headless::{Benchmark, RunLimits, RunOutcome};
+pub use input::Input;
+pub use instruction::Instruction;
+pub use keymap::Keymap;
+pub use memory::Memory;
+pub use movie::{Movie, MovieError};
+pub use opcode::Opcode;
+pub use quirks::Quirks;
+pub use rng::{Chip8Rng, RngState};
+pub use save_state::{SaveState, SaveStateError};
+pub use threading::{EmulatorCommand, EmulatorResponse, ThreadedEmulator};
@@ -1,51 +1,474 @@
-mod computer;
-mod cpu;
-mod debug;
-mod display;
-mod frame_buffer;
-mod input;
-mod instruction;
-mod memory;
-use computer::{Chip8Computer, ThreadedEmulator};
-use display::ProgramDisplay;
-use instruction::Instruction;
+extern crate chip8;
+
+mod cli;
+
+use chip8::assembler::assemble_to_fit;
+use chip8::computer::Chip8Computer;
+use chip8::debugger::Debugger;
+use chip8::disassembler::disassemble_rom;
+#[cfg(feature = "window")]
+use chip8::display::ProgramDisplay;
+use chip8::frame_buffer::FrameBuffer;
+use chip8::headless::{self, RunLimits, RunOutcome};
+#[cfg(feature = "window")]
+use chip8::keymap::Keymap;
+use chip8::memory::{PROGRAM_START, RAM_SIZE, XO_CHIP_RAM_SIZE};
+use chip8::movie::{rom_checksum, Movie, MovieError};
+use chip8::threading::{EmulatorCommand, ThreadedEmulator};
+use cli::{BenchmarkOptions, CliError, Command, Options, USAGE};
+use std::fmt::Display;
 use std::fs::File;
-use std::io::Read;
-use std::path::PathBuf;
-extern crate lazy_static;
-extern crate pixels;
-extern crate winit;
+use std::io::{BufWriter, Read};
+use std::path::{Path, PathBuf};
+use std::process::exit;
+use std::sync::mpsc::channel;
+use std::time::Duration;
+
+///The ROM couldn't be loaded.
+const EXIT_ROM_ERROR: i32 = 1;
+///The command line arguments were invalid.
+const EXIT_USAGE_ERROR: i32 = 2;
+///The program stopped because an instruction couldn't be executed.
+const EXIT_EXECUTION_ERROR: i32 = 3;
+///The screen of a headless run or an assembled ROM couldn't be written out.
+const EXIT_OUTPUT_ERROR: i32 = 4;
+///A source file couldn't be assembled.
+const EXIT_ASSEMBLER_ERROR: i32 = 5;
+///A movie couldn't be played back, or a headless run playing one back desynced.
+const EXIT_MOVIE_ERROR: i32 = 6;
+
+///How many frames of history are kept for rewinding in the window and the debugger, 10 seconds.
+const REWIND_FRAMES: usize = 600;
+///How many instructions the debugger can undo.
+const UNDO_INSTRUCTIONS: usize = 100_000;
 
 fn main() {
-    let roms: [String; 6] = [
-        "roms/1-chip8-logo.ch8".to_string(),
-        "roms/2-ibm-logo.ch8".to_string(),
-        "roms/3-corax+.ch8".to_string(),
-        "roms/4-flags.ch8".to_string(),
-        "roms/c8_test.c8".to_string(),
-        "roms/test_opcode.ch8".to_string(),
-    ];
-    let bytes = read_bytes_from_file(PathBuf::from(roms[3].clone()));
+    let options = match Command::parse(std::env::args().skip(1)) {
+        Ok(Command::Run(options)) => options,
+        Ok(Command::Disassemble { rom_path }) => {
+            match read_rom(&rom_path, XO_CHIP_RAM_SIZE - PROGRAM_START) {
+                Ok(bytes) => print!("{}", disassemble_rom(&bytes)),
+                Err(message) => {
+                    eprintln!("{message}");
+                    exit(EXIT_ROM_ERROR);
+                }
+            }
+            return;
+        }
+        Ok(Command::Assemble {
+            source_path,
+            output_path,
+            xo_chip,
+        }) => exit(assemble_file(&source_path, &output_path, xo_chip)),
+        Ok(Command::Benchmark(options)) => exit(run_benchmarks(&options)),
+        Err(CliError::HelpRequested) => {
+            print!("{USAGE}");
+            return;
+        }
+        Err(CliError::Invalid(message)) => {
+            eprintln!("{message}\n\n{USAGE}");
+            exit(EXIT_USAGE_ERROR);
+        }
+    };
+
+    let program_space = match options.xo_chip {
+        true => XO_CHIP_RAM_SIZE - PROGRAM_START,
+        false => RAM_SIZE - PROGRAM_START,
+    };
+    let bytes = match read_rom(&options.rom_path, program_space) {
+        Ok(bytes) => bytes,
+        Err(message) => {
+            eprintln!("{message}");
+            exit(EXIT_ROM_ERROR);
+        }
+    };
+    let movie = match &options.movie_path {
+        Some(movie_path) => match read_movie(movie_path, &bytes, options.xo_chip) {
+            Ok(movie) => Some(movie),
+            Err(message) => {
+                eprintln!("{message}");
+                exit(EXIT_MOVIE_ERROR);
+            }
+        },
+        None => None,
+    };
+
+    if options.headless {
+        exit(match options.debugger {
+            true => run_debugger(&options, bytes, movie),
+            false => run_headless(&options, bytes, movie),
+        });
+    }
+    run_window(&options, bytes, movie);
+}
+
+#[cfg(not(feature = "window"))]
+fn run_window(_options: &Options, _rom_bytes: Vec<u8>, _movie: Option<Vec<u8>>) {
+    eprintln!("This build doesn't include the window, run with --headless instead.");
+    exit(EXIT_USAGE_ERROR);
+}
+
+///Runs the emulator on its own thread, showing the screen in a window. This never returns.
+#[cfg(feature = "window")]
+fn run_window(options: &Options, rom_bytes: Vec<u8>, movie: Option<Vec<u8>>) {
+    let keymap = load_keymap(options);
     let (sender_to_emulator, receiver_from_emulator) = Chip8Computer::initialize();
-    emulator_instance.load_rom(bytes);
+    for command in setup_commands(options, rom_bytes, movie) {
+        sender_to_emulator
+            .send(command)
+            .expect("The emulator thread stopped before it could be started.");
+    }
 
-   
-        for _ in 0..1000 {
-            emulator_instance.execute_loop();
-            // std::thread::sleep(std::time::Duration::from_secs_f32(0.5));
+    let window_name = match options.rom_path.file_name() {
+        Some(name) => format!("CHIP-8 - {}", name.to_string_lossy()),
+        None => "CHIP-8".to_string(),
+    };
+    let mut display = ProgramDisplay::new(
+        64,
+        32,
+        options.scale,
+        window_name,
+        receiver_from_emulator,
+        sender_to_emulator.clone(),
+        keymap,
+    )
+    .save_slots(options.rom_path.clone());
+    if let Some(record_path) = &options.record_path {
+        display = display.record_to(record_path.clone());
+    }
+    if options.debugger {
+        let (response_sender, response_receiver) = channel();
+        display = display.forward_responses(response_sender);
+        let debugger = Debugger::new(sender_to_emulator, response_receiver);
+        std::thread::spawn(move || {
+            debugger.run();
+            exit(0);
+        });
+    }
+    display.run();
+}
+
+///Returns the commands that configure the emulator according to the options, load the ROM, start recording or
+///playing back a movie and start the program.
+fn setup_commands(
+    options: &Options,
+    rom_bytes: Vec<u8>,
+    movie: Option<Vec<u8>>,
+) -> Vec<EmulatorCommand> {
+    let mut commands = Vec::new();
+    if options.xo_chip {
+        commands.push(EmulatorCommand::EnableXoChip);
+    }
+    commands.push(EmulatorCommand::SetQuirks(options.quirks));
+    commands.push(EmulatorCommand::SetClockSpeed(options.clock_speed_hz));
+    if let Some(palette) = options.palette {
+        commands.push(EmulatorCommand::SetPalette(palette));
+    }
+    commands.push(EmulatorCommand::SetDebugOutput(options.debug));
+    if let Some(seed) = options.seed {
+        commands.push(EmulatorCommand::SetSeed(seed));
+    }
+    //Only interactive runs can be rewound, so plain headless runs don't pay for recording every frame.
+    if !options.headless || options.debugger {
+        commands.push(EmulatorCommand::SetRewindFrames(REWIND_FRAMES));
+    }
+    if options.debugger {
+        commands.push(EmulatorCommand::SetUndoLimit(UNDO_INSTRUCTIONS));
+    }
+    commands.push(EmulatorCommand::LoadRom(rom_bytes));
+    if options.record_path.is_some() {
+        commands.push(EmulatorCommand::StartRecording);
+    }
+    if let Some(movie) = movie {
+        commands.push(EmulatorCommand::PlayMovie(movie));
+    }
+    //The debugger starts the program itself once the user asks it to.
+    if !options.debugger {
+        commands.push(EmulatorCommand::Go);
+    }
+    commands
+}
+
+///Runs the emulator on its own thread without a window, controlled from the debugger on the terminal.
+///Returns the process exit code once the debugger is quit.
+fn run_debugger(options: &Options, rom_bytes: Vec<u8>, movie: Option<Vec<u8>>) -> i32 {
+    let (sender_to_emulator, receiver_from_emulator) = Chip8Computer::initialize();
+    for command in setup_commands(options, rom_bytes, movie) {
+        if sender_to_emulator.send(command).is_err() {
+            eprintln!("The emulator thread stopped before it could be started.");
+            return EXIT_EXECUTION_ERROR;
         }
+    }
+    Debugger::new(sender_to_emulator, receiver_from_emulator).run();
+    0
 }
 
-fn read_bytes_from_file(file_path: PathBuf) -> Vec<u8> {
-    let mut file = match File::open(file_path) {
-        Ok(file) => file,
-        Err(e) => panic!("Error opening the file: {}.", e),
+///Runs the emulator on this thread without a window until the program exits, fails, loops or reaches a limit,
+///then writes out the screen.
+///
+///Timers count down every `clock_speed_hz / 60` instructions so runs are reproducible.
+///Returns the process exit code.
+fn run_headless(options: &Options, rom_bytes: Vec<u8>, movie: Option<Vec<u8>>) -> i32 {
+    let (response_sender, _) = channel();
+    let mut computer = Chip8Computer::new(response_sender);
+    for command in setup_commands(options, rom_bytes, movie) {
+        if let Err(e) = computer.match_received_command(command) {
+            eprintln!("{e}");
+            return EXIT_EXECUTION_ERROR;
+        }
+    }
+
+    let limits = RunLimits {
+        frames: options.frame_limit,
+        instructions: options.instruction_limit,
     };
+    let outcome = headless::run(&mut computer, options.clock_speed_hz as u32 / 60, limits);
+    eprintln!("{outcome}");
 
-    let mut byte_buffer = Vec::new();
-    if let Err(e) = file.read_to_end(&mut byte_buffer) {
-        panic!("Error reading the file: {}.", e)
+    if let Err(message) = write_screen(&computer.frame_buffer, options) {
+        eprintln!("{message}");
+        return EXIT_OUTPUT_ERROR;
+    }
+    if let (Some(record_path), Some(movie)) = (&options.record_path, computer.stop_recording()) {
+        if let Err(e) = std::fs::write(record_path, movie.to_bytes()) {
+            eprintln!("Couldn't write {}: {}", record_path.display(), e);
+            return EXIT_OUTPUT_ERROR;
+        }
+    }
+    match outcome {
+        RunOutcome::Halted(_) => EXIT_EXECUTION_ERROR,
+        RunOutcome::MovieFinished(playback) if playback.desync.is_some() => EXIT_MOVIE_ERROR,
+        _ => 0,
+    }
+}
+
+///Reads a movie file, checking that it can be played back with the ROM. Returns the movie's bytes.
+fn read_movie(movie_path: &Path, rom_bytes: &[u8], xo_chip: bool) -> Result<Vec<u8>, String> {
+    let bytes = std::fs::read(movie_path)
+        .map_err(|e| format!("Error reading {}: {}.", movie_path.display(), e))?;
+    let movie = Movie::from_bytes(&bytes)
+        .map_err(|e| format!("Error reading {}: {}", movie_path.display(), e))?;
+    let loaded = rom_checksum(rom_bytes);
+    let error = match (movie.rom_checksum == loaded, xo_chip && !movie.xo_chip) {
+        (false, _) => MovieError::WrongRom {
+            recorded: movie.rom_checksum,
+            loaded,
+        },
+        (true, true) => MovieError::XoChipEnabled,
+        (true, false) => return Ok(bytes),
+    };
+    Err(format!("Can't play {}: {}", movie_path.display(), error))
+}
+
+///Writes the screen to the output file given on the command line, or prints it as text if there isn't one.
+fn write_screen(frame_buffer: &FrameBuffer, options: &Options) -> Result<(), String> {
+    let output_path = match &options.output_path {
+        Some(output_path) => output_path,
+        None => {
+            print!("{}", frame_buffer.get_buffer_as_string());
+            return Ok(());
+        }
     };
+    let write_error = |e: &dyn Display| format!("Error writing {}: {}.", output_path.display(), e);
+
+    let is_png = output_path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("png"));
+    match is_png {
+        true => {
+            let file = File::create(output_path).map_err(|e| write_error(&e))?;
+            headless::write_png(frame_buffer, options.scale, BufWriter::new(file))
+                .map_err(|e| write_error(&e))
+        }
+        false => std::fs::write(output_path, frame_buffer.get_buffer_as_string())
+            .map_err(|e| write_error(&e)),
+    }
+}
+
+///Assembles a source file and writes the ROM out. Returns the process exit code.
+fn assemble_file(source_path: &Path, output_path: &Path, xo_chip: bool) -> i32 {
+    let source = match std::fs::read_to_string(source_path) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("Error reading {}: {}.", source_path.display(), e);
+            return EXIT_ROM_ERROR;
+        }
+    };
+    let program_space = match xo_chip {
+        true => XO_CHIP_RAM_SIZE - PROGRAM_START,
+        false => RAM_SIZE - PROGRAM_START,
+    };
+    let rom = match assemble_to_fit(&source, program_space) {
+        Ok(rom) => rom,
+        Err(e) => {
+            eprintln!("{}: {}", source_path.display(), e);
+            return EXIT_ASSEMBLER_ERROR;
+        }
+    };
+    if let Err(e) = std::fs::write(output_path, &rom) {
+        eprintln!("Error writing {}: {}.", output_path.display(), e);
+        return EXIT_OUTPUT_ERROR;
+    }
+    eprintln!("Wrote {} bytes to {}.", rom.len(), output_path.display());
+    0
+}
+
+///Runs each ROM for the same number of instructions and prints how many it executed per second.
+///Returns the process exit code.
+fn run_benchmarks(options: &BenchmarkOptions) -> i32 {
+    let rom_paths = match find_roms(&options.rom_paths) {
+        Ok(rom_paths) => rom_paths,
+        Err(message) => {
+            eprintln!("{message}");
+            return EXIT_ROM_ERROR;
+        }
+    };
+    let program_space = match options.xo_chip {
+        true => XO_CHIP_RAM_SIZE - PROGRAM_START,
+        false => RAM_SIZE - PROGRAM_START,
+    };
+    let names: Vec<String> = rom_paths
+        .iter()
+        .map(|rom_path| rom_path.display().to_string())
+        .collect();
+    let width = names.iter().map(String::len).max().unwrap_or(0).max(5);
+
+    let mut exit_code = 0;
+    let mut total_instructions = 0;
+    let mut total_elapsed = Duration::ZERO;
+    for (rom_path, name) in rom_paths.iter().zip(&names) {
+        let rom_bytes = match read_rom(rom_path, program_space) {
+            Ok(rom_bytes) => rom_bytes,
+            Err(message) => {
+                eprintln!("{message}");
+                exit_code = EXIT_ROM_ERROR;
+                continue;
+            }
+        };
+        let (response_sender, _) = channel();
+        let mut computer = Chip8Computer::new(response_sender);
+        if options.xo_chip {
+            computer.enable_xo_chip();
+        }
+        computer.quirks = options.quirks;
+        if let Err(e) = computer.load_rom(rom_bytes) {
+            eprintln!("{}: {e}", rom_path.display());
+            exit_code = EXIT_ROM_ERROR;
+            continue;
+        }
+
+        let instructions_per_frame = options.clock_speed_hz as u32 / 60;
+        match headless::benchmark(&mut computer, instructions_per_frame, options.instructions) {
+            Ok(result) => {
+                let note = match result.exited {
+                    true => "  (exited early)",
+                    false => "",
+                };
+                println!(
+                    "{:<width$}  {:>14.0} instructions/s{}",
+                    name,
+                    result.instructions_per_second(),
+                    note,
+                    width = width
+                );
+                total_instructions += result.instructions;
+                total_elapsed += result.elapsed;
+            }
+            Err(e) => {
+                println!("{:<width$}  {}", name, e, width = width);
+                exit_code = EXIT_EXECUTION_ERROR;
+            }
+        }
+    }
+
+    if total_instructions > 0 {
+        let total = headless::Benchmark {
+            instructions: total_instructions,
+            elapsed: total_elapsed,
+            exited: false,
+        };
+        println!(
+            "{:<width$}  {:>14.0} instructions/s",
+            "Total",
+            total.instructions_per_second(),
+            width = width
+        );
+    }
+    exit_code
+}
+
+///Expands directories into the files directly inside them, sorted by name.
+///The `roms` directory in the working directory is used when no paths are given.
+fn find_roms(paths: &[PathBuf]) -> Result<Vec<PathBuf>, String> {
+    let default_paths = [PathBuf::from("roms")];
+    let paths = match paths.is_empty() {
+        true => &default_paths[..],
+        false => paths,
+    };
+
+    let mut rom_paths = Vec::new();
+    for path in paths {
+        if !path.is_dir() {
+            rom_paths.push(path.clone());
+            continue;
+        }
+        let read_error = |e: std::io::Error| format!("Error reading {}: {}.", path.display(), e);
+        let mut directory_roms = Vec::new();
+        for entry in std::fs::read_dir(path).map_err(read_error)? {
+            let entry_path = entry.map_err(read_error)?.path();
+            if entry_path.is_file() {
+                directory_roms.push(entry_path);
+            }
+        }
+        directory_roms.sort();
+        rom_paths.extend(directory_roms);
+    }
+    match rom_paths.is_empty() {
+        true => Err("No ROMs were found to benchmark.".to_string()),
+        false => Ok(rom_paths),
+    }
+}
+
+///Loads the keymap given on the command line, or `keymap.toml` from the working directory if there is one.
+///Otherwise the default QWERTY layout is used.
+#[cfg(feature = "window")]
+fn load_keymap(options: &Options) -> Keymap {
+    let keymap_path = match &options.keymap_path {
+        Some(keymap_path) => keymap_path.as_path(),
+        None => Path::new("keymap.toml"),
+    };
+    if options.keymap_path.is_none() && !keymap_path.exists() {
+        return Keymap::default();
+    }
+    let rom_name = options.rom_path.file_name().and_then(|name| name.to_str());
+    match Keymap::load(keymap_path, rom_name) {
+        Ok(keymap) => keymap,
+        Err(e) => {
+            eprintln!("{e} Falling back to the default keymap.");
+            Keymap::default()
+        }
+    }
+}
+
+///Reads a ROM, checking that it isn't empty and fits in the given amount of program space.
+fn read_rom(file_path: &Path, program_space: usize) -> Result<Vec<u8>, String> {
+    let mut file = File::open(file_path)
+        .map_err(|e| format!("Error opening the ROM {}: {}.", file_path.display(), e))?;
+
+    let mut byte_buffer = Vec::new();
+    file.read_to_end(&mut byte_buffer)
+        .map_err(|e| format!("Error reading the ROM {}: {}.", file_path.display(), e))?;
 
-    return byte_buffer;
+    if byte_buffer.is_empty() {
+        return Err(format!("The ROM {} is empty.", file_path.display()));
+    }
+    if byte_buffer.len() > program_space {
+        return Err(format!(
+            "The ROM {} is {} bytes, but only {} bytes of program space are available.",
+            file_path.display(),
+            byte_buffer.len(),
+            program_space
+        ));
+    }
+    Ok(byte_buffer)
 }
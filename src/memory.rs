@@ -1,13 +1,27 @@
 use std::fmt::Display;
 
+use crate::error::RomTooLarge;
+
+///Where the 10 byte tall SUPER-CHIP font is stored, directly after the regular hex sprites.
+pub const BIG_FONT_ADDRESS: u16 = 0x50;
+
+///How much RAM the original CHIP-8 has.
+pub const RAM_SIZE: usize = 0x1000;
+///How much RAM is available in XO-CHIP mode.
+pub const XO_CHIP_RAM_SIZE: usize = 0x10000;
+
+///Where programs are loaded into memory.
+pub const PROGRAM_START: usize = 0x200;
+
+#[derive(Clone)]
 pub struct Memory {
-    pub ram: [u8; 4096],
+    pub ram: Vec<u8>,
     pub stack: [u16; 16],
 }
 
 impl Memory {
     pub fn new() -> Self {
-        let mut ram = [0; 4096];
+        let mut ram = vec![0; RAM_SIZE];
         let hex_sprites = [
             0xF0, 0x90, 0x90, 0x90, 0xF0, 0x20, 0x60, 0x20, 0x20, 0x70, 0xF0, 0x10, 0xF0, 0x80,
             0xF0, 0xF0, 0x10, 0xF0, 0x10, 0xF0, 0x90, 0x90, 0xF0, 0x10, 0x10, 0xF0, 0x80, 0xF0,
@@ -17,23 +31,55 @@ impl Memory {
             0xF0, 0x80, 0xF0, 0x80, 0xF0, 0xF0, 0x80, 0xF0, 0x80, 0x80,
         ];
 
+        let big_hex_sprites = [
+            0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x18, 0x78, 0x78, 0x18,
+            0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0,
+            0xFF, 0xFF, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC3, 0xC3,
+            0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF,
+            0x03, 0x03, 0xFF, 0xFF, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF,
+            0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF, 0xC3, 0xC3,
+            0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03,
+            0xFF, 0xFF, 0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xFC, 0xFC,
+            0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0,
+            0xC0, 0xC3, 0xFF, 0x3C, 0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC,
+            0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xFF, 0xFF, 0xC0, 0xC0,
+            0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0,
+        ];
+
         ram[0x00..0x50].copy_from_slice(&hex_sprites);
+        ram[0x50..0xF0].copy_from_slice(&big_hex_sprites);
         Memory {
             ram,
             stack: [0; 16],
         }
     }
 
-    pub fn _new_from_rom(rom_bytes: Vec<u8>) -> Self {
+    ///Grows RAM to the 64 KiB used by XO-CHIP, keeping its current contents.
+    pub fn extend_to_xo_chip(&mut self) {
+        self.ram.resize(XO_CHIP_RAM_SIZE, 0);
+    }
+
+    ///Returns how many bytes of memory are available for a program.
+    pub fn program_space(&self) -> usize {
+        self.ram.len() - PROGRAM_START
+    }
+
+    pub fn _new_from_rom(rom_bytes: Vec<u8>) -> Result<Self, RomTooLarge> {
         let mut memory = Memory::new();
-        memory.store_rom(rom_bytes);
-        memory
+        memory.store_rom(rom_bytes)?;
+        Ok(memory)
     }
 
-    pub fn store_rom(&mut self, rom_bytes: Vec<u8>) {
-        for (i, byte) in rom_bytes.into_iter().enumerate() {
-            self.ram[i + 0x200] = byte;
+    ///Copies the ROM into memory at the start of the program space. Nothing is changed if it doesn't fit.
+    pub fn store_rom(&mut self, rom_bytes: Vec<u8>) -> Result<(), RomTooLarge> {
+        if rom_bytes.len() > self.program_space() {
+            return Err(RomTooLarge {
+                size: rom_bytes.len(),
+                program_space: self.program_space(),
+            });
         }
+        self.ram[PROGRAM_START..PROGRAM_START + rom_bytes.len()].copy_from_slice(&rom_bytes);
+        Ok(())
     }
 
     pub fn read_byte<T>(&self, address: T) -> u8
@@ -50,27 +96,39 @@ impl Memory {
             .map(|offset| self.read_byte(starting_address.into() + offset))
             .collect()
     }
+    ///Returns the first address in the given range that falls outside of RAM, if any.
+    pub fn find_out_of_bounds(&self, starting_address: usize, num_bytes: usize) -> Option<usize> {
+        match starting_address + num_bytes > self.ram.len() {
+            true => Some(std::cmp::max(starting_address, self.ram.len())),
+            false => None,
+        }
+    }
+
     pub fn read_instruction(&self, address: u16) -> u16 {
-        let bytes = self.read_bytes(address, 2);
-        let instruction_value = ((bytes[0] as u16) << 8) + bytes[1] as u16;
-        instruction_value
+        let address = address as usize;
+        u16::from_be_bytes([self.ram[address], self.ram[address + 1]])
     }
 
     pub fn print_rom(&self) {
-        let rom_view = self.view_memory_section(0x200, self.ram.len());
+        let rom_view = self.view_memory_section(PROGRAM_START, self.ram.len());
         println!("{rom_view}");
     }
 
-    fn view_memory_section(&self, starting_index: usize, ending_index: usize) -> String {
+    ///Returns the bytes from `starting_index` up to `ending_index` as rows of 16 bytes, each row labelled with
+    ///its address.
+    pub fn view_memory_section(&self, starting_index: usize, ending_index: usize) -> String {
         let mut return_string: String = "".into();
 
         let ending_index = std::cmp::min(ending_index, self.ram.len());
         let bytes: Vec<u8> = self.ram[starting_index..ending_index].into();
 
-        for (i, chunk) in bytes.chunks(16).into_iter().enumerate() {
+        for (i, chunk) in bytes.chunks(16).enumerate() {
             return_string.push_str(&format!("0x{:04x}:    ", (i * 16) + starting_index));
             for bytes in chunk.chunks(2) {
-                return_string.push_str(&format!("0x{:02x}{:02x}  ", bytes[0], bytes[1]));
+                match bytes {
+                    [high, low] => return_string.push_str(&format!("0x{:02x}{:02x}  ", high, low)),
+                    _ => return_string.push_str(&format!("0x{:02x}  ", bytes[0])),
+                }
             }
             return_string.push('\n');
         }
@@ -113,7 +171,7 @@ impl Memory {
                 }
             } else {
                 num_consecutive_zeros = 0;
-                if start_of_interesting_data == None {
+                if start_of_interesting_data.is_none() {
                     start_of_interesting_data = Some(i);
                 }
             }
@@ -130,15 +188,15 @@ impl Memory {
         let _ = std::process::Command::new(&clear_command).status();
 
         for (i, chunk) in self.ram.chunks_exact(5).enumerate() {
-            if chunk.into_iter().all(|byte| *byte == 0) {
+            if chunk.iter().all(|byte| *byte == 0) {
                 continue;
             }
 
             let mut sprite = "".to_string();
             sprite.push_str(&format!("0x{:04x}\n", i * 5));
-            for i in 0..5 {
+            for row in chunk {
                 for j in (0..8).rev() {
-                    match (chunk[i] >> j) & 0x01 == 1 {
+                    match (row >> j) & 0x01 == 1 {
                         true => sprite.push('\u{2588}'),
                         false => sprite.push(' '),
                     }
@@ -154,6 +212,12 @@ impl Memory {
     }
 }
 
+impl Default for Memory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Display for Memory {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.get_only_interesting_memory())
@@ -0,0 +1,345 @@
+use std::fmt::Display;
+
+use crate::container::{self, crc32, ContainerError, Reader};
+use crate::frame_buffer::FrameBuffer;
+use crate::quirks::Quirks;
+
+///The bytes every movie starts with.
+pub const MAGIC: [u8; 4] = *b"CH8M";
+///The version of the format written by [`Movie::to_bytes`].
+pub const VERSION: u16 = 1;
+///How many frames pass between the screen checksums a movie records, once a second.
+pub const CHECKSUM_INTERVAL: usize = 60;
+
+///Describes why a movie could not be read or played.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MovieError {
+    ///The data doesn't start with the movie magic bytes.
+    NotAMovie,
+    ///The movie was written in a format version this build doesn't know.
+    UnsupportedVersion(u16),
+    ///The data ends before the movie does.
+    Truncated,
+    ///The contents don't match their checksum, so the movie is corrupted.
+    ChecksumMismatch,
+    ///The contents can't be a movie, e.g. a flag that is neither 0 nor 1.
+    Invalid(String),
+    ///The movie was recorded with a different ROM than the one loaded, identified by their CRC-32.
+    WrongRom { recorded: u32, loaded: u32 },
+    ///The movie was recorded without XO-CHIP's extra memory, which can't be removed again once enabled.
+    XoChipEnabled,
+}
+
+impl Display for MovieError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MovieError::NotAMovie => write!(f, "This isn't a movie."),
+            MovieError::UnsupportedVersion(version) => write!(
+                f,
+                "The movie has version {}, but only version {} is supported.",
+                version, VERSION
+            ),
+            MovieError::Truncated => write!(f, "The movie is truncated."),
+            MovieError::ChecksumMismatch => {
+                write!(f, "The movie's checksum doesn't match, it is corrupted.")
+            }
+            MovieError::Invalid(message) => write!(f, "Invalid movie: {}", message),
+            MovieError::WrongRom { recorded, loaded } => write!(
+                f,
+                "The movie was recorded with a different ROM, its CRC-32 is 0x{:08X} rather than 0x{:08X}.",
+                recorded, loaded
+            ),
+            MovieError::XoChipEnabled => write!(
+                f,
+                "The movie was recorded without XO-CHIP, which is enabled."
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MovieError {}
+
+impl From<ContainerError> for MovieError {
+    fn from(e: ContainerError) -> Self {
+        match e {
+            ContainerError::WrongMagic => MovieError::NotAMovie,
+            ContainerError::UnsupportedVersion(version) => MovieError::UnsupportedVersion(version),
+            ContainerError::Truncated => MovieError::Truncated,
+            ContainerError::ChecksumMismatch => MovieError::ChecksumMismatch,
+            ContainerError::Invalid(message) => MovieError::Invalid(message),
+        }
+    }
+}
+
+///A recording of a program being played, which replays exactly the same way.
+///
+///A movie starts with the ROM just loaded and records everything a run depends on: the ROM, the random number
+///generator's seed, the quirks and for every 60 Hz frame which keys were held and how many instructions were
+///executed. The screen's checksum is recorded regularly, so that a replay going differently is noticed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Movie {
+    ///The CRC-32 of the ROM, see [`rom_checksum`].
+    pub rom_checksum: u32,
+    pub seed: u64,
+    pub quirks: Quirks,
+    ///Whether XO-CHIP's 64 KiB of memory were enabled.
+    pub xo_chip: bool,
+    pub frames: Vec<MovieFrame>,
+}
+
+///What happened during a single frame of a [`Movie`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MovieFrame {
+    ///The keys held down for the whole frame, with bit n set for key n.
+    pub keys: u16,
+    ///How many instructions were executed during the frame.
+    pub instructions: u32,
+    ///The checksum of the screen at the end of the frame, see [`screen_checksum`].
+    ///Recorded for every [`CHECKSUM_INTERVAL`]th frame.
+    pub screen_checksum: Option<u32>,
+}
+
+impl Movie {
+    ///Creates a movie without any frames.
+    pub fn new(rom_checksum: u32, seed: u64, quirks: Quirks, xo_chip: bool) -> Self {
+        Movie {
+            rom_checksum,
+            seed,
+            quirks,
+            xo_chip,
+            frames: Vec::new(),
+        }
+    }
+
+    ///Adds a frame that has just ended, recording the screen's checksum if it is due.
+    pub fn record_frame(&mut self, keys: &[bool; 16], instructions: u32, screen: &FrameBuffer) {
+        let screen_checksum = match (self.frames.len() + 1) % CHECKSUM_INTERVAL {
+            0 => Some(screen_checksum(screen)),
+            _ => None,
+        };
+        self.frames.push(MovieFrame {
+            keys: keys
+                .iter()
+                .enumerate()
+                .fold(0, |bits, (key, &pressed)| bits | (pressed as u16) << key),
+            instructions,
+            screen_checksum,
+        });
+    }
+
+    ///Serializes the movie behind the same header as a save state, but with [`MAGIC`] and [`VERSION`] of its own.
+    ///The frames follow the settings, each with a flag byte saying whether a screen checksum comes after it.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut contents = Vec::new();
+        contents.extend_from_slice(&self.rom_checksum.to_be_bytes());
+        contents.extend_from_slice(&self.seed.to_be_bytes());
+        let quirks = &self.quirks;
+        contents.extend(
+            [
+                quirks.shift,
+                quirks.jump_with_vx,
+                quirks.memory_increment,
+                quirks.vf_reset,
+                quirks.clipping,
+                quirks.display_wait,
+                self.xo_chip,
+            ]
+            .iter()
+            .map(|&flag| flag as u8),
+        );
+
+        contents.extend_from_slice(&(self.frames.len() as u32).to_be_bytes());
+        for frame in &self.frames {
+            contents.extend_from_slice(&frame.keys.to_be_bytes());
+            contents.extend_from_slice(&frame.instructions.to_be_bytes());
+            match frame.screen_checksum {
+                Some(checksum) => {
+                    contents.push(1);
+                    contents.extend_from_slice(&checksum.to_be_bytes());
+                }
+                None => contents.push(0),
+            }
+        }
+
+        container::wrap(MAGIC, VERSION, &contents)
+    }
+
+    ///Parses a movie written by [`Movie::to_bytes`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, MovieError> {
+        let contents = container::unwrap(MAGIC, VERSION, bytes)?;
+        let mut reader = Reader::new(contents);
+        let rom_checksum = reader.u32()?;
+        let seed = u64::from_be_bytes(reader.array()?);
+        let quirks = Quirks {
+            shift: reader.bool()?,
+            jump_with_vx: reader.bool()?,
+            memory_increment: reader.bool()?,
+            vf_reset: reader.bool()?,
+            clipping: reader.bool()?,
+            display_wait: reader.bool()?,
+        };
+        let xo_chip = reader.bool()?;
+        let frame_count = reader.u32()? as usize;
+        //Every frame takes at least 7 bytes, which keeps a damaged count from reserving lots of memory.
+        let mut frames = Vec::with_capacity(frame_count.min(contents.len() / 7));
+        for _ in 0..frame_count {
+            frames.push(MovieFrame {
+                keys: reader.u16()?,
+                instructions: reader.u32()?,
+                screen_checksum: match reader.bool()? {
+                    true => Some(reader.u32()?),
+                    false => None,
+                },
+            });
+        }
+
+        Ok(Movie {
+            rom_checksum,
+            seed,
+            quirks,
+            xo_chip,
+            frames,
+        })
+    }
+}
+
+///Returns the CRC-32 a movie identifies a ROM by.
+pub fn rom_checksum(rom_bytes: &[u8]) -> u32 {
+    crc32(rom_bytes)
+}
+
+///Returns the CRC-32 of everything on the screen, every plane and the resolution.
+pub fn screen_checksum(screen: &FrameBuffer) -> u32 {
+    let mut bytes = Vec::with_capacity(std::mem::size_of_val(&screen.buffer) + 1);
+    for row in screen.buffer.iter().flatten() {
+        bytes.extend_from_slice(&row.to_be_bytes());
+    }
+    bytes.push(screen.high_resolution as u8);
+    crc32(&bytes)
+}
+
+///The screen didn't match the recording when a movie was played back.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Desync {
+    ///The frame, counting from 0, at the end of which the screen was different.
+    pub frame: usize,
+    pub recorded: u32,
+    pub played: u32,
+}
+
+impl Display for Desync {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "The movie desynced, the screen at the end of frame {} has checksum 0x{:08X} rather than 0x{:08X}.",
+            self.frame, self.played, self.recorded
+        )
+    }
+}
+
+///How far playing back a movie has got.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Playback {
+    ///How many frames have been played.
+    pub frame: usize,
+    ///How many frames the movie has.
+    pub frames: usize,
+    ///The first frame at which the screen didn't match the recording, if any.
+    pub desync: Option<Desync>,
+}
+
+impl Playback {
+    pub fn is_finished(&self) -> bool {
+        self.frame >= self.frames
+    }
+}
+
+impl Display for Playback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.is_finished(), self.desync) {
+            (true, None) => write!(f, "Played all {} frames of the movie.", self.frames),
+            (false, None) => write!(f, "Played {} of {} frames.", self.frame, self.frames),
+            (true, Some(desync)) => write!(f, "Played all {} frames. {}", self.frames, desync),
+            (false, Some(desync)) => {
+                write!(
+                    f,
+                    "Played {} of {} frames. {}",
+                    self.frame, self.frames, desync
+                )
+            }
+        }
+    }
+}
+
+///Feeds a movie's keys and frame timing to the machine while it plays back.
+#[derive(Clone, Debug)]
+pub(crate) struct Player {
+    movie: Movie,
+    frame: usize,
+    desync: Option<Desync>,
+}
+
+impl Player {
+    pub(crate) fn new(movie: Movie) -> Self {
+        Player {
+            movie,
+            frame: 0,
+            desync: None,
+        }
+    }
+
+    ///Returns how many instructions the current frame lasts, or `None` once the movie is over.
+    pub(crate) fn frame_length(&self) -> Option<u32> {
+        self.movie
+            .frames
+            .get(self.frame)
+            .map(|frame| frame.instructions)
+    }
+
+    ///Returns the keys held during the current frame, or `None` once the movie is over.
+    pub(crate) fn keys(&self) -> Option<[bool; 16]> {
+        let frame = self.movie.frames.get(self.frame)?;
+        let mut keys = [false; 16];
+        for (key, pressed) in keys.iter_mut().enumerate() {
+            *pressed = frame.keys & (1 << key) != 0;
+        }
+        Some(keys)
+    }
+
+    ///Moves on to the next frame, comparing the screen with the recording. Returns the desync if it is the first.
+    pub(crate) fn end_frame(&mut self, screen: &FrameBuffer) -> Option<Desync> {
+        let recorded = self.movie.frames.get(self.frame)?.screen_checksum;
+        let frame = self.frame;
+        self.frame += 1;
+        let recorded = recorded?;
+        let played = screen_checksum(screen);
+        match recorded == played || self.desync.is_some() {
+            true => None,
+            false => {
+                self.desync = Some(Desync {
+                    frame,
+                    recorded,
+                    played,
+                });
+                self.desync
+            }
+        }
+    }
+
+    ///Goes back to an earlier frame, when the machine has been rewound or an instruction undone.
+    ///A desync that happened after it is forgotten.
+    pub(crate) fn go_back(&mut self, frames: usize) {
+        self.frame = self.frame.saturating_sub(frames);
+        if self.desync.is_some_and(|desync| desync.frame >= self.frame) {
+            self.desync = None;
+        }
+    }
+
+    pub(crate) fn progress(&self) -> Playback {
+        Playback {
+            frame: self.frame,
+            frames: self.movie.frames.len(),
+            desync: self.desync,
+        }
+    }
+}
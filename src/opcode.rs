@@ -0,0 +1,344 @@
+use std::convert::TryFrom;
+use std::fmt::Display;
+
+use crate::instruction::Instruction;
+
+///The index of one of the 16 data registers, V0 to VF.
+pub type Register = u8;
+
+///A decoded instruction, covering CHIP-8, SUPER-CHIP and XO-CHIP.
+///
+///Variants are named after their assembly mnemonics. Where a mnemonic has several forms the variant says which
+///operands it takes, e.g. [`Opcode::SeImmediate`] for `SE Vx, byte` and [`Opcode::Se`] for `SE Vx, Vy`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Opcode {
+    ///00E0: clears the screen.
+    Cls,
+    ///00EE: returns from a subroutine.
+    Ret,
+    ///00Cn: scrolls the screen down by n pixels.
+    Scd(u8),
+    ///00FB: scrolls the screen right by 4 pixels.
+    Scr,
+    ///00FC: scrolls the screen left by 4 pixels.
+    Scl,
+    ///00FD: exits the interpreter.
+    Exit,
+    ///00FE: switches to the 64x32 low resolution mode.
+    Low,
+    ///00FF: switches to the 128x64 high resolution mode.
+    High,
+    ///1nnn: jumps to nnn.
+    Jp(u16),
+    ///2nnn: calls the subroutine at nnn.
+    Call(u16),
+    ///3xkk: skips the next instruction if Vx == kk.
+    SeImmediate(Register, u8),
+    ///4xkk: skips the next instruction if Vx != kk.
+    SneImmediate(Register, u8),
+    ///5xy0: skips the next instruction if Vx == Vy.
+    Se(Register, Register),
+    ///5xy2: stores Vx to Vy in memory starting at I.
+    Save(Register, Register),
+    ///5xy3: loads Vx to Vy from memory starting at I.
+    Load(Register, Register),
+    ///6xkk: Vx = kk.
+    LdImmediate(Register, u8),
+    ///7xkk: Vx += kk, without a carry flag.
+    AddImmediate(Register, u8),
+    ///8xy0: Vx = Vy.
+    Ld(Register, Register),
+    ///8xy1: Vx |= Vy.
+    Or(Register, Register),
+    ///8xy2: Vx &= Vy.
+    And(Register, Register),
+    ///8xy3: Vx ^= Vy.
+    Xor(Register, Register),
+    ///8xy4: Vx += Vy, VF = carry.
+    Add(Register, Register),
+    ///8xy5: Vx -= Vy, VF = not borrow.
+    Sub(Register, Register),
+    ///8xy6: Vx = Vy >> 1 (or Vx >> 1 with the shift quirk), VF = the bit shifted out.
+    Shr(Register, Register),
+    ///8xy7: Vx = Vy - Vx, VF = not borrow.
+    Subn(Register, Register),
+    ///8xyE: Vx = Vy << 1 (or Vx << 1 with the shift quirk), VF = the bit shifted out.
+    Shl(Register, Register),
+    ///9xy0: skips the next instruction if Vx != Vy.
+    Sne(Register, Register),
+    ///Annn: I = nnn.
+    LdI(u16),
+    ///Bnnn: jumps to nnn + V0 (or xnn + Vx with the jump quirk).
+    JpV0(u16),
+    ///Cxkk: Vx = random byte & kk.
+    Rnd(Register, u8),
+    ///Dxyn: draws an n byte sprite from I at (Vx, Vy), or a 16x16 sprite when n is 0.
+    Drw(Register, Register, u8),
+    ///Ex9E: skips the next instruction if the key in Vx is pressed.
+    Skp(Register),
+    ///ExA1: skips the next instruction if the key in Vx isn't pressed.
+    Sknp(Register),
+    ///F000 nnnn: I = the 16-bit address in the next word.
+    LdILong,
+    ///Fn01: selects the bitplanes to draw to.
+    Plane(u8),
+    ///Fx07: Vx = delay timer.
+    LdVxDt(Register),
+    ///Fx0A: waits for a key press and release and stores the key in Vx.
+    LdVxK(Register),
+    ///Fx15: delay timer = Vx.
+    LdDtVx(Register),
+    ///Fx18: sound timer = Vx.
+    LdStVx(Register),
+    ///Fx1E: I += Vx.
+    AddI(Register),
+    ///Fx29: I = address of the small font sprite for the digit in Vx.
+    LdF(Register),
+    ///Fx30: I = address of the big font sprite for the digit in Vx.
+    LdHf(Register),
+    ///Fx33: stores the BCD digits of Vx at I, I+1 and I+2.
+    LdB(Register),
+    ///Fx55: stores V0 to Vx in memory starting at I.
+    LdIVx(Register),
+    ///Fx65: loads V0 to Vx from memory starting at I.
+    LdVxI(Register),
+    ///Fx75: stores V0 to Vx in the RPL user flags.
+    LdRVx(Register),
+    ///Fx85: loads V0 to Vx from the RPL user flags.
+    LdVxR(Register),
+}
+
+///An instruction that doesn't decode to any operation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InvalidInstruction(pub u16);
+
+impl Display for InvalidInstruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "0x{:04X} isn't a valid instruction.", self.0)
+    }
+}
+
+impl std::error::Error for InvalidInstruction {}
+
+impl TryFrom<Instruction> for Opcode {
+    type Error = InvalidInstruction;
+
+    fn try_from(instruction: Instruction) -> Result<Self, Self::Error> {
+        let x = instruction.get_register();
+        let y = instruction.get_second_register();
+        let n = instruction.get_small_immediate();
+        let kk = instruction.get_immediate();
+        let nnn = instruction.get_address_immediate();
+        let invalid = Err(InvalidInstruction(instruction.value));
+
+        let opcode = match instruction.get_opcode() {
+            0x0 => match instruction.value {
+                0x00E0 => Opcode::Cls,
+                0x00EE => Opcode::Ret,
+                0x00C0..=0x00CF => Opcode::Scd(n),
+                0x00FB => Opcode::Scr,
+                0x00FC => Opcode::Scl,
+                0x00FD => Opcode::Exit,
+                0x00FE => Opcode::Low,
+                0x00FF => Opcode::High,
+                _ => return invalid,
+            },
+            0x1 => Opcode::Jp(nnn),
+            0x2 => Opcode::Call(nnn),
+            0x3 => Opcode::SeImmediate(x, kk),
+            0x4 => Opcode::SneImmediate(x, kk),
+            0x5 => match n {
+                0x0 => Opcode::Se(x, y),
+                0x2 => Opcode::Save(x, y),
+                0x3 => Opcode::Load(x, y),
+                _ => return invalid,
+            },
+            0x6 => Opcode::LdImmediate(x, kk),
+            0x7 => Opcode::AddImmediate(x, kk),
+            0x8 => match n {
+                0x0 => Opcode::Ld(x, y),
+                0x1 => Opcode::Or(x, y),
+                0x2 => Opcode::And(x, y),
+                0x3 => Opcode::Xor(x, y),
+                0x4 => Opcode::Add(x, y),
+                0x5 => Opcode::Sub(x, y),
+                0x6 => Opcode::Shr(x, y),
+                0x7 => Opcode::Subn(x, y),
+                0xE => Opcode::Shl(x, y),
+                _ => return invalid,
+            },
+            0x9 => match n {
+                0x0 => Opcode::Sne(x, y),
+                _ => return invalid,
+            },
+            0xA => Opcode::LdI(nnn),
+            0xB => Opcode::JpV0(nnn),
+            0xC => Opcode::Rnd(x, kk),
+            0xD => Opcode::Drw(x, y, n),
+            0xE => match kk {
+                0x9E => Opcode::Skp(x),
+                0xA1 => Opcode::Sknp(x),
+                _ => return invalid,
+            },
+            _ => match kk {
+                0x00 if x == 0 => Opcode::LdILong,
+                0x01 => Opcode::Plane(x),
+                0x07 => Opcode::LdVxDt(x),
+                0x0A => Opcode::LdVxK(x),
+                0x15 => Opcode::LdDtVx(x),
+                0x18 => Opcode::LdStVx(x),
+                0x1E => Opcode::AddI(x),
+                0x29 => Opcode::LdF(x),
+                0x30 => Opcode::LdHf(x),
+                0x33 => Opcode::LdB(x),
+                0x55 => Opcode::LdIVx(x),
+                0x65 => Opcode::LdVxI(x),
+                0x75 => Opcode::LdRVx(x),
+                0x85 => Opcode::LdVxR(x),
+                _ => return invalid,
+            },
+        };
+        Ok(opcode)
+    }
+}
+
+impl Opcode {
+    ///Encodes the operation back into its 16-bit instruction.
+    ///
+    ///Operands are masked to the bits available for them, e.g. addresses to 12 bits.
+    ///The address following [`Opcode::LdILong`] is not part of this.
+    pub fn encode(&self) -> u16 {
+        let xy = |x: Register, y: Register| ((x as u16 & 0xF) << 8) | ((y as u16 & 0xF) << 4);
+        let xkk = |x: Register, kk: u8| ((x as u16 & 0xF) << 8) | kk as u16;
+        let x = |x: Register| (x as u16 & 0xF) << 8;
+
+        match *self {
+            Opcode::Cls => 0x00E0,
+            Opcode::Ret => 0x00EE,
+            Opcode::Scd(n) => 0x00C0 | (n as u16 & 0xF),
+            Opcode::Scr => 0x00FB,
+            Opcode::Scl => 0x00FC,
+            Opcode::Exit => 0x00FD,
+            Opcode::Low => 0x00FE,
+            Opcode::High => 0x00FF,
+            Opcode::Jp(nnn) => 0x1000 | (nnn & 0xFFF),
+            Opcode::Call(nnn) => 0x2000 | (nnn & 0xFFF),
+            Opcode::SeImmediate(vx, kk) => 0x3000 | xkk(vx, kk),
+            Opcode::SneImmediate(vx, kk) => 0x4000 | xkk(vx, kk),
+            Opcode::Se(vx, vy) => 0x5000 | xy(vx, vy),
+            Opcode::Save(vx, vy) => 0x5002 | xy(vx, vy),
+            Opcode::Load(vx, vy) => 0x5003 | xy(vx, vy),
+            Opcode::LdImmediate(vx, kk) => 0x6000 | xkk(vx, kk),
+            Opcode::AddImmediate(vx, kk) => 0x7000 | xkk(vx, kk),
+            Opcode::Ld(vx, vy) => 0x8000 | xy(vx, vy),
+            Opcode::Or(vx, vy) => 0x8001 | xy(vx, vy),
+            Opcode::And(vx, vy) => 0x8002 | xy(vx, vy),
+            Opcode::Xor(vx, vy) => 0x8003 | xy(vx, vy),
+            Opcode::Add(vx, vy) => 0x8004 | xy(vx, vy),
+            Opcode::Sub(vx, vy) => 0x8005 | xy(vx, vy),
+            Opcode::Shr(vx, vy) => 0x8006 | xy(vx, vy),
+            Opcode::Subn(vx, vy) => 0x8007 | xy(vx, vy),
+            Opcode::Shl(vx, vy) => 0x800E | xy(vx, vy),
+            Opcode::Sne(vx, vy) => 0x9000 | xy(vx, vy),
+            Opcode::LdI(nnn) => 0xA000 | (nnn & 0xFFF),
+            Opcode::JpV0(nnn) => 0xB000 | (nnn & 0xFFF),
+            Opcode::Rnd(vx, kk) => 0xC000 | xkk(vx, kk),
+            Opcode::Drw(vx, vy, n) => 0xD000 | xy(vx, vy) | (n as u16 & 0xF),
+            Opcode::Skp(vx) => 0xE09E | x(vx),
+            Opcode::Sknp(vx) => 0xE0A1 | x(vx),
+            Opcode::LdILong => 0xF000,
+            Opcode::Plane(n) => 0xF001 | x(n),
+            Opcode::LdVxDt(vx) => 0xF007 | x(vx),
+            Opcode::LdVxK(vx) => 0xF00A | x(vx),
+            Opcode::LdDtVx(vx) => 0xF015 | x(vx),
+            Opcode::LdStVx(vx) => 0xF018 | x(vx),
+            Opcode::AddI(vx) => 0xF01E | x(vx),
+            Opcode::LdF(vx) => 0xF029 | x(vx),
+            Opcode::LdHf(vx) => 0xF030 | x(vx),
+            Opcode::LdB(vx) => 0xF033 | x(vx),
+            Opcode::LdIVx(vx) => 0xF055 | x(vx),
+            Opcode::LdVxI(vx) => 0xF065 | x(vx),
+            Opcode::LdRVx(vx) => 0xF075 | x(vx),
+            Opcode::LdVxR(vx) => 0xF085 | x(vx),
+        }
+    }
+
+    ///Returns how many bytes the operation takes up in memory, including the address after a long load.
+    pub fn length(&self) -> u16 {
+        match self {
+            Opcode::LdILong => 4,
+            _ => 2,
+        }
+    }
+
+    ///Returns the assembly for the operation, using `address_name` to show the addresses it refers to.
+    ///
+    ///The address after a long load isn't known here, so it is shown as `LD I, long` without one.
+    pub fn to_assembly(&self, address_name: &dyn Fn(u16) -> String) -> String {
+        match *self {
+            Opcode::Cls => "CLS".to_string(),
+            Opcode::Ret => "RET".to_string(),
+            Opcode::Scd(n) => format!("SCD {}", n),
+            Opcode::Scr => "SCR".to_string(),
+            Opcode::Scl => "SCL".to_string(),
+            Opcode::Exit => "EXIT".to_string(),
+            Opcode::Low => "LOW".to_string(),
+            Opcode::High => "HIGH".to_string(),
+            Opcode::Jp(nnn) => format!("JP {}", address_name(nnn)),
+            Opcode::Call(nnn) => format!("CALL {}", address_name(nnn)),
+            Opcode::SeImmediate(vx, kk) => format!("SE V{:X}, 0x{:02X}", vx, kk),
+            Opcode::SneImmediate(vx, kk) => format!("SNE V{:X}, 0x{:02X}", vx, kk),
+            Opcode::Se(vx, vy) => format!("SE V{:X}, V{:X}", vx, vy),
+            Opcode::Save(vx, vy) => format!("SAVE V{:X} - V{:X}", vx, vy),
+            Opcode::Load(vx, vy) => format!("LOAD V{:X} - V{:X}", vx, vy),
+            Opcode::LdImmediate(vx, kk) => format!("LD V{:X}, 0x{:02X}", vx, kk),
+            Opcode::AddImmediate(vx, kk) => format!("ADD V{:X}, 0x{:02X}", vx, kk),
+            Opcode::Ld(vx, vy) => format!("LD V{:X}, V{:X}", vx, vy),
+            Opcode::Or(vx, vy) => format!("OR V{:X}, V{:X}", vx, vy),
+            Opcode::And(vx, vy) => format!("AND V{:X}, V{:X}", vx, vy),
+            Opcode::Xor(vx, vy) => format!("XOR V{:X}, V{:X}", vx, vy),
+            Opcode::Add(vx, vy) => format!("ADD V{:X}, V{:X}", vx, vy),
+            Opcode::Sub(vx, vy) => format!("SUB V{:X}, V{:X}", vx, vy),
+            Opcode::Shr(vx, vy) => format!("SHR V{:X}, V{:X}", vx, vy),
+            Opcode::Subn(vx, vy) => format!("SUBN V{:X}, V{:X}", vx, vy),
+            Opcode::Shl(vx, vy) => format!("SHL V{:X}, V{:X}", vx, vy),
+            Opcode::Sne(vx, vy) => format!("SNE V{:X}, V{:X}", vx, vy),
+            Opcode::LdI(nnn) => format!("LD I, {}", address_name(nnn)),
+            Opcode::JpV0(nnn) => format!("JP V0, {}", address_name(nnn)),
+            Opcode::Rnd(vx, kk) => format!("RND V{:X}, 0x{:02X}", vx, kk),
+            Opcode::Drw(vx, vy, n) => format!("DRW V{:X}, V{:X}, {}", vx, vy, n),
+            Opcode::Skp(vx) => format!("SKP V{:X}", vx),
+            Opcode::Sknp(vx) => format!("SKNP V{:X}", vx),
+            Opcode::LdILong => "LD I, long".to_string(),
+            Opcode::Plane(n) => format!("PLANE {}", n),
+            Opcode::LdVxDt(vx) => format!("LD V{:X}, DT", vx),
+            Opcode::LdVxK(vx) => format!("LD V{:X}, K", vx),
+            Opcode::LdDtVx(vx) => format!("LD DT, V{:X}", vx),
+            Opcode::LdStVx(vx) => format!("LD ST, V{:X}", vx),
+            Opcode::AddI(vx) => format!("ADD I, V{:X}", vx),
+            Opcode::LdF(vx) => format!("LD F, V{:X}", vx),
+            Opcode::LdHf(vx) => format!("LD HF, V{:X}", vx),
+            Opcode::LdB(vx) => format!("LD B, V{:X}", vx),
+            Opcode::LdIVx(vx) => format!("LD [I], V{:X}", vx),
+            Opcode::LdVxI(vx) => format!("LD V{:X}, [I]", vx),
+            Opcode::LdRVx(vx) => format!("LD R, V{:X}", vx),
+            Opcode::LdVxR(vx) => format!("LD V{:X}, R", vx),
+        }
+    }
+}
+
+impl Display for Opcode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            self.to_assembly(&|address| format!("0x{:03X}", address))
+        )
+    }
+}
+
+impl From<Opcode> for Instruction {
+    fn from(opcode: Opcode) -> Self {
+        Instruction::new(opcode.encode())
+    }
+}
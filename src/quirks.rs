@@ -0,0 +1,67 @@
+///Toggles for the instructions whose behaviour differs between CHIP-8 interpreters.
+///
+///Different ROMs were written against different interpreters, so these need to match whatever the ROM expects.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Quirks {
+    ///8xy6/8xyE shift Vx in place instead of copying Vy into Vx first.
+    pub shift: bool,
+    ///Bnnn jumps to xnn + Vx instead of nnn + V0.
+    pub jump_with_vx: bool,
+    ///Fx55/Fx65 leave I pointing past the last register stored or loaded.
+    pub memory_increment: bool,
+    ///8xy1/8xy2/8xy3 reset VF to 0.
+    pub vf_reset: bool,
+    ///Sprites are clipped at the edges of the screen instead of wrapping around to the other side.
+    pub clipping: bool,
+    ///Dxyn waits for the next frame before drawing, limiting draws to one per frame.
+    pub display_wait: bool,
+}
+
+impl Quirks {
+    ///The behaviour of the original interpreter on the COSMAC VIP.
+    pub fn cosmac_vip() -> Self {
+        Quirks {
+            shift: false,
+            jump_with_vx: false,
+            memory_increment: true,
+            vf_reset: true,
+            clipping: true,
+            display_wait: true,
+        }
+    }
+
+    ///The behaviour of CHIP-48 on the HP-48 calculators.
+    pub fn chip48() -> Self {
+        Quirks {
+            shift: true,
+            jump_with_vx: true,
+            memory_increment: false,
+            vf_reset: false,
+            clipping: true,
+            display_wait: false,
+        }
+    }
+
+    ///The behaviour of Octo's XO-CHIP.
+    pub fn xo_chip() -> Self {
+        Quirks {
+            shift: false,
+            jump_with_vx: false,
+            memory_increment: true,
+            vf_reset: false,
+            clipping: false,
+            display_wait: false,
+        }
+    }
+
+    ///The behaviour of SUPER-CHIP 1.1, which kept CHIP-48's take on all of these.
+    pub fn super_chip() -> Self {
+        Quirks::chip48()
+    }
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Quirks::cosmac_vip()
+    }
+}
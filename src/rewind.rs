@@ -0,0 +1,141 @@
+use std::collections::VecDeque;
+
+///Differences closer together than this many equal bytes are stored as one run, since every run has some overhead.
+const MERGE_GAP: usize = 8;
+
+///A bounded history of serialized machine states that can be walked back through, newest first.
+///
+///Only the newest state is kept in full. Every older state is stored as the bytes that differ from the state
+///recorded after it, which for consecutive frames is usually a small part of RAM and the screen.
+#[derive(Clone, Debug, Default)]
+pub struct RewindBuffer {
+    capacity: usize,
+    latest: Option<Vec<u8>>,
+    ///Each delta turns a state into the one recorded before it. The oldest is at the front.
+    deltas: VecDeque<Delta>,
+}
+
+impl RewindBuffer {
+    ///Creates a buffer holding up to `capacity` states. A capacity of 0 disables recording.
+    pub fn new(capacity: usize) -> Self {
+        RewindBuffer {
+            capacity,
+            latest: None,
+            deltas: VecDeque::new(),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    ///Changes how many states are kept, dropping the oldest ones if there are too many.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        if capacity == 0 {
+            self.clear();
+        }
+        while self.deltas.len() >= capacity.max(1) {
+            self.deltas.pop_front();
+        }
+    }
+
+    ///Returns how many states are recorded.
+    pub fn len(&self) -> usize {
+        self.deltas.len() + self.latest.is_some() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.latest.is_none()
+    }
+
+    ///Returns roughly how many bytes the recorded states take up.
+    pub fn size(&self) -> usize {
+        self.latest.as_ref().map_or(0, Vec::len)
+            + self.deltas.iter().map(Delta::size).sum::<usize>()
+    }
+
+    pub fn clear(&mut self) {
+        self.latest = None;
+        self.deltas.clear();
+    }
+
+    ///Records a state as the newest one, dropping the oldest state once the buffer is full.
+    pub fn push(&mut self, state: Vec<u8>) {
+        if self.capacity == 0 {
+            return;
+        }
+        if let Some(latest) = self.latest.take() {
+            self.deltas.push_back(Delta::between(&state, &latest));
+            if self.deltas.len() >= self.capacity {
+                self.deltas.pop_front();
+            }
+        }
+        self.latest = Some(state);
+    }
+
+    ///Removes and returns the newest state, making the one recorded before it the newest.
+    pub fn pop(&mut self) -> Option<Vec<u8>> {
+        let latest = self.latest.take()?;
+        self.latest = self.deltas.pop_back().map(|delta| delta.apply(&latest));
+        Some(latest)
+    }
+}
+
+///Turns one state into another by overwriting the runs of bytes where they differ.
+#[derive(Clone, Debug)]
+struct Delta {
+    length: usize,
+    patches: Vec<(usize, Vec<u8>)>,
+}
+
+impl Delta {
+    ///Returns the delta that turns `from` into `to`.
+    fn between(from: &[u8], to: &[u8]) -> Self {
+        if from.len() != to.len() {
+            return Delta {
+                length: to.len(),
+                patches: vec![(0, to.to_vec())],
+            };
+        }
+
+        let mut patches: Vec<(usize, Vec<u8>)> = Vec::new();
+        let mut run: Option<(usize, usize)> = None;
+        for (i, (old, new)) in from.iter().zip(to).enumerate() {
+            if old == new {
+                continue;
+            }
+            run = match run {
+                Some((start, end)) if i - end <= MERGE_GAP => Some((start, i + 1)),
+                Some((start, end)) => {
+                    patches.push((start, to[start..end].to_vec()));
+                    Some((i, i + 1))
+                }
+                None => Some((i, i + 1)),
+            };
+        }
+        if let Some((start, end)) = run {
+            patches.push((start, to[start..end].to_vec()));
+        }
+        Delta {
+            length: to.len(),
+            patches,
+        }
+    }
+
+    fn apply(&self, from: &[u8]) -> Vec<u8> {
+        let mut bytes = from.to_vec();
+        bytes.resize(self.length, 0);
+        for (start, patch) in &self.patches {
+            bytes[*start..*start + patch.len()].copy_from_slice(patch);
+        }
+        bytes
+    }
+
+    fn size(&self) -> usize {
+        self.patches
+            .iter()
+            .map(|(_, patch)| std::mem::size_of::<(usize, Vec<u8>)>() + patch.len())
+            .sum()
+    }
+}
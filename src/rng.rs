@@ -0,0 +1,56 @@
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+///The random number generator behind Cxkk.
+///
+///It is seeded with a single number, so a run can be repeated exactly by starting it with the same seed, and its
+///whole state is the seed and how far into the generated stream it has got, which is what save states record.
+#[derive(Clone, Debug)]
+pub struct Chip8Rng {
+    seed: u64,
+    generator: ChaCha8Rng,
+}
+
+///Everything needed to recreate a [`Chip8Rng`] exactly where it was.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RngState {
+    pub seed: u64,
+    ///How many 32-bit words of the stream have been used.
+    pub position: u128,
+}
+
+impl Chip8Rng {
+    pub fn new(seed: u64) -> Self {
+        Chip8Rng {
+            seed,
+            generator: ChaCha8Rng::seed_from_u64(seed),
+        }
+    }
+
+    ///Creates a generator with a seed drawn from the host's generator, for when runs don't need to be repeatable.
+    pub fn from_entropy() -> Self {
+        Chip8Rng::new(rand::random())
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    ///Returns a byte from the full range 0x00 to 0xFF.
+    pub fn next_byte(&mut self) -> u8 {
+        self.generator.gen()
+    }
+
+    pub fn state(&self) -> RngState {
+        RngState {
+            seed: self.seed,
+            position: self.generator.get_word_pos(),
+        }
+    }
+
+    pub fn from_state(state: RngState) -> Self {
+        let mut rng = Chip8Rng::new(state.seed);
+        rng.generator.set_word_pos(state.position);
+        rng
+    }
+}
@@ -0,0 +1,216 @@
+use std::fmt::Display;
+
+use crate::container::{self, ContainerError, Reader};
+use crate::cpu::Cpu;
+use crate::frame_buffer::{MAX_HEIGHT, PLANE_COUNT};
+use crate::memory::{RAM_SIZE, XO_CHIP_RAM_SIZE};
+use crate::quirks::Quirks;
+use crate::rng::RngState;
+
+///The bytes every save state starts with.
+pub const MAGIC: [u8; 4] = *b"CH8S";
+///The version of the format written by [`SaveState::to_bytes`].
+pub const VERSION: u16 = 2;
+
+///Describes why a save state could not be loaded.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SaveStateError {
+    ///The data doesn't start with the save state magic bytes.
+    NotASaveState,
+    ///The save state was written in a format version this build doesn't know.
+    UnsupportedVersion(u16),
+    ///The data ends before the save state does.
+    Truncated,
+    ///The contents don't match their checksum, so the save state is corrupted.
+    ChecksumMismatch,
+    ///The contents describe a machine that can't exist, e.g. a stack pointer past the end of the stack.
+    Invalid(String),
+}
+
+impl Display for SaveStateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SaveStateError::NotASaveState => write!(f, "This isn't a save state."),
+            SaveStateError::UnsupportedVersion(version) => write!(
+                f,
+                "The save state has version {}, but only version {} is supported.",
+                version, VERSION
+            ),
+            SaveStateError::Truncated => write!(f, "The save state is truncated."),
+            SaveStateError::ChecksumMismatch => {
+                write!(
+                    f,
+                    "The save state's checksum doesn't match, it is corrupted."
+                )
+            }
+            SaveStateError::Invalid(message) => write!(f, "Invalid save state: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for SaveStateError {}
+
+impl From<ContainerError> for SaveStateError {
+    fn from(e: ContainerError) -> Self {
+        match e {
+            ContainerError::WrongMagic => SaveStateError::NotASaveState,
+            ContainerError::UnsupportedVersion(version) => {
+                SaveStateError::UnsupportedVersion(version)
+            }
+            ContainerError::Truncated => SaveStateError::Truncated,
+            ContainerError::ChecksumMismatch => SaveStateError::ChecksumMismatch,
+            ContainerError::Invalid(message) => SaveStateError::Invalid(message),
+        }
+    }
+}
+
+///A snapshot of everything needed to resume a program exactly where it was.
+///
+///Settings that don't change what the program does, such as the clock speed and palette, aren't included.
+///The random number generator is, so a program continues with the same random numbers after loading a state.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SaveState {
+    pub cpu: Cpu,
+    pub ram: Vec<u8>,
+    pub stack: [u16; 16],
+    pub frame_buffer: [[u128; MAX_HEIGHT]; PLANE_COUNT],
+    pub high_resolution: bool,
+    pub selected_planes: u8,
+    pub keys: [bool; 16],
+    pub quirks: Quirks,
+    ///Whether a sprite has been drawn in the current frame, for the display wait quirk.
+    pub drawn_this_frame: bool,
+    ///How many instructions have been executed in the current frame, for instruction based timing.
+    pub instructions_this_frame: u32,
+    pub rng: RngState,
+}
+
+impl SaveState {
+    ///Serializes the save state: a header with the magic bytes, format version, length and CRC-32 of the contents,
+    ///followed by the contents. Numbers are big-endian.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut contents = Vec::new();
+        let cpu = &self.cpu;
+        contents.extend_from_slice(&cpu.data_registers);
+        contents.extend_from_slice(&cpu.index_register.to_be_bytes());
+        contents.push(cpu.stack_pointer);
+        contents.push(cpu.delay_timer);
+        contents.push(cpu.sound_timer);
+        contents.extend_from_slice(&cpu.program_counter.to_be_bytes());
+        contents.extend_from_slice(&cpu.rpl_flags);
+        match cpu.waiting_for_key_release {
+            Some(key) => contents.extend_from_slice(&[1, key]),
+            None => contents.extend_from_slice(&[0, 0]),
+        }
+
+        contents.extend_from_slice(&(self.ram.len() as u32).to_be_bytes());
+        contents.extend_from_slice(&self.ram);
+        for address in self.stack.iter() {
+            contents.extend_from_slice(&address.to_be_bytes());
+        }
+
+        for row in self.frame_buffer.iter().flatten() {
+            contents.extend_from_slice(&row.to_be_bytes());
+        }
+        contents.push(self.high_resolution as u8);
+        contents.push(self.selected_planes);
+        contents.extend(self.keys.iter().map(|&pressed| pressed as u8));
+
+        let quirks = &self.quirks;
+        contents.extend(
+            [
+                quirks.shift,
+                quirks.jump_with_vx,
+                quirks.memory_increment,
+                quirks.vf_reset,
+                quirks.clipping,
+                quirks.display_wait,
+            ]
+            .iter()
+            .map(|&quirk| quirk as u8),
+        );
+        contents.push(self.drawn_this_frame as u8);
+        contents.extend_from_slice(&self.instructions_this_frame.to_be_bytes());
+        contents.extend_from_slice(&self.rng.seed.to_be_bytes());
+        contents.extend_from_slice(&self.rng.position.to_be_bytes());
+
+        container::wrap(MAGIC, VERSION, &contents)
+    }
+
+    ///Parses a save state written by [`SaveState::to_bytes`], checking the header and checksum first.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SaveStateError> {
+        let contents = container::unwrap(MAGIC, VERSION, bytes)?;
+        let mut reader = Reader::new(contents);
+        let cpu = Cpu {
+            data_registers: reader.array()?,
+            index_register: reader.u16()?,
+            stack_pointer: reader.u8()?,
+            delay_timer: reader.u8()?,
+            sound_timer: reader.u8()?,
+            program_counter: reader.u16()?,
+            rpl_flags: reader.array()?,
+            waiting_for_key_release: match (reader.bool()?, reader.u8()?) {
+                (true, key) => Some(key),
+                (false, _) => None,
+            },
+        };
+        let ram_length = reader.u32()? as usize;
+        let ram = reader.bytes(ram_length)?.to_vec();
+        let mut stack = [0; 16];
+        for address in stack.iter_mut() {
+            *address = reader.u16()?;
+        }
+        let mut frame_buffer = [[0; MAX_HEIGHT]; PLANE_COUNT];
+        for row in frame_buffer.iter_mut().flatten() {
+            *row = u128::from_be_bytes(reader.array()?);
+        }
+        let high_resolution = reader.bool()?;
+        let selected_planes = reader.u8()?;
+        let mut keys = [false; 16];
+        for key in keys.iter_mut() {
+            *key = reader.bool()?;
+        }
+        let quirks = Quirks {
+            shift: reader.bool()?,
+            jump_with_vx: reader.bool()?,
+            memory_increment: reader.bool()?,
+            vf_reset: reader.bool()?,
+            clipping: reader.bool()?,
+            display_wait: reader.bool()?,
+        };
+        let drawn_this_frame = reader.bool()?;
+        let instructions_this_frame = reader.u32()?;
+        let rng = RngState {
+            seed: u64::from_be_bytes(reader.array()?),
+            position: u128::from_be_bytes(reader.array()?),
+        };
+
+        let invalid = |message: String| Err(SaveStateError::Invalid(message));
+        if ram_length != RAM_SIZE && ram_length != XO_CHIP_RAM_SIZE {
+            return invalid(format!("0x{:X} bytes of RAM.", ram_length));
+        }
+        if cpu.stack_pointer as usize > stack.len() {
+            return invalid(format!("stack pointer {}.", cpu.stack_pointer));
+        }
+        if let Some(key) = cpu.waiting_for_key_release.filter(|&key| key > 0xF) {
+            return invalid(format!("waiting for key 0x{:X}.", key));
+        }
+        if selected_planes > 0b11 {
+            return invalid(format!("selected planes 0b{:b}.", selected_planes));
+        }
+
+        Ok(SaveState {
+            cpu,
+            ram,
+            stack,
+            frame_buffer,
+            high_resolution,
+            selected_planes,
+            keys,
+            quirks,
+            drawn_this_frame,
+            instructions_this_frame,
+            rng,
+        })
+    }
+}
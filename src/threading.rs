@@ -1,52 +1,171 @@
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+use crate::breakpoint::{Breakpoint, BreakpointHit};
+use crate::computer::TimingMode;
+use crate::cpu::Cpu;
+use crate::error::ExecutionError;
+use crate::memory::Memory;
+use crate::movie::{Desync, Playback};
+use crate::quirks::Quirks;
+
 pub enum EmulatorCommand {
+    /// Runs the emulator freely at its configured clock speed.
     Go,
+    /// Executes the given number of instructions and then pauses.
     Step(u32),
+    /// Requests a snapshot of the emulator's memory.
     GetMemory,
+    /// Requests a snapshot of the emulator's CPU registers.
     GetRegisters,
+    /// Stops execution until a Go or Step command is received. Answered with `Paused`.
     Pause,
+    /// Loads the given bytes into program memory.
+    LoadRom(Vec<u8>),
+    /// Replaces the quirks used when executing ambiguous instructions.
+    SetQuirks(Quirks),
+    /// Switches to XO-CHIP mode with 64 KiB of RAM and XO-CHIP's quirks.
+    EnableXoChip,
+    /// Sets how many instructions are executed per second while running freely.
+    SetClockSpeed(u16),
+    /// Sets whether the 60 Hz timers follow the wall clock or the instruction count.
+    SetTimingMode(TimingMode),
+    /// Restarts the random number generator behind Cxkk from a seed, making runs repeatable.
+    SetSeed(u64),
+    /// Records a key on the hex keypad as held down or released.
+    SetKey { key: u8, pressed: bool },
+    /// Sets the colours used for each combination of the two bitplanes.
+    SetPalette([[u8; 4]; 4]),
+    /// Sets whether every executed instruction is printed to stdout.
+    SetDebugOutput(bool),
+    /// Replaces the CPU state, e.g. to change registers from a debugger.
+    SetCpu(Cpu),
+    /// Requests the screen drawn as text.
+    GetScreen,
+    /// Requests the machine's state in the save state format. Answered with `SaveState`.
+    SaveState,
+    /// Restores a state produced by `SaveState`. The current state is kept if it can't be read.
+    LoadState(Vec<u8>),
+    /// Sets how many frames of history are recorded for rewinding. 0 turns it off.
+    SetRewindFrames(usize),
+    /// Goes back the given number of frames. Answered with `Rewound`.
+    Rewind(usize),
+    /// Sets how many of the most recent instructions can be undone. 0 turns it off.
+    SetUndoLimit(usize),
+    /// Undoes the given number of instructions and pauses. Answered with `SteppedBack`.
+    StepBack(usize),
+    /// Undoes instructions until a breakpoint is hit going backwards, then pauses. Answered with `BreakpointHit`
+    /// if one was hit, followed by `SteppedBack`.
+    ReverseContinue,
+    /// Adds a breakpoint, which pauses execution and is reported with `BreakpointHit` when it is reached.
+    SetBreakpoint(Breakpoint),
+    /// Removes the breakpoint, if it is set.
+    ClearBreakpoint(Breakpoint),
+    /// Starts recording a movie of the program, which should have just been loaded.
+    StartRecording,
+    /// Stops recording the movie. Answered with `Movie`.
+    StopRecording,
+    /// Plays back a movie produced by `StopRecording`, which must have been recorded with the loaded ROM.
+    /// Reported with `MovieDesync` if it goes differently and `MovieFinished` once it is over.
+    PlayMovie(Vec<u8>),
 }
 
 pub enum EmulatorResponse {
-    FrameBuffer(Vec<[u8;4]>)
-}
-
-impl EmulatorResponse {
-    pub fn match_response(&self) {
-        match self {
-
-        }
-    }
+    /// The screen has changed. Pixels are in row order for a screen of the given size.
+    FrameBuffer {
+        width: usize,
+        height: usize,
+        pixels: Vec<[u8; 4]>,
+    },
+    Memory(Box<Memory>),
+    Cpu(Cpu),
+    /// Execution stopped because the emulator couldn't execute an instruction.
+    Halted(ExecutionError),
+    /// The program exited through the SUPER-CHIP exit instruction.
+    Exited,
+    /// Execution paused with the program counter at the given address because of a Pause command.
+    Paused {
+        program_counter: u16,
+        /// Whether the program was running freely before, rather than already stopped.
+        was_running: bool,
+    },
+    /// Execution paused because a breakpoint was reached.
+    BreakpointHit(BreakpointHit),
+    /// The screen as text, one line per row.
+    Screen(String),
+    /// The machine's state, in the format read by `LoadState`.
+    SaveState(Vec<u8>),
+    /// The machine went back this many frames, fewer than requested if the history ran out.
+    Rewound {
+        frames: usize,
+    },
+    /// This many instructions were undone, fewer than requested if the undo log ran out.
+    SteppedBack {
+        instructions: usize,
+    },
+    /// The recorded movie, in the format read by `PlayMovie`.
+    Movie(Vec<u8>),
+    /// The movie being played back went differently from the recording for the first time.
+    MovieDesync(Desync),
+    /// The movie being played back is over, and the keypad and timing are back to normal.
+    MovieFinished(Playback),
 }
 
 pub trait ThreadedEmulator {
-
-    /// Creates a new instance of the associated type Computer. 
-    /// 
-    /// The Sender should be hooked up into wherever graphics updates will come from 
-    /// (e.g. frame buffer updates) 
+    /// Creates a new instance of the associated type Computer.
+    ///
+    /// The Sender should be hooked up into wherever graphics updates will come from
+    /// (e.g. frame buffer updates)
     fn new(sender_from_computer: Sender<EmulatorResponse>) -> Self;
 
     /// Performs the action corresponding to the received EmulatorCommand
-    fn match_received_command(&self, command: EmulatorCommand) -> std::result::Result<(), String>;
+    fn match_received_command(&mut self, command: EmulatorCommand) -> Result<(), String>;
 
+    /// Returns whether the emulator is free running and needs `run_cycle` called between commands.
+    fn is_running(&self) -> bool;
+
+    /// Performs one slice of free running work, such as executing the next instruction once it is due.
+    fn run_cycle(&mut self) -> Result<(), String>;
+
+    /// Spawns the emulator on its own thread and returns the channels used to talk to it.
     ///
+    /// While paused the thread blocks on the command channel. It shuts down once the command sender is dropped.
     fn initialize() -> (Sender<EmulatorCommand>, Receiver<EmulatorResponse>)
-    where Self: Sized {
+    where
+        Self: Sized,
+    {
         let (sender_to_computer, receiver_to_computer) = channel::<EmulatorCommand>();
         let (sender_from_computer, receiver_from_computer) = channel::<EmulatorResponse>();
 
         std::thread::spawn(move || {
-            let computer = Self::new(sender_from_computer);
+            let mut computer = Self::new(sender_from_computer);
 
             loop {
-                if let Ok(command) = receiver_to_computer.try_recv() {
-                    if let Err(e) = computer.match_received_command(command){
+                let command = match computer.is_running() {
+                    true => match receiver_to_computer.try_recv() {
+                        Ok(command) => Some(command),
+                        Err(std::sync::mpsc::TryRecvError::Empty) => None,
+                        Err(std::sync::mpsc::TryRecvError::Disconnected) => break,
+                    },
+                    false => match receiver_to_computer.recv() {
+                        Ok(command) => Some(command),
+                        Err(_) => break,
+                    },
+                };
+
+                if let Some(command) = command {
+                    if let Err(e) = computer.match_received_command(command) {
+                        eprintln!("{e}");
+                    }
+                }
+
+                if computer.is_running() {
+                    if let Err(e) = computer.run_cycle() {
                         eprintln!("{e}");
-                    };
+                    }
                 }
             }
         });
-       
-       (sender_to_computer, receiver_from_computer)
+
+        (sender_to_computer, receiver_from_computer)
     }
-}
\ No newline at end of file
+}
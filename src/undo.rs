@@ -0,0 +1,84 @@
+use std::collections::VecDeque;
+
+use crate::breakpoint::Access;
+use crate::cpu::Cpu;
+use crate::rng::RngState;
+
+///What a single instruction overwrote, so that it can be undone exactly.
+#[derive(Clone, Debug)]
+pub struct UndoRecord {
+    pub cpu: Cpu,
+    pub stack: [u16; 16],
+    pub drawn_this_frame: bool,
+    pub instructions_this_frame: u32,
+    pub high_resolution: bool,
+    pub selected_planes: u8,
+    pub rng: RngState,
+    pub keys: [bool; 16],
+    ///The previous contents of every RAM range written, in the order they were written.
+    pub ram: Vec<(usize, Vec<u8>)>,
+    ///The previous contents of every screen row that changed, as (plane, row, pixels).
+    pub rows: Vec<(usize, usize, u128)>,
+    ///The RAM the instruction accessed, for checking watchpoints while going backwards.
+    pub accesses: Vec<(usize, usize, Access)>,
+    ///How many new frames started after the instruction and before the next one, each of which recorded a rewind
+    ///state if rewinding is enabled. Only movies can end more than one frame at once, with frames that have no
+    ///instructions.
+    pub frames_started: usize,
+}
+
+///The most recent instructions' undo records, up to a limit.
+#[derive(Clone, Debug, Default)]
+pub struct UndoLog {
+    limit: usize,
+    records: VecDeque<UndoRecord>,
+}
+
+impl UndoLog {
+    ///Creates a log holding up to `limit` instructions. A limit of 0 disables recording.
+    pub fn new(limit: usize) -> Self {
+        UndoLog {
+            limit,
+            records: VecDeque::new(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.limit > 0
+    }
+
+    ///Changes how many instructions are kept, forgetting the oldest ones if there are too many.
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+        while self.records.len() > limit {
+            self.records.pop_front();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.records.clear();
+    }
+
+    ///Records the newest instruction, forgetting the oldest once the log is full.
+    pub fn push(&mut self, record: UndoRecord) {
+        if self.limit == 0 {
+            return;
+        }
+        if self.records.len() == self.limit {
+            self.records.pop_front();
+        }
+        self.records.push_back(record);
+    }
+
+    ///Counts a frame that started after the newest instruction, so that undoing the instruction undoes the frame.
+    pub fn frame_started(&mut self) {
+        if let Some(record) = self.records.back_mut() {
+            record.frames_started += 1;
+        }
+    }
+
+    ///Removes the newest instruction's record.
+    pub fn pop(&mut self) -> Option<UndoRecord> {
+        self.records.pop_back()
+    }
+}
@@ -0,0 +1,118 @@
+extern crate chip8;
+
+use chip8::assembler::{assemble, assemble_to_fit, AssemblerError};
+use chip8::disassembler::disassemble_rom;
+use chip8::memory::{PROGRAM_START, RAM_SIZE, XO_CHIP_RAM_SIZE};
+use std::path::PathBuf;
+
+#[test]
+fn labels_constants_and_data() {
+    let source = "
+        HEIGHT equ 5
+        start:  LD I, digit   ; point at the sprite
+                DRW V0, V1, HEIGHT
+                CALL wait
+                JP start
+        wait:   LD V2, K
+                ret
+        digit:  db 0xF0, 0b10010000, 144
+                dw 0x1234
+    ";
+    assert_eq!(
+        assemble(source),
+        Ok(vec![
+            0xA2, 0x0C, 0xD0, 0x15, 0x22, 0x08, 0x12, 0x00, 0xF2, 0x0A, 0x00, 0xEE, 0xF0, 0x90,
+            0x90, 0x12, 0x34,
+        ])
+    );
+}
+
+#[test]
+fn errors_have_line_numbers() {
+    let error = |line, message: &str| {
+        Err(AssemblerError {
+            line,
+            message: message.to_string(),
+        })
+    };
+    assert_eq!(
+        assemble("CLS\nJP nowhere"),
+        error(2, "'nowhere' isn't defined.")
+    );
+    assert_eq!(
+        assemble("LD V0, 0x100"),
+        error(
+            1,
+            "0x100 is too large for a byte, which can be at most 0xFF."
+        )
+    );
+    assert_eq!(
+        assemble("a:\na: CLS"),
+        error(2, "'a' is defined more than once.")
+    );
+    assert_eq!(
+        assemble("\n\nMOV V0, V1"),
+        error(3, "Unknown instruction 'MOV V0, V1'.")
+    );
+    assert_eq!(
+        assemble("V1: CLS"),
+        error(1, "'V1' is reserved and can't be used as a name.")
+    );
+}
+
+#[test]
+fn directives_ignore_case_and_spacing() {
+    assert_eq!(
+        assemble("w equ 1\nx EQU 2\ny\tEqu\t3\nz  equ   4\ndb w, x, y, z"),
+        Ok(vec![1, 2, 3, 4])
+    );
+}
+
+#[test]
+fn programs_have_to_fit_in_program_space() {
+    let space = RAM_SIZE - PROGRAM_START;
+    let filler = "db 0xAA\n".repeat(space - 2);
+    assert_eq!(
+        assemble(&format!("{}dw 0x1234", filler)).unwrap().len(),
+        space
+    );
+    assert_eq!(
+        assemble(&format!("{}CLS\ndb 1\nCLS", filler)),
+        Err(AssemblerError {
+            line: space,
+            message: "The program doesn't fit in the 3584 bytes of program space.".to_string(),
+        })
+    );
+
+    let xo_chip_space = XO_CHIP_RAM_SIZE - PROGRAM_START;
+    assert_eq!(
+        assemble_to_fit(&format!("{}CLS\ndb 1", filler), xo_chip_space)
+            .unwrap()
+            .len(),
+        space + 1
+    );
+}
+
+#[test]
+fn long_load_takes_four_bytes() {
+    assert_eq!(
+        assemble("LD I, long data\nJP data\ndata: db 1"),
+        Ok(vec![0xF0, 0x00, 0x02, 0x06, 0x12, 0x06, 0x01])
+    );
+}
+
+#[test]
+fn disassembled_roms_assemble_to_the_same_bytes() {
+    let roms = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("roms");
+    for entry in std::fs::read_dir(roms).unwrap() {
+        let path = entry.unwrap().path();
+        let rom = std::fs::read(&path).unwrap();
+        let listing = disassemble_rom(&rom);
+        assert_eq!(
+            assemble(&listing).unwrap(),
+            rom,
+            "{} didn't survive a round trip through the disassembler.",
+            path.display()
+        );
+    }
+}
@@ -0,0 +1,125 @@
+extern crate chip8;
+
+mod common;
+
+use chip8::breakpoint::{Access, Comparison, Condition};
+use chip8::{Breakpoint, BreakpointHit, Chip8Computer};
+
+const PROGRAM: &str = "
+loop:
+    LD I, data
+    ADD V5, 1
+    LD [I], V1
+    LD V1, [I]
+    JP loop
+data:
+    db 0, 0
+";
+
+fn computer_running(source: &str) -> Chip8Computer {
+    let mut computer = common::computer_running(source);
+    //Keep I pointing at the data, so both the store and the load access it.
+    computer.quirks.memory_increment = false;
+    computer
+}
+
+///Ticks until a breakpoint is hit, giving up after a few hundred instructions.
+fn run_to_breakpoint(computer: &mut Chip8Computer) -> Option<BreakpointHit> {
+    for _ in 0..300 {
+        computer.tick().unwrap();
+        if let Some(hit) = computer.breakpoint_hit() {
+            return Some(hit);
+        }
+    }
+    None
+}
+
+#[test]
+fn address_breakpoints_stop_before_the_instruction() {
+    let mut computer = computer_running(PROGRAM);
+    let breakpoint = Breakpoint::Address {
+        address: 0x208,
+        condition: None,
+    };
+    computer.add_breakpoint(breakpoint);
+
+    let hit = run_to_breakpoint(&mut computer).unwrap();
+    assert_eq!(hit.program_counter, 0x208);
+    assert_eq!(hit.instruction_address, 0x206);
+    assert_eq!(computer.cpu.data_registers[5], 1);
+
+    //Continuing executes the instruction under the breakpoint before it can be hit again.
+    assert_eq!(run_to_breakpoint(&mut computer).unwrap(), hit);
+    assert_eq!(computer.cpu.data_registers[5], 2);
+
+    assert!(computer.remove_breakpoint(&breakpoint));
+    assert!(!computer.remove_breakpoint(&breakpoint));
+    assert_eq!(run_to_breakpoint(&mut computer), None);
+}
+
+#[test]
+fn conditions_stop_when_they_become_true() {
+    let condition: Condition = "V5 == 3".parse().unwrap();
+    let mut computer = computer_running(PROGRAM);
+    computer.add_breakpoint(Breakpoint::Condition(condition));
+
+    let hit = run_to_breakpoint(&mut computer).unwrap();
+    assert_eq!(hit.instruction_address, 0x202);
+    assert_eq!(computer.cpu.data_registers[5], 3);
+    //The condition stays true until V5 is incremented again, which doesn't stop execution.
+    assert_eq!(run_to_breakpoint(&mut computer), None);
+}
+
+#[test]
+fn address_breakpoints_can_have_conditions() {
+    let mut computer = computer_running(PROGRAM);
+    computer.add_breakpoint(Breakpoint::Address {
+        address: 0x202,
+        condition: Some("v5>=0x10".parse().unwrap()),
+    });
+    run_to_breakpoint(&mut computer).unwrap();
+    assert_eq!(computer.cpu.data_registers[5], 0x10);
+}
+
+#[test]
+fn watchpoints_stop_after_memory_accesses() {
+    let watch = |start, end, access| Breakpoint::Watch { start, end, access };
+    let mut computer = computer_running(PROGRAM);
+    computer.add_breakpoint(watch(0x20B, 0x20D, Access::Write));
+    let hit = run_to_breakpoint(&mut computer).unwrap();
+    assert_eq!(hit.instruction_address, 0x204);
+    assert_eq!(hit.program_counter, 0x206);
+
+    let mut computer = computer_running(PROGRAM);
+    computer.add_breakpoint(watch(0x20A, 0x20A, Access::Read));
+    assert_eq!(
+        run_to_breakpoint(&mut computer)
+            .unwrap()
+            .instruction_address,
+        0x206
+    );
+
+    //Instruction fetches and accesses outside the range don't count.
+    let mut computer = computer_running(PROGRAM);
+    computer.add_breakpoint(watch(0x200, 0x209, Access::ReadWrite));
+    computer.add_breakpoint(watch(0x20C, 0x20C, Access::ReadWrite));
+    assert_eq!(run_to_breakpoint(&mut computer), None);
+}
+
+#[test]
+fn parses_and_shows_conditions() {
+    assert_eq!(
+        "VA != 0x1F".parse(),
+        Ok(Condition {
+            register: 0xA,
+            comparison: Comparison::NotEqual,
+            value: 0x1F,
+        })
+    );
+    let condition: Condition = "v0<=7".parse().unwrap();
+    assert_eq!(condition.comparison, Comparison::LessOrEqual);
+    assert_eq!(condition.to_string(), "V0 <= 0x07");
+    for invalid in ["V5", "V5 = 3", "VG == 1", "V5 == 256", "I == 3"] {
+        assert!(invalid.parse::<Condition>().is_err(), "{}", invalid);
+    }
+}
@@ -0,0 +1,30 @@
+//Not every test uses every helper.
+#![allow(dead_code)]
+
+use chip8::assembler::assemble;
+use chip8::{Chip8Computer, SaveState};
+use std::sync::mpsc::channel;
+
+///Creates a computer with the program assembled and loaded.
+pub fn computer_running(source: &str) -> Chip8Computer {
+    let (sender, _) = channel();
+    let mut computer = Chip8Computer::new(sender);
+    computer.load_rom(assemble(source).unwrap()).unwrap();
+    computer
+}
+
+pub fn tick(computer: &mut Chip8Computer, count: usize) {
+    for _ in 0..count {
+        computer.tick().unwrap();
+    }
+}
+
+///Executes instructions, returning the state before each of them and after the last.
+pub fn run(computer: &mut Chip8Computer, instructions: usize) -> Vec<SaveState> {
+    let mut states = vec![computer.snapshot()];
+    for _ in 0..instructions {
+        computer.tick().unwrap();
+        states.push(computer.snapshot());
+    }
+    states
+}
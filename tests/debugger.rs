@@ -0,0 +1,171 @@
+extern crate chip8;
+
+use chip8::assembler::assemble;
+use chip8::debugger::Debugger;
+use chip8::{Chip8Computer, EmulatorCommand, ThreadedEmulator, TimingMode};
+
+///Starts an emulator thread with the assembled program loaded and a debugger attached to it.
+fn debug_program(source: &str) -> Debugger {
+    let (sender, receiver) = Chip8Computer::initialize();
+    sender
+        .send(EmulatorCommand::LoadRom(assemble(source).unwrap()))
+        .unwrap();
+    Debugger::new(sender, receiver)
+}
+
+const COUNTER: &str = "
+    LD V0, 1
+loop:
+    ADD V0, 1
+    CALL sub
+    JP loop
+sub:
+    RET
+";
+
+#[test]
+fn continue_stops_at_breakpoints() {
+    let mut debugger = debug_program(COUNTER);
+    assert!(debugger.execute("break 0x206").unwrap().contains("0x0206"));
+    assert_eq!(
+        debugger.execute("c").unwrap(),
+        "Reached breakpoint 0x0206.\n>* 0x0206: 1202  JP 0x202\n"
+    );
+    assert!(debugger.execute("regs").unwrap().contains("V0 02"));
+
+    //Continuing runs the instruction under the breakpoint before stopping on it again.
+    debugger.execute("c").unwrap();
+    assert!(debugger.execute("regs").unwrap().contains("V0 03"));
+
+    debugger.execute("delete 1").unwrap();
+    assert_eq!(
+        debugger.execute("break").unwrap(),
+        "There are no breakpoints.\n"
+    );
+    assert!(debugger.execute("delete 1").is_err());
+}
+
+#[test]
+fn conditions_and_watchpoints() {
+    let mut debugger = debug_program(COUNTER);
+    debugger.execute("break 0x202 if V0 == 4").unwrap();
+    debugger.execute("break if V0 > 5").unwrap();
+    debugger.execute("watch 0x2F0 2 w").unwrap();
+    assert_eq!(
+        debugger.execute("b").unwrap(),
+        "1: 0x0202 if V0 == 0x04\n2: when V0 > 0x05\n3: write 0x02F0-0x02F1\n"
+    );
+    assert!(debugger.execute("break if V0 > 5").is_err());
+
+    let stop = debugger.execute("c").unwrap();
+    assert!(stop.starts_with("Reached breakpoint 0x0202 if V0 == 0x04."));
+    assert!(debugger.execute("regs").unwrap().contains("V0 04"));
+
+    debugger.execute("delete 1").unwrap();
+    let stop = debugger.execute("continue").unwrap();
+    assert!(stop.starts_with("Stopped on when V0 > 0x05 after the instruction at 0x0202."));
+    assert!(debugger.execute("regs").unwrap().contains("V0 06"));
+
+    assert!(debugger.execute("watch 0x200 0").is_err());
+    assert!(debugger.execute("watch 0x200 1 x").is_err());
+}
+
+#[test]
+fn step_and_inspect() {
+    let mut debugger = debug_program(COUNTER);
+    assert_eq!(
+        debugger.execute("step 3").unwrap(),
+        ">  0x0208: 00EE  RET\n"
+    );
+    assert_eq!(debugger.execute("stack").unwrap(), "0x0206\n");
+    assert!(debugger
+        .execute("mem 0x200 4")
+        .unwrap()
+        .starts_with("0x0200:    0x6001  0x7001"));
+    assert_eq!(
+        debugger.execute("disasm 0x200 2").unwrap(),
+        "   0x0200: 6001  LD V0, 0x01\n   0x0202: 7001  ADD V0, 0x01\n"
+    );
+    assert_eq!(debugger.execute("screen").unwrap().lines().count(), 32);
+}
+
+#[test]
+fn set_registers() {
+    let mut debugger = debug_program(COUNTER);
+    debugger.execute("set V3 0x10").unwrap();
+    debugger.execute("set i 768").unwrap();
+    let registers = debugger.execute("regs").unwrap();
+    assert!(registers.contains("V3 10"));
+    assert!(registers.contains("I 0x0300"));
+
+    debugger.execute("set pc 0x202").unwrap();
+    debugger.execute("step").unwrap();
+    assert!(debugger.execute("regs").unwrap().contains("V0 01"));
+
+    assert!(debugger.execute("set V3 0x100").is_err());
+    assert!(debugger.execute("set VG 1").is_err());
+    assert!(debugger.execute("jump").is_err());
+}
+
+#[test]
+fn rewind_goes_back_whole_frames() {
+    let (sender, receiver) = Chip8Computer::initialize();
+    sender
+        .send(EmulatorCommand::SetTimingMode(
+            TimingMode::InstructionsPerFrame(4),
+        ))
+        .unwrap();
+    sender.send(EmulatorCommand::SetRewindFrames(60)).unwrap();
+    sender
+        .send(EmulatorCommand::LoadRom(assemble(COUNTER).unwrap()))
+        .unwrap();
+    let mut debugger = Debugger::new(sender, receiver);
+
+    assert!(debugger.execute("rewind").is_err());
+    //Frames start after instructions 4 and 8, both returning to 0x206.
+    debugger.execute("step 10").unwrap();
+    assert!(debugger.execute("regs").unwrap().contains("V0 04"));
+    assert_eq!(
+        debugger.execute("rewind").unwrap(),
+        ">  0x0206: 1202  JP 0x202\n"
+    );
+    assert!(debugger.execute("regs").unwrap().contains("V0 03"));
+    assert!(debugger
+        .execute("rewind 5")
+        .unwrap()
+        .starts_with("Rewound as far back as the history goes, 1 of 5 frames.\n>  0x0206"));
+    assert!(debugger.execute("regs").unwrap().contains("V0 02"));
+    assert!(debugger.execute("rewind 0").is_err());
+}
+
+#[test]
+fn stepping_backwards() {
+    let (sender, receiver) = Chip8Computer::initialize();
+    sender.send(EmulatorCommand::SetUndoLimit(100)).unwrap();
+    sender
+        .send(EmulatorCommand::LoadRom(assemble(COUNTER).unwrap()))
+        .unwrap();
+    let mut debugger = Debugger::new(sender, receiver);
+
+    debugger.execute("step 5").unwrap();
+    assert_eq!(debugger.execute("rs 2").unwrap(), ">  0x0208: 00EE  RET\n");
+    debugger.execute("break 0x200").unwrap();
+    assert_eq!(
+        debugger.execute("rc").unwrap(),
+        "Reached breakpoint 0x0200.\n>* 0x0200: 6001  LD V0, 0x01\n"
+    );
+    assert!(debugger.execute("regs").unwrap().contains("V0 00"));
+    assert!(debugger.execute("rstep").is_err());
+
+    debugger.execute("delete 1").unwrap();
+    debugger.execute("step 5").unwrap();
+    assert_eq!(
+        debugger.execute("rs 10").unwrap(),
+        "Undid as many instructions as the undo log holds, 5 of 10.\n>  0x0200: 6001  LD V0, 0x01\n"
+    );
+    debugger.execute("step 3").unwrap();
+    assert_eq!(
+        debugger.execute("rcontinue").unwrap(),
+        "Reached the start of the undo log.\n>  0x0200: 6001  LD V0, 0x01\n"
+    );
+}
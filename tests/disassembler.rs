@@ -0,0 +1,74 @@
+extern crate chip8;
+
+use chip8::disassembler::{disassemble, disassemble_rom};
+use chip8::Instruction;
+
+#[test]
+fn mnemonics() {
+    for (value, expected) in [
+        (0x00E0, "CLS"),
+        (0x00C4, "SCD 4"),
+        (0x22A4, "CALL 0x2A4"),
+        (0x631F, "LD V3, 0x1F"),
+        (0x5232, "SAVE V2 - V3"),
+        (0x8AB7, "SUBN VA, VB"),
+        (0xB300, "JP V0, 0x300"),
+        (0xD015, "DRW V0, V1, 5"),
+        (0xE2A1, "SKNP V2"),
+        (0xF000, "LD I, long"),
+        (0xF201, "PLANE 2"),
+        (0xF40A, "LD V4, K"),
+        (0xF565, "LD V5, [I]"),
+    ] {
+        assert_eq!(
+            disassemble(&Instruction::new(value)).as_deref(),
+            Some(expected)
+        );
+    }
+    for value in [0x0000, 0x5121, 0x8008, 0xE000, 0xF0FF] {
+        assert_eq!(disassemble(&Instruction::new(value)), None);
+    }
+}
+
+#[test]
+fn rom_listing_separates_code_from_data() {
+    let rom = [
+        0xA2, 0x0A, // LD I, data_20A
+        0x22, 0x08, // CALL sub_208
+        0x12, 0x04, // JP label_204
+        0xFF, 0xFF, // never reached
+        0x00, 0xEE, // RET
+        0x3C, 0x42, // sprite data
+    ];
+    let listing = disassemble_rom(&rom);
+    let lines: Vec<&str> = listing
+        .lines()
+        .map(|line| line.split(';').next().unwrap().trim())
+        .collect();
+    assert_eq!(
+        lines,
+        [
+            "LD I, data_20A",
+            "CALL sub_208",
+            "label_204:",
+            "JP label_204",
+            "db 0xFF, 0xFF",
+            "sub_208:",
+            "RET",
+            "data_20A:",
+            "db 0x3C, 0x42",
+        ]
+    );
+}
+
+#[test]
+fn tracing_stops_at_the_top_of_memory() {
+    //Fills XO-CHIP memory up to 0xFFFF with skips, so the last ones would skip past the end.
+    let rom = [0x30, 0x00].repeat((0x10000 - 0x200) / 2);
+    let listing = disassemble_rom(&rom);
+    assert_eq!(listing.lines().count(), rom.len() / 2);
+    assert_eq!(
+        listing.lines().last(),
+        Some("    SE V0, 0x00  ; 0xFFFE: 3000")
+    );
+}
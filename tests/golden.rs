@@ -0,0 +1,151 @@
+//!Runs the bundled test ROMs headlessly until they settle and compares the screen against golden images.
+//!
+//!The golden images in `tests/golden` are text files with one line per row of the screen: `.` for palette index 0,
+//!`#` for 1 and `2`/`3` for the XO-CHIP colours. Run with `UPDATE_GOLDEN=1` to rewrite them from the current output.
+
+extern crate chip8;
+
+use chip8::frame_buffer::{MAX_HEIGHT, PLANE_COUNT};
+use chip8::headless::{self, RunLimits, RunOutcome};
+use chip8::{Chip8Computer, FrameBuffer, Quirks};
+use std::path::PathBuf;
+use std::sync::mpsc::channel;
+
+///The default speed of 700 instructions per second.
+const INSTRUCTIONS_PER_FRAME: u32 = 700 / 60;
+///Every test ROM finishes well within this many frames, anything longer is treated as a failure.
+const FRAME_LIMIT: u64 = 3000;
+
+fn manifest_path(path: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(path)
+}
+
+///Runs the ROM until it ends up in an endless loop and returns the computer in that state.
+fn run_rom(rom_name: &str, quirks: Quirks) -> Chip8Computer {
+    let rom_bytes = std::fs::read(manifest_path("roms").join(rom_name))
+        .unwrap_or_else(|e| panic!("Couldn't read the ROM {}: {}", rom_name, e));
+    let (response_sender, _) = channel();
+    let mut computer = Chip8Computer::new(response_sender);
+    computer.quirks = quirks;
+    computer.load_rom(rom_bytes).unwrap();
+
+    let limits = RunLimits {
+        frames: Some(FRAME_LIMIT),
+        instructions: None,
+    };
+    match headless::run(&mut computer, INSTRUCTIONS_PER_FRAME, limits) {
+        RunOutcome::Looped { .. } => computer,
+        outcome => panic!("{} didn't settle on a final screen: {}", rom_name, outcome),
+    }
+}
+
+fn screen_to_golden(frame_buffer: &FrameBuffer) -> String {
+    let mut golden = String::new();
+    for y in 0..frame_buffer.height() {
+        for x in 0..frame_buffer.width() {
+            golden.push(match frame_buffer.get_pixel(x, y) {
+                0 => '.',
+                1 => '#',
+                2 => '2',
+                _ => '3',
+            });
+        }
+        golden.push('\n');
+    }
+    golden
+}
+
+///Parses a golden image back into the bitplanes of a frame buffer, checking that it has the expected size.
+fn golden_to_buffer(
+    golden: &str,
+    width: usize,
+    height: usize,
+) -> [[u128; MAX_HEIGHT]; PLANE_COUNT] {
+    let rows: Vec<&str> = golden.lines().collect();
+    assert_eq!(rows.len(), height, "The golden image has the wrong height.");
+
+    let mut buffer = [[0; MAX_HEIGHT]; PLANE_COUNT];
+    for (y, row) in rows.into_iter().enumerate() {
+        assert_eq!(
+            row.chars().count(),
+            width,
+            "Row {} of the golden image has the wrong width.",
+            y
+        );
+        for (x, pixel) in row.chars().enumerate() {
+            let colour = match pixel {
+                '.' => 0,
+                '#' => 1,
+                '2' => 2,
+                '3' => 3,
+                _ => panic!(
+                    "Unexpected character {:?} in row {} of the golden image.",
+                    pixel, y
+                ),
+            };
+            for (plane, plane_buffer) in buffer.iter_mut().enumerate() {
+                if colour & (1 << plane) != 0 {
+                    plane_buffer[y] |= 1 << (127 - x);
+                }
+            }
+        }
+    }
+    buffer
+}
+
+fn assert_matches_golden(rom_name: &str, quirks: Quirks) {
+    let computer = run_rom(rom_name, quirks);
+    let frame_buffer = &computer.frame_buffer;
+    let golden_path = manifest_path("tests/golden").join(format!("{}.txt", rom_name));
+
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::write(&golden_path, screen_to_golden(frame_buffer)).unwrap();
+        return;
+    }
+
+    let golden = std::fs::read_to_string(&golden_path)
+        .unwrap_or_else(|e| panic!("Couldn't read {}: {}", golden_path.display(), e));
+    let expected = golden_to_buffer(&golden, frame_buffer.width(), frame_buffer.height());
+    assert!(
+        frame_buffer.buffer == expected,
+        "The screen of {} doesn't match its golden image.\nExpected:\n{}\nActual:\n{}",
+        rom_name,
+        golden,
+        screen_to_golden(frame_buffer)
+    );
+}
+
+#[test]
+fn chip8_logo() {
+    assert_matches_golden("1-chip8-logo.ch8", Quirks::cosmac_vip());
+}
+
+#[test]
+fn ibm_logo() {
+    assert_matches_golden("2-ibm-logo.ch8", Quirks::cosmac_vip());
+}
+
+#[test]
+fn corax_plus() {
+    assert_matches_golden("3-corax+.ch8", Quirks::cosmac_vip());
+}
+
+#[test]
+fn flags() {
+    assert_matches_golden("4-flags.ch8", Quirks::cosmac_vip());
+}
+
+#[test]
+fn c8_test() {
+    //This ROM checks for CHIP-48's shifts and loads/stores but still expects Bnnn to add V0.
+    let quirks = Quirks {
+        jump_with_vx: false,
+        ..Quirks::chip48()
+    };
+    assert_matches_golden("c8_test.c8", quirks);
+}
+
+#[test]
+fn test_opcode() {
+    assert_matches_golden("test_opcode.ch8", Quirks::cosmac_vip());
+}
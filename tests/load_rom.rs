@@ -0,0 +1,52 @@
+extern crate chip8;
+
+use chip8::memory::{PROGRAM_START, RAM_SIZE, XO_CHIP_RAM_SIZE};
+use chip8::{Chip8Computer, EmulatorCommand, EmulatorResponse, RomTooLarge, ThreadedEmulator};
+use std::sync::mpsc::channel;
+
+#[test]
+fn roms_too_large_for_memory_are_rejected() {
+    let (sender, _) = channel();
+    let mut computer = Chip8Computer::new(sender);
+    computer.load_rom(vec![0x12, 0x00]).unwrap();
+
+    let space = RAM_SIZE - PROGRAM_START;
+    assert_eq!(
+        computer.load_rom(vec![0xAA; space + 1]),
+        Err(RomTooLarge {
+            size: space + 1,
+            program_space: space,
+        })
+    );
+    //The ROM that was loaded before is untouched.
+    assert_eq!(
+        computer.memory.ram[PROGRAM_START..PROGRAM_START + 3],
+        [0x12, 0x00, 0x00]
+    );
+
+    computer.load_rom(vec![0xAA; space]).unwrap();
+    assert_eq!(computer.memory.ram[RAM_SIZE - 1], 0xAA);
+
+    computer.enable_xo_chip();
+    let space = XO_CHIP_RAM_SIZE - PROGRAM_START;
+    computer.load_rom(vec![0xBB; space]).unwrap();
+    assert!(computer.load_rom(vec![0xBB; space + 1]).is_err());
+}
+
+#[test]
+fn the_emulator_thread_reports_roms_too_large() {
+    let (sender, receiver) = Chip8Computer::initialize();
+    sender
+        .send(EmulatorCommand::LoadRom(vec![0xAA; RAM_SIZE]))
+        .unwrap();
+    //The thread is still running and answers the next command.
+    sender.send(EmulatorCommand::GetRegisters).unwrap();
+    let cpu = receiver
+        .iter()
+        .find_map(|response| match response {
+            EmulatorResponse::Cpu(cpu) => Some(cpu),
+            _ => None,
+        })
+        .unwrap();
+    assert_eq!(cpu.program_counter, PROGRAM_START as u16);
+}
@@ -0,0 +1,170 @@
+extern crate chip8;
+
+mod common;
+
+use chip8::headless::{self, RunLimits};
+use chip8::movie::{Playback, CHECKSUM_INTERVAL, VERSION};
+use chip8::{Chip8Computer, Movie, MovieError, RunOutcome, TimingMode};
+use common::tick;
+
+///Moves a dot right while key 5 is held and draws it at a random height, so both the keys and the seed matter.
+const PROGRAM: &str = "
+loop:
+    LD V2, 5
+    SKNP V2
+    ADD V0, 1
+    RND V1, 0x1F
+    LD I, dot
+    DRW V0, V1, 1
+    JP loop
+dot:
+    db 0x80
+";
+
+const INSTRUCTIONS_PER_FRAME: u32 = 10;
+
+fn computer_running(source: &str) -> Chip8Computer {
+    let mut computer = common::computer_running(source);
+    computer.timing_mode = TimingMode::InstructionsPerFrame(INSTRUCTIONS_PER_FRAME);
+    computer
+}
+
+///Runs whole frames, holding key 5 during the frames for which `pressed` is true.
+fn play(computer: &mut Chip8Computer, frames: std::ops::Range<usize>, pressed: fn(usize) -> bool) {
+    for frame in frames {
+        computer.set_key(5, pressed(frame));
+        tick(computer, INSTRUCTIONS_PER_FRAME as usize);
+    }
+}
+
+///Records 3 seconds of pressing and releasing key 5.
+fn record(computer: &mut Chip8Computer) -> Movie {
+    computer.start_recording();
+    play(computer, 0..180, |frame| frame % 50 < 20);
+    computer.stop_recording().unwrap()
+}
+
+///Plays a movie back headless, with a different number of instructions per frame than it was recorded with.
+fn play_back(movie: Movie) -> (Chip8Computer, RunOutcome) {
+    let mut computer = computer_running(PROGRAM);
+    computer.play_movie(movie).unwrap();
+    let outcome = headless::run(&mut computer, 7, RunLimits::default());
+    (computer, outcome)
+}
+
+#[test]
+fn movies_replay_exactly() {
+    let mut recorder = computer_running(PROGRAM);
+    recorder.set_seed(7);
+    let movie = record(&mut recorder);
+    assert_eq!(movie.frames.len(), 180);
+    assert_eq!(movie.seed, 7);
+    assert_eq!(
+        movie
+            .frames
+            .iter()
+            .filter(|frame| frame.keys == 1 << 5)
+            .count(),
+        80
+    );
+    assert_eq!(Movie::from_bytes(&movie.to_bytes()).unwrap(), movie);
+
+    let (player, outcome) = play_back(movie);
+    assert_eq!(
+        outcome,
+        RunOutcome::MovieFinished(Playback {
+            frame: 180,
+            frames: 180,
+            desync: None,
+        })
+    );
+    assert_eq!(player.seed(), 7);
+    assert_eq!(player.snapshot(), recorder.snapshot());
+}
+
+#[test]
+fn frame_limits_count_the_movies_frames() {
+    let movie = record(&mut computer_running(PROGRAM));
+    let mut player = computer_running(PROGRAM);
+    player.play_movie(movie).unwrap();
+    let limits = RunLimits {
+        frames: Some(100),
+        instructions: None,
+    };
+    //Frames last 10 instructions in the movie, not 7.
+    assert_eq!(
+        headless::run(&mut player, 7, limits),
+        RunOutcome::FrameLimit
+    );
+    assert_eq!(player.playback().unwrap().frame, 100);
+}
+
+#[test]
+fn desyncs_are_reported() {
+    let mut movie = record(&mut computer_running(PROGRAM));
+    movie.frames[10].keys ^= 1 << 5;
+
+    let (_, outcome) = play_back(movie);
+    let playback = match outcome {
+        RunOutcome::MovieFinished(playback) => playback,
+        outcome => panic!("Expected the movie to finish, but: {}", outcome),
+    };
+    assert_eq!(playback.desync.unwrap().frame, CHECKSUM_INTERVAL - 1);
+}
+
+#[test]
+fn movies_only_play_with_their_rom() {
+    let movie = record(&mut computer_running(PROGRAM));
+
+    let mut other = computer_running("loop: JP loop");
+    assert!(matches!(
+        other.play_movie(movie.clone()),
+        Err(MovieError::WrongRom { .. })
+    ));
+    let mut xo_chip = computer_running(PROGRAM);
+    xo_chip.enable_xo_chip();
+    assert_eq!(
+        xo_chip.play_movie(movie.clone()),
+        Err(MovieError::XoChipEnabled)
+    );
+
+    let mut corrupted = movie.to_bytes();
+    corrupted[20] ^= 0x01;
+    assert_eq!(
+        Movie::from_bytes(&corrupted),
+        Err(MovieError::ChecksumMismatch)
+    );
+    assert_eq!(
+        Movie::from_bytes(&movie.to_bytes()[..30]),
+        Err(MovieError::Truncated)
+    );
+    assert_eq!(Movie::from_bytes(b"CH8S"), Err(MovieError::NotAMovie));
+    let mut newer = movie.to_bytes();
+    newer[5] += 1;
+    let error = Movie::from_bytes(&newer).unwrap_err();
+    assert_eq!(error, MovieError::UnsupportedVersion(VERSION + 1));
+    assert!(error.to_string().starts_with("The movie has version"));
+}
+
+#[test]
+fn going_backwards_while_recording_drops_the_undone_frames() {
+    let mut recorder = computer_running(PROGRAM);
+    recorder.set_rewind_frames(100);
+    recorder.set_undo_limit(1000);
+    recorder.start_recording();
+    play(&mut recorder, 0..60, |frame| frame < 30);
+
+    //Back into frame 57, then to the start of frame 48.
+    assert_eq!(recorder.step_back(25), 25);
+    assert_eq!(recorder.rewind(10), 10);
+    play(&mut recorder, 48..120, |frame| frame % 3 == 0);
+    let movie = recorder.stop_recording().unwrap();
+    assert_eq!(movie.frames.len(), 120);
+
+    let (player, outcome) = play_back(movie);
+    assert!(matches!(
+        outcome,
+        RunOutcome::MovieFinished(Playback { desync: None, .. })
+    ));
+    assert_eq!(player.snapshot(), recorder.snapshot());
+}
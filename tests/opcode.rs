@@ -0,0 +1,52 @@
+extern crate chip8;
+
+use std::convert::TryFrom;
+
+use chip8::disassembler::disassemble;
+use chip8::{Instruction, Opcode};
+
+#[test]
+fn decoding_then_encoding_gives_back_every_valid_instruction() {
+    for value in 0..=u16::MAX {
+        let instruction = Instruction::new(value);
+        match Opcode::try_from(instruction) {
+            Ok(opcode) => assert_eq!(opcode.encode(), value, "{}", opcode),
+            Err(e) => assert_eq!(e.0, value),
+        }
+    }
+}
+
+#[test]
+fn decodes_operands() {
+    for (value, expected) in [
+        (0x00C4, Opcode::Scd(4)),
+        (0x1ABC, Opcode::Jp(0xABC)),
+        (0x3A1F, Opcode::SeImmediate(0xA, 0x1F)),
+        (0x5232, Opcode::Save(2, 3)),
+        (0x8AB7, Opcode::Subn(0xA, 0xB)),
+        (0xB300, Opcode::JpV0(0x300)),
+        (0xD01F, Opcode::Drw(0, 1, 0xF)),
+        (0xF000, Opcode::LdILong),
+        (0xF201, Opcode::Plane(2)),
+        (0xF40A, Opcode::LdVxK(4)),
+    ] {
+        assert_eq!(Opcode::try_from(Instruction::new(value)), Ok(expected));
+        assert_eq!(Instruction::from(expected).value, value);
+    }
+    for value in [0x0000, 0x5121, 0x8008, 0x9001, 0xE000, 0xF100, 0xF0FF] {
+        assert!(Opcode::try_from(Instruction::new(value)).is_err());
+    }
+}
+
+#[test]
+fn display_matches_the_disassembler() {
+    for value in 0..=u16::MAX {
+        let instruction = Instruction::new(value);
+        assert_eq!(
+            Opcode::try_from(instruction)
+                .ok()
+                .map(|opcode| opcode.to_string()),
+            disassemble(&instruction)
+        );
+    }
+}
@@ -0,0 +1,1003 @@
+//!Unit tests for every instruction the interpreter supports.
+//!
+//!Each test sets up the machine with [`OpcodeTest`], executes one or more instructions through the normal
+//!fetch/decode path and then checks the resulting state with the `assert_*` methods on [`Executed`].
+
+extern crate chip8;
+
+use chip8::frame_buffer::MAX_HEIGHT;
+use chip8::memory::BIG_FONT_ADDRESS;
+use chip8::{Chip8Computer, EmulatorResponse, ExecutionError, Instruction, Quirks};
+use std::sync::mpsc::{channel, Receiver};
+
+///Builds the state of the machine before an instruction is executed.
+struct OpcodeTest {
+    computer: Chip8Computer,
+    responses: Receiver<EmulatorResponse>,
+}
+
+///The state of the machine after executing an instruction, with assertions on it.
+struct Executed {
+    computer: Chip8Computer,
+    responses: Receiver<EmulatorResponse>,
+    result: Result<Instruction, ExecutionError>,
+}
+
+impl OpcodeTest {
+    fn new() -> Self {
+        let (response_sender, responses) = channel();
+        OpcodeTest {
+            computer: Chip8Computer::new(response_sender),
+            responses,
+        }
+    }
+
+    fn quirks(mut self, quirks: Quirks) -> Self {
+        self.computer.quirks = quirks;
+        self
+    }
+
+    fn xo_chip(mut self) -> Self {
+        self.computer.enable_xo_chip();
+        self
+    }
+
+    fn register(mut self, register: usize, value: u8) -> Self {
+        self.computer.cpu.data_registers[register] = value;
+        self
+    }
+
+    fn index(mut self, address: u16) -> Self {
+        self.computer.cpu.index_register = address;
+        self
+    }
+
+    fn program_counter(mut self, address: u16) -> Self {
+        self.computer.cpu.program_counter = address;
+        self
+    }
+
+    fn delay_timer(mut self, value: u8) -> Self {
+        self.computer.cpu.delay_timer = value;
+        self
+    }
+
+    fn ram(mut self, address: usize, bytes: &[u8]) -> Self {
+        self.computer.write_ram(address, bytes);
+        self
+    }
+
+    fn key(mut self, key: u8, pressed: bool) -> Self {
+        self.computer.input.set_key(key, pressed);
+        self
+    }
+
+    fn seed(mut self, seed: u64) -> Self {
+        self.computer.set_seed(seed);
+        self
+    }
+
+    fn high_resolution(mut self) -> Self {
+        self.computer.frame_buffer.set_high_resolution(true);
+        self
+    }
+
+    ///Stores the instruction at the program counter and executes it.
+    fn execute(mut self, instruction: u16) -> Executed {
+        let result = execute_at_program_counter(&mut self.computer, instruction);
+        Executed {
+            computer: self.computer,
+            responses: self.responses,
+            result,
+        }
+    }
+}
+
+impl Executed {
+    ///Executes another instruction, stored at the current program counter.
+    fn then(mut self, instruction: u16) -> Self {
+        self.result = execute_at_program_counter(&mut self.computer, instruction);
+        self
+    }
+
+    ///Changes the state between instructions.
+    fn with(mut self, change: impl FnOnce(&mut Chip8Computer)) -> Self {
+        change(&mut self.computer);
+        self
+    }
+
+    fn assert_ok(self) -> Self {
+        if let Err(e) = self.result {
+            panic!(
+                "Expected the instruction to succeed, but it failed with: {}",
+                e
+            );
+        }
+        self
+    }
+
+    fn assert_error(self, error: ExecutionError) -> Self {
+        assert_eq!(self.result.err(), Some(error));
+        self
+    }
+
+    fn assert_register(self, register: usize, value: u8) -> Self {
+        assert_eq!(
+            self.computer.cpu.data_registers[register], value,
+            "V{:X} has the wrong value.",
+            register
+        );
+        self
+    }
+
+    fn assert_vf(self, value: u8) -> Self {
+        self.assert_register(0xF, value)
+    }
+
+    fn assert_index(self, address: u16) -> Self {
+        assert_eq!(
+            self.computer.cpu.index_register, address,
+            "I has the wrong value."
+        );
+        self
+    }
+
+    fn assert_program_counter(self, address: u16) -> Self {
+        assert_eq!(
+            self.computer.cpu.program_counter, address,
+            "PC has the wrong value."
+        );
+        self
+    }
+
+    fn assert_ram(self, address: usize, bytes: &[u8]) -> Self {
+        assert_eq!(
+            &self.computer.memory.ram[address..address + bytes.len()],
+            bytes
+        );
+        self
+    }
+
+    fn assert_row(self, y: usize, row: u128) -> Self {
+        assert_eq!(
+            self.computer.frame_buffer.buffer[0][y], row,
+            "Row {} of the screen is wrong.",
+            y
+        );
+        self
+    }
+
+    fn assert_pixel(self, x: usize, y: usize, value: u8) -> Self {
+        assert_eq!(self.computer.frame_buffer.get_pixel(x, y), value);
+        self
+    }
+
+    fn assert_blank_screen(self) -> Self {
+        assert_eq!(self.computer.frame_buffer.buffer, [[0; MAX_HEIGHT]; 2]);
+        self
+    }
+
+    fn assert_exited(self) -> Self {
+        let exited = self
+            .responses
+            .try_iter()
+            .any(|response| matches!(response, EmulatorResponse::Exited));
+        assert!(exited, "The program didn't exit.");
+        self
+    }
+}
+
+fn execute_at_program_counter(
+    computer: &mut Chip8Computer,
+    instruction: u16,
+) -> Result<Instruction, ExecutionError> {
+    let address = computer.cpu.program_counter as usize;
+    computer.write_ram(address, &instruction.to_be_bytes());
+    computer.tick()
+}
+
+///The quirks presets that change how 8xy1/8xy2/8xy3 and the shifts behave.
+fn all_quirks() -> [Quirks; 3] {
+    [Quirks::cosmac_vip(), Quirks::chip48(), Quirks::xo_chip()]
+}
+
+///A row of the screen with the given byte drawn at the left edge.
+fn row(byte: u8) -> u128 {
+    (byte as u128) << 120
+}
+
+#[test]
+fn clear_screen() {
+    OpcodeTest::new()
+        .index(0x300)
+        .ram(0x300, &[0xFF])
+        .execute(0xD001)
+        .then(0x00E0)
+        .assert_ok()
+        .assert_blank_screen()
+        .assert_program_counter(0x204);
+}
+
+#[test]
+fn call_and_return() {
+    OpcodeTest::new()
+        .execute(0x2400)
+        .assert_ok()
+        .assert_program_counter(0x400)
+        .then(0x00EE)
+        .assert_ok()
+        .assert_program_counter(0x202);
+}
+
+#[test]
+fn return_with_empty_stack() {
+    OpcodeTest::new()
+        .execute(0x00EE)
+        .assert_error(ExecutionError::StackUnderflow {
+            program_counter: 0x200,
+        })
+        .assert_program_counter(0x200);
+}
+
+#[test]
+fn call_with_full_stack() {
+    let mut executed = OpcodeTest::new().execute(0x2200);
+    for _ in 0..15 {
+        executed = executed.then(0x2200).assert_ok();
+    }
+    executed
+        .then(0x2200)
+        .assert_error(ExecutionError::StackOverflow {
+            program_counter: 0x200,
+        });
+}
+
+#[test]
+fn jump() {
+    OpcodeTest::new()
+        .execute(0x1ABC)
+        .assert_ok()
+        .assert_program_counter(0xABC);
+}
+
+#[test]
+fn skip_equal_immediate() {
+    OpcodeTest::new()
+        .register(3, 0x42)
+        .execute(0x3342)
+        .assert_program_counter(0x204);
+    OpcodeTest::new()
+        .register(3, 0x41)
+        .execute(0x3342)
+        .assert_program_counter(0x202);
+}
+
+#[test]
+fn skip_not_equal_immediate() {
+    OpcodeTest::new()
+        .register(3, 0x41)
+        .execute(0x4342)
+        .assert_program_counter(0x204);
+    OpcodeTest::new()
+        .register(3, 0x42)
+        .execute(0x4342)
+        .assert_program_counter(0x202);
+}
+
+#[test]
+fn skip_equal_register() {
+    OpcodeTest::new()
+        .register(1, 7)
+        .register(2, 7)
+        .execute(0x5120)
+        .assert_program_counter(0x204);
+    OpcodeTest::new()
+        .register(1, 7)
+        .register(2, 8)
+        .execute(0x5120)
+        .assert_program_counter(0x202);
+}
+
+#[test]
+fn skip_not_equal_register() {
+    OpcodeTest::new()
+        .register(1, 7)
+        .register(2, 8)
+        .execute(0x9120)
+        .assert_program_counter(0x204);
+    OpcodeTest::new()
+        .register(1, 7)
+        .register(2, 7)
+        .execute(0x9120)
+        .assert_program_counter(0x202);
+}
+
+#[test]
+fn skip_over_long_load() {
+    OpcodeTest::new()
+        .xo_chip()
+        .ram(0x202, &[0xF0, 0x00, 0x12, 0x34])
+        .execute(0x3000)
+        .assert_program_counter(0x206);
+}
+
+#[test]
+fn load_immediate() {
+    OpcodeTest::new().execute(0x6A2B).assert_register(0xA, 0x2B);
+}
+
+#[test]
+fn add_immediate_wraps_without_touching_vf() {
+    OpcodeTest::new()
+        .register(2, 0xFF)
+        .register(0xF, 0x55)
+        .execute(0x7203)
+        .assert_register(2, 0x02)
+        .assert_vf(0x55);
+}
+
+#[test]
+fn move_register() {
+    OpcodeTest::new()
+        .register(4, 0x99)
+        .execute(0x8140)
+        .assert_register(1, 0x99)
+        .assert_register(4, 0x99);
+}
+
+#[test]
+fn logical_operations() {
+    for quirks in all_quirks() {
+        let vf = match quirks.vf_reset {
+            true => 0,
+            false => 0x77,
+        };
+        for (instruction, expected) in [(0x8121, 0b1110), (0x8122, 0b1000), (0x8123, 0b0110)] {
+            OpcodeTest::new()
+                .quirks(quirks)
+                .register(1, 0b1100)
+                .register(2, 0b1010)
+                .register(0xF, 0x77)
+                .execute(instruction)
+                .assert_register(1, expected)
+                .assert_vf(vf);
+        }
+    }
+}
+
+#[test]
+fn add_register_carry() {
+    OpcodeTest::new()
+        .register(1, 0xF0)
+        .register(2, 0x0F)
+        .execute(0x8124)
+        .assert_register(1, 0xFF)
+        .assert_vf(0);
+    OpcodeTest::new()
+        .register(1, 0xF0)
+        .register(2, 0x20)
+        .execute(0x8124)
+        .assert_register(1, 0x10)
+        .assert_vf(1);
+}
+
+#[test]
+fn subtract_register_borrow() {
+    OpcodeTest::new()
+        .register(1, 0x20)
+        .register(2, 0x20)
+        .execute(0x8125)
+        .assert_register(1, 0)
+        .assert_vf(1);
+    OpcodeTest::new()
+        .register(1, 0x10)
+        .register(2, 0x20)
+        .execute(0x8125)
+        .assert_register(1, 0xF0)
+        .assert_vf(0);
+}
+
+#[test]
+fn subtract_register_not_borrow() {
+    OpcodeTest::new()
+        .register(1, 0x10)
+        .register(2, 0x30)
+        .execute(0x8127)
+        .assert_register(1, 0x20)
+        .assert_vf(1);
+    OpcodeTest::new()
+        .register(1, 0x30)
+        .register(2, 0x10)
+        .execute(0x8127)
+        .assert_register(1, 0xE0)
+        .assert_vf(0);
+}
+
+#[test]
+fn shifts() {
+    for quirks in all_quirks() {
+        //With the shift quirk Vx is shifted in place, otherwise Vy is shifted into Vx.
+        let (right, left, source) = match quirks.shift {
+            true => (0b0100_0000, 0b0000_0010, 0b1000_0001),
+            false => (0b0010_0001, 0b1000_0100, 0b0100_0011),
+        };
+        OpcodeTest::new()
+            .quirks(quirks)
+            .register(1, 0b1000_0001)
+            .register(2, 0b0100_0011)
+            .execute(0x8126)
+            .assert_register(1, right)
+            .assert_register(2, 0b0100_0011)
+            .assert_vf(source & 1);
+        OpcodeTest::new()
+            .quirks(quirks)
+            .register(1, 0b1000_0001)
+            .register(2, 0b0100_0010)
+            .execute(0x812E)
+            .assert_register(1, left)
+            .assert_vf(match quirks.shift {
+                true => 1,
+                false => 0,
+            });
+    }
+}
+
+#[test]
+fn vf_as_destination_is_overwritten_by_the_flag() {
+    OpcodeTest::new()
+        .register(0xF, 0xFF)
+        .register(1, 0x01)
+        .execute(0x8F14)
+        .assert_vf(1);
+    OpcodeTest::new()
+        .register(0xF, 0x10)
+        .register(1, 0x01)
+        .execute(0x8F15)
+        .assert_vf(1);
+    OpcodeTest::new()
+        .register(0xF, 0x01)
+        .register(1, 0x10)
+        .execute(0x8F17)
+        .assert_vf(1);
+    for quirks in all_quirks() {
+        OpcodeTest::new()
+            .quirks(quirks)
+            .register(0xF, 0x02)
+            .execute(0x8FF6)
+            .assert_vf(0);
+        OpcodeTest::new()
+            .quirks(quirks)
+            .register(0xF, 0x80)
+            .execute(0x8FFE)
+            .assert_vf(1);
+    }
+}
+
+#[test]
+fn vf_as_source_is_read_before_the_flag_is_written() {
+    OpcodeTest::new()
+        .register(1, 0xFF)
+        .register(0xF, 0x02)
+        .execute(0x81F4)
+        .assert_register(1, 0x01)
+        .assert_vf(1);
+    OpcodeTest::new()
+        .register(1, 0x05)
+        .register(0xF, 0x03)
+        .execute(0x81F5)
+        .assert_register(1, 0x02)
+        .assert_vf(1);
+    OpcodeTest::new()
+        .register(1, 0x05)
+        .register(0xF, 0x03)
+        .execute(0x81F7)
+        .assert_register(1, 0xFE)
+        .assert_vf(0);
+}
+
+#[test]
+fn load_address() {
+    OpcodeTest::new().execute(0xA123).assert_index(0x123);
+}
+
+#[test]
+fn jump_with_offset() {
+    OpcodeTest::new()
+        .quirks(Quirks::cosmac_vip())
+        .register(0, 0x10)
+        .register(2, 0x20)
+        .execute(0xB230)
+        .assert_program_counter(0x240);
+    OpcodeTest::new()
+        .quirks(Quirks::chip48())
+        .register(0, 0x10)
+        .register(2, 0x20)
+        .execute(0xB230)
+        .assert_program_counter(0x250);
+}
+
+#[test]
+fn random_is_masked() {
+    for _ in 0..32 {
+        OpcodeTest::new().execute(0xC500).assert_register(5, 0);
+        let executed = OpcodeTest::new().execute(0xC50F);
+        assert_eq!(executed.computer.cpu.data_registers[5] & 0xF0, 0);
+    }
+}
+
+#[test]
+fn random_is_repeatable_with_a_seed() {
+    let random_bytes = |seed: u64| {
+        let mut executed = OpcodeTest::new().seed(seed).execute(0xC5FF).assert_ok();
+        let mut bytes = vec![executed.computer.cpu.data_registers[5]];
+        for _ in 0..4095 {
+            executed = executed
+                .with(|computer| computer.cpu.program_counter = 0x200)
+                .then(0xC5FF);
+            bytes.push(executed.computer.cpu.data_registers[5]);
+        }
+        bytes
+    };
+    let bytes = random_bytes(1234);
+    assert_eq!(random_bytes(1234), bytes);
+    assert_ne!(random_bytes(1235), bytes);
+    //Every value can come up, including 0xFF.
+    for value in 0..=255u8 {
+        assert!(bytes.contains(&value), "0x{:02X} never came up.", value);
+    }
+}
+
+#[test]
+fn draw_sets_vf_on_collision() {
+    OpcodeTest::new()
+        .quirks(Quirks::chip48())
+        .index(0x300)
+        .ram(0x300, &[0b1100_0000, 0b0011_0000])
+        .register(0, 2)
+        .register(1, 3)
+        .execute(0xD012)
+        .assert_ok()
+        .assert_row(3, row(0b1100_0000) >> 2)
+        .assert_row(4, row(0b0011_0000) >> 2)
+        .assert_vf(0)
+        .then(0xD012)
+        .assert_blank_screen()
+        .assert_vf(1);
+}
+
+#[test]
+fn draw_clips_or_wraps_at_the_edges() {
+    let clipping = Quirks::chip48();
+    let wrapping = Quirks::xo_chip();
+    OpcodeTest::new()
+        .quirks(clipping)
+        .index(0x300)
+        .ram(0x300, &[0xFF, 0xFF])
+        .register(0, 60)
+        .register(1, 31)
+        .execute(0xD012)
+        .assert_pixel(63, 31, 1)
+        .assert_pixel(0, 31, 0)
+        .assert_pixel(60, 0, 0);
+    OpcodeTest::new()
+        .quirks(wrapping)
+        .index(0x300)
+        .ram(0x300, &[0xFF, 0xFF])
+        .register(0, 60)
+        .register(1, 31)
+        .execute(0xD012)
+        .assert_pixel(63, 31, 1)
+        .assert_pixel(0, 31, 1)
+        .assert_pixel(60, 0, 1);
+}
+
+#[test]
+fn draw_starting_position_wraps() {
+    OpcodeTest::new()
+        .quirks(Quirks::chip48())
+        .index(0x300)
+        .ram(0x300, &[0x80])
+        .register(0, 64 + 5)
+        .register(1, 32 + 7)
+        .execute(0xD011)
+        .assert_pixel(5, 7, 1);
+}
+
+#[test]
+fn draw_big_sprite() {
+    let sprite: Vec<u8> = (0..32)
+        .map(|i| if i % 2 == 0 { 0xFF } else { 0x01 })
+        .collect();
+    OpcodeTest::new()
+        .quirks(Quirks::chip48())
+        .high_resolution()
+        .index(0x300)
+        .ram(0x300, &sprite)
+        .execute(0xD000)
+        .assert_ok()
+        .assert_row(0, 0xFF01 << 112)
+        .assert_row(15, 0xFF01 << 112)
+        .assert_row(16, 0);
+}
+
+#[test]
+fn draw_out_of_bounds() {
+    OpcodeTest::new()
+        .index(0xFFE)
+        .execute(0xD003)
+        .assert_error(ExecutionError::MemoryOutOfBounds {
+            program_counter: 0x200,
+            address: 0x1000,
+        })
+        .assert_program_counter(0x200);
+}
+
+#[test]
+fn display_wait_allows_one_draw_per_frame() {
+    OpcodeTest::new()
+        .quirks(Quirks::cosmac_vip())
+        .index(0x300)
+        .ram(0x300, &[0x80])
+        .execute(0xD001)
+        .assert_program_counter(0x202)
+        .then(0xD001)
+        .assert_program_counter(0x202)
+        .assert_pixel(0, 0, 1)
+        .with(|computer| computer.vertical_blank())
+        .then(0xD001)
+        .assert_program_counter(0x204)
+        .assert_pixel(0, 0, 0);
+}
+
+#[test]
+fn scroll_down() {
+    OpcodeTest::new()
+        .index(0x300)
+        .ram(0x300, &[0xFF])
+        .execute(0xD001)
+        .then(0x00C3)
+        .assert_row(0, 0)
+        .assert_row(3, row(0xFF));
+}
+
+#[test]
+fn scroll_sideways() {
+    OpcodeTest::new()
+        .index(0x300)
+        .ram(0x300, &[0xFF])
+        .register(0, 8)
+        .execute(0xD011)
+        .then(0x00FB)
+        .assert_row(0, row(0xFF) >> 12)
+        .then(0x00FC)
+        .then(0x00FC)
+        .assert_row(0, row(0xFF) >> 4);
+}
+
+#[test]
+fn exit() {
+    OpcodeTest::new()
+        .execute(0x00FD)
+        .assert_ok()
+        .assert_program_counter(0x200)
+        .assert_exited();
+}
+
+#[test]
+fn resolution() {
+    let executed = OpcodeTest::new().execute(0x00FF).assert_ok();
+    assert_eq!(executed.computer.frame_buffer.width(), 128);
+    assert_eq!(executed.computer.frame_buffer.height(), 64);
+    let executed = executed.then(0x00FE).assert_ok();
+    assert_eq!(executed.computer.frame_buffer.width(), 64);
+    assert_eq!(executed.computer.frame_buffer.height(), 32);
+}
+
+#[test]
+fn skip_if_key_pressed() {
+    OpcodeTest::new()
+        .register(1, 0xA)
+        .key(0xA, true)
+        .execute(0xE19E)
+        .assert_program_counter(0x204);
+    OpcodeTest::new()
+        .register(1, 0xA)
+        .execute(0xE19E)
+        .assert_program_counter(0x202);
+}
+
+#[test]
+fn skip_if_key_not_pressed() {
+    OpcodeTest::new()
+        .register(1, 0xA)
+        .execute(0xE1A1)
+        .assert_program_counter(0x204);
+    OpcodeTest::new()
+        .register(1, 0xA)
+        .key(0xA, true)
+        .execute(0xE1A1)
+        .assert_program_counter(0x202);
+}
+
+#[test]
+fn wait_for_key_press_and_release() {
+    OpcodeTest::new()
+        .execute(0xF30A)
+        .assert_program_counter(0x200)
+        .with(|computer| computer.input.set_key(0x7, true))
+        .then(0xF30A)
+        .assert_program_counter(0x200)
+        .then(0xF30A)
+        .assert_program_counter(0x200)
+        .with(|computer| computer.input.set_key(0x7, false))
+        .then(0xF30A)
+        .assert_program_counter(0x202)
+        .assert_register(3, 0x7);
+}
+
+#[test]
+fn timers() {
+    OpcodeTest::new()
+        .delay_timer(0x33)
+        .execute(0xF407)
+        .assert_register(4, 0x33);
+    let executed = OpcodeTest::new()
+        .register(4, 0x44)
+        .execute(0xF415)
+        .then(0xF418);
+    assert_eq!(executed.computer.cpu.delay_timer, 0x44);
+    assert_eq!(executed.computer.cpu.sound_timer, 0x44);
+}
+
+#[test]
+fn add_index() {
+    OpcodeTest::new()
+        .index(0x0FFF)
+        .register(2, 0x02)
+        .execute(0xF21E)
+        .assert_index(0x1001)
+        .assert_vf(0);
+    OpcodeTest::new()
+        .index(0xFFFF)
+        .register(2, 0x02)
+        .execute(0xF21E)
+        .assert_index(0x0001);
+}
+
+#[test]
+fn font_sprites() {
+    OpcodeTest::new()
+        .register(6, 0xA)
+        .execute(0xF629)
+        .assert_index(50);
+    OpcodeTest::new()
+        .register(6, 0x3)
+        .execute(0xF630)
+        .assert_index(BIG_FONT_ADDRESS + 30);
+}
+
+#[test]
+fn store_bcd() {
+    OpcodeTest::new()
+        .register(1, 254)
+        .index(0x300)
+        .execute(0xF133)
+        .assert_ram(0x300, &[2, 5, 4])
+        .assert_index(0x300);
+    OpcodeTest::new()
+        .register(1, 7)
+        .index(0x300)
+        .execute(0xF133)
+        .assert_ram(0x300, &[0, 0, 7]);
+    OpcodeTest::new().index(0xFFE).execute(0xF133).assert_error(
+        ExecutionError::MemoryOutOfBounds {
+            program_counter: 0x200,
+            address: 0x1000,
+        },
+    );
+}
+
+#[test]
+fn store_and_load_registers() {
+    for quirks in all_quirks() {
+        let index_after = match quirks.memory_increment {
+            true => 0x303,
+            false => 0x300,
+        };
+        OpcodeTest::new()
+            .quirks(quirks)
+            .register(0, 1)
+            .register(1, 2)
+            .register(2, 3)
+            .register(3, 4)
+            .index(0x300)
+            .execute(0xF255)
+            .assert_ram(0x300, &[1, 2, 3, 0])
+            .assert_index(index_after);
+        OpcodeTest::new()
+            .quirks(quirks)
+            .ram(0x300, &[5, 6, 7, 8])
+            .index(0x300)
+            .execute(0xF265)
+            .assert_register(0, 5)
+            .assert_register(1, 6)
+            .assert_register(2, 7)
+            .assert_register(3, 0)
+            .assert_index(index_after);
+    }
+}
+
+#[test]
+fn store_registers_at_the_end_of_memory() {
+    OpcodeTest::new()
+        .xo_chip()
+        .index(0xFFF0)
+        .execute(0xFF55)
+        .assert_ok()
+        .assert_index(0x0000);
+    OpcodeTest::new()
+        .index(0xFF8)
+        .execute(0xFF55)
+        .assert_error(ExecutionError::MemoryOutOfBounds {
+            program_counter: 0x200,
+            address: 0x1000,
+        })
+        .assert_index(0xFF8);
+}
+
+#[test]
+fn instructions_overwritten_by_the_program_are_decoded_again() {
+    OpcodeTest::new()
+        .program_counter(0x300)
+        .index(0x300)
+        .register(0, 0x62)
+        .register(1, 0x07)
+        .execute(0x6201)
+        .assert_register(2, 0x01)
+        .then(0xF155)
+        .assert_ram(0x300, &[0x62, 0x07])
+        .with(|computer| {
+            computer.cpu.program_counter = 0x300;
+            computer.tick().unwrap();
+        })
+        .assert_register(2, 0x07);
+}
+
+#[test]
+fn clearing_the_decode_cache_picks_up_direct_ram_changes() {
+    OpcodeTest::new()
+        .program_counter(0x300)
+        .execute(0x6201)
+        .assert_register(2, 0x01)
+        .with(|computer| {
+            computer.memory.ram[0x301] = 0x09;
+            computer.clear_decode_cache();
+            computer.cpu.program_counter = 0x300;
+            computer.tick().unwrap();
+        })
+        .assert_register(2, 0x09);
+}
+
+#[test]
+fn store_and_load_register_ranges() {
+    OpcodeTest::new()
+        .register(2, 0xA)
+        .register(3, 0xB)
+        .register(4, 0xC)
+        .index(0x300)
+        .execute(0x5242)
+        .assert_ram(0x300, &[0xA, 0xB, 0xC])
+        .assert_index(0x300);
+    OpcodeTest::new()
+        .register(2, 0xA)
+        .register(3, 0xB)
+        .register(4, 0xC)
+        .index(0x300)
+        .execute(0x5422)
+        .assert_ram(0x300, &[0xC, 0xB, 0xA]);
+    OpcodeTest::new()
+        .ram(0x300, &[0xA, 0xB])
+        .index(0x300)
+        .execute(0x5673)
+        .assert_register(6, 0xA)
+        .assert_register(7, 0xB)
+        .assert_index(0x300);
+}
+
+#[test]
+fn long_load() {
+    OpcodeTest::new()
+        .xo_chip()
+        .ram(0x202, &[0xBE, 0xEF])
+        .execute(0xF000)
+        .assert_ok()
+        .assert_index(0xBEEF)
+        .assert_program_counter(0x204);
+}
+
+#[test]
+fn program_counter_wraps_at_the_top_of_memory() {
+    OpcodeTest::new()
+        .xo_chip()
+        .program_counter(0xFFFE)
+        .execute(0x6A2B)
+        .assert_register(0xA, 0x2B)
+        .assert_program_counter(0x0000);
+    OpcodeTest::new()
+        .xo_chip()
+        .program_counter(0xFFFA)
+        .ram(0xFFFC, &[0xF0, 0x00, 0x12, 0x34])
+        .execute(0x3000)
+        .assert_program_counter(0x0000);
+    OpcodeTest::new()
+        .xo_chip()
+        .program_counter(0xFFFC)
+        .ram(0xFFFE, &[0xBE, 0xEF])
+        .execute(0xF000)
+        .assert_ok()
+        .assert_index(0xBEEF)
+        .assert_program_counter(0x0000);
+    //Instructions that stay where they are go back to the top of memory.
+    OpcodeTest::new()
+        .xo_chip()
+        .program_counter(0xFFFE)
+        .execute(0xF30A)
+        .assert_ok()
+        .assert_program_counter(0xFFFE)
+        .then(0x00FD)
+        .assert_ok()
+        .assert_program_counter(0xFFFE);
+    OpcodeTest::new()
+        .xo_chip()
+        .quirks(Quirks::cosmac_vip())
+        .index(0x300)
+        .ram(0x300, &[0x80])
+        .execute(0xD001)
+        .with(|computer| computer.cpu.program_counter = 0xFFFE)
+        .then(0xD001)
+        .assert_ok()
+        .assert_program_counter(0xFFFE);
+}
+
+#[test]
+fn select_planes() {
+    let executed = OpcodeTest::new().xo_chip().execute(0xF201).assert_ok();
+    assert_eq!(executed.computer.frame_buffer.selected_planes, 2);
+    executed
+        .with(|computer| {
+            computer.cpu.index_register = 0x300;
+            computer.memory.ram[0x300] = 0x80;
+        })
+        .then(0xD001)
+        .assert_pixel(0, 0, 2)
+        .then(0xF301)
+        .with(|computer| computer.memory.ram[0x301] = 0x80)
+        .then(0xD001)
+        .assert_pixel(0, 0, 1);
+}
+
+#[test]
+fn store_and_load_flags() {
+    OpcodeTest::new()
+        .register(0, 0x11)
+        .register(1, 0x22)
+        .execute(0xF175)
+        .with(|computer| computer.cpu.data_registers = [0; 16])
+        .then(0xF085)
+        .assert_register(0, 0x11)
+        .assert_register(1, 0);
+}
+
+#[test]
+fn unknown_opcodes() {
+    for instruction in [0x0123, 0x5121, 0x8128, 0xE1FF, 0xF1FF] {
+        OpcodeTest::new()
+            .program_counter(0x300)
+            .execute(instruction)
+            .assert_error(ExecutionError::UnknownOpcode {
+                program_counter: 0x300,
+                instruction,
+            })
+            .assert_program_counter(0x300);
+    }
+}
@@ -0,0 +1,102 @@
+extern crate chip8;
+
+mod common;
+
+use chip8::rewind::RewindBuffer;
+use chip8::{
+    Chip8Computer, EmulatorCommand, EmulatorResponse, SaveState, ThreadedEmulator, TimingMode,
+};
+use common::{computer_running, tick};
+
+#[test]
+fn states_come_back_newest_first() {
+    let mut buffer = RewindBuffer::new(3);
+    let state = |frame: u8| {
+        let mut bytes = vec![0; 4096];
+        bytes[frame as usize * 100] = frame;
+        bytes[4000] = frame;
+        bytes
+    };
+    for frame in 1..=5 {
+        buffer.push(state(frame));
+    }
+    assert_eq!(buffer.len(), 3);
+    assert!(buffer.size() < 4096 + 200);
+
+    assert_eq!(buffer.pop(), Some(state(5)));
+    assert_eq!(buffer.pop(), Some(state(4)));
+    buffer.push(state(9));
+    assert_eq!(buffer.pop(), Some(state(9)));
+    assert_eq!(buffer.pop(), Some(state(3)));
+    assert_eq!(buffer.pop(), None);
+    assert!(buffer.is_empty());
+}
+
+#[test]
+fn states_can_change_size() {
+    let mut buffer = RewindBuffer::new(10);
+    buffer.push(vec![1; 10]);
+    buffer.push(vec![2; 20]);
+    buffer.push(vec![2; 5]);
+    assert_eq!(buffer.pop(), Some(vec![2; 5]));
+    assert_eq!(buffer.pop(), Some(vec![2; 20]));
+    assert_eq!(buffer.pop(), Some(vec![1; 10]));
+
+    buffer.set_capacity(0);
+    buffer.push(vec![1]);
+    assert!(buffer.is_empty());
+}
+
+#[test]
+fn rewinding_returns_to_the_start_of_earlier_frames() {
+    let mut computer = computer_running("loop: ADD V0, 1\n LD I, 0x300\n LD B, V0\n JP loop");
+    computer.timing_mode = TimingMode::InstructionsPerFrame(4);
+    computer.set_rewind_frames(4);
+
+    //A frame starts after every 4 instructions.
+    let mut frame_starts = Vec::new();
+    for _ in 0..6 {
+        tick(&mut computer, 4);
+        frame_starts.push(computer.snapshot());
+    }
+    computer.tick().unwrap();
+
+    assert_eq!(computer.rewind(1), 1);
+    assert_eq!(computer.snapshot(), frame_starts[5]);
+    assert_eq!(computer.rewind(2), 2);
+    assert_eq!(computer.snapshot(), frame_starts[3]);
+    assert_eq!(computer.memory.ram[0x300..0x303], [0, 0, 4]);
+
+    //Only 4 frames are kept.
+    assert_eq!(computer.rewind(5), 1);
+    assert_eq!(computer.snapshot(), frame_starts[2]);
+    assert_eq!(computer.rewind(1), 0);
+    assert_eq!(
+        SaveState::from_bytes(&computer.save_state()).unwrap(),
+        frame_starts[2]
+    );
+}
+
+#[test]
+fn pausing_reports_whether_the_program_was_running() {
+    let (sender, receiver) = Chip8Computer::initialize();
+    sender
+        .send(EmulatorCommand::LoadRom(vec![0x12, 0x00]))
+        .unwrap();
+    let pause = || {
+        sender.send(EmulatorCommand::Pause).unwrap();
+        receiver
+            .iter()
+            .find_map(|response| match response {
+                EmulatorResponse::Paused { was_running, .. } => Some(was_running),
+                _ => None,
+            })
+            .unwrap()
+    };
+
+    //The display only resumes the program after rewinding if it was running before.
+    assert!(!pause());
+    sender.send(EmulatorCommand::Go).unwrap();
+    assert!(pause());
+    assert!(!pause());
+}
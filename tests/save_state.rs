@@ -0,0 +1,156 @@
+extern crate chip8;
+
+mod common;
+
+use chip8::assembler::assemble;
+use chip8::save_state::VERSION;
+use chip8::{
+    Chip8Computer, EmulatorCommand, EmulatorResponse, SaveState, SaveStateError, ThreadedEmulator,
+};
+use common::{computer_running, tick};
+
+///Draws a moving sprite with a subroutine and writes to memory, so every part of the state changes.
+const PROGRAM: &str = "
+    HIGH
+    LD V2, 3
+    LD DT, V2
+loop:
+    CALL draw
+    ADD V0, 3
+    LD I, scratch
+    LD B, V0
+    JP loop
+draw:
+    LD I, sprite
+    DRW V0, V1, 2
+    RET
+sprite:
+    db 0xFF, 0x81
+scratch:
+    db 0, 0, 0
+";
+
+#[test]
+fn loading_a_state_resumes_where_it_was_saved() {
+    let mut computer = computer_running(PROGRAM);
+    computer.input.set_key(7, true);
+    tick(&mut computer, 25);
+    let saved = computer.save_state();
+    tick(&mut computer, 40);
+    let expected = computer.snapshot();
+
+    let mut restored = computer_running("CLS");
+    restored.load_state(&saved).unwrap();
+    assert_eq!(SaveState::from_bytes(&saved).unwrap(), restored.snapshot());
+    tick(&mut restored, 40);
+    assert_eq!(restored.snapshot(), expected);
+    assert_eq!(
+        restored.frame_buffer.get_buffer_as_string(),
+        computer.frame_buffer.get_buffer_as_string()
+    );
+}
+
+#[test]
+fn random_numbers_continue_where_they_were_saved() {
+    let source = "loop: RND V0, 0xFF\n LD I, 0x300\n LD B, V0\n JP loop";
+    let mut computer = computer_running(source);
+    computer.set_seed(99);
+    tick(&mut computer, 20);
+    let saved = computer.save_state();
+    tick(&mut computer, 40);
+    let expected = computer.snapshot();
+
+    let mut restored = computer_running(source);
+    restored.load_state(&saved).unwrap();
+    assert_eq!(restored.seed(), 99);
+    tick(&mut restored, 40);
+    assert_eq!(restored.snapshot(), expected);
+}
+
+#[test]
+fn xo_chip_states_keep_all_of_ram() {
+    let mut computer = computer_running(PROGRAM);
+    computer.enable_xo_chip();
+    computer.memory.ram[0xFFFF] = 0x42;
+    let saved = computer.save_state();
+
+    let mut restored = computer_running("CLS");
+    restored.load_state(&saved).unwrap();
+    assert_eq!(restored.memory.ram.len(), 0x10000);
+    assert_eq!(restored.memory.ram[0xFFFF], 0x42);
+    assert_eq!(restored.quirks, computer.quirks);
+}
+
+#[test]
+fn damaged_states_are_rejected() {
+    let mut computer = computer_running(PROGRAM);
+    tick(&mut computer, 10);
+    let saved = computer.save_state();
+    let before = computer.snapshot();
+
+    let mut corrupted = saved.clone();
+    corrupted[100] ^= 0x01;
+    assert_eq!(
+        computer.load_state(&corrupted),
+        Err(SaveStateError::ChecksumMismatch)
+    );
+    assert_eq!(
+        computer.load_state(&saved[..saved.len() - 1]),
+        Err(SaveStateError::Truncated)
+    );
+    assert_eq!(
+        computer.load_state(b"CH8"),
+        Err(SaveStateError::NotASaveState)
+    );
+    assert_eq!(
+        computer.load_state(PROGRAM.as_bytes()),
+        Err(SaveStateError::NotASaveState)
+    );
+
+    let mut newer = saved.clone();
+    newer[4..6].copy_from_slice(&(VERSION + 1).to_be_bytes());
+    assert_eq!(
+        computer.load_state(&newer),
+        Err(SaveStateError::UnsupportedVersion(VERSION + 1))
+    );
+
+    let mut impossible = computer.snapshot();
+    impossible.cpu.stack_pointer = 17;
+    assert!(matches!(
+        computer.load_state(&impossible.to_bytes()),
+        Err(SaveStateError::Invalid(_))
+    ));
+
+    //Failed loads leave the machine as it was.
+    assert_eq!(computer.snapshot(), before);
+}
+
+#[test]
+fn states_can_be_saved_and_loaded_through_commands() {
+    let (sender, receiver) = Chip8Computer::initialize();
+    sender
+        .send(EmulatorCommand::LoadRom(assemble(PROGRAM).unwrap()))
+        .unwrap();
+    sender.send(EmulatorCommand::Step(8)).unwrap();
+    sender.send(EmulatorCommand::SaveState).unwrap();
+    let saved = receiver
+        .iter()
+        .find_map(|response| match response {
+            EmulatorResponse::SaveState(bytes) => Some(bytes),
+            _ => None,
+        })
+        .unwrap();
+
+    sender.send(EmulatorCommand::Step(8)).unwrap();
+    sender.send(EmulatorCommand::LoadState(saved)).unwrap();
+    sender.send(EmulatorCommand::GetRegisters).unwrap();
+    let cpu = receiver
+        .iter()
+        .find_map(|response| match response {
+            EmulatorResponse::Cpu(cpu) => Some(cpu),
+            _ => None,
+        })
+        .unwrap();
+    assert_eq!(cpu.data_registers[0], 3);
+    assert_eq!(cpu.program_counter, 0x20A);
+}
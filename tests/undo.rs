@@ -0,0 +1,148 @@
+extern crate chip8;
+
+mod common;
+
+use chip8::breakpoint::Access;
+use chip8::{Breakpoint, Chip8Computer, TimingMode};
+use common::run;
+
+///Changes registers, RAM, the stack and the screen in as many ways as possible.
+const PROGRAM: &str = "
+    HIGH
+    LD V0, 5
+loop:
+    LD I, sprite
+    DRW V0, V1, 3
+    SCR
+    SCD 2
+    ADD V0, 7
+    LD I, scratch
+    LD B, V0
+    LD [I], V2
+    CALL sub
+    JP loop
+sub:
+    LD DT, V0
+    ADD V1, 3
+    RET
+sprite:
+    db 0xFF, 0x81, 0xFF
+scratch:
+    db 0, 0, 0
+";
+
+fn computer_running(source: &str) -> Chip8Computer {
+    let mut computer = common::computer_running(source);
+    computer.timing_mode = TimingMode::InstructionsPerFrame(5);
+    computer.set_undo_limit(1000);
+    computer
+}
+
+#[test]
+fn stepping_back_undoes_instructions_exactly() {
+    let mut computer = computer_running(PROGRAM);
+    let states = run(&mut computer, 100);
+
+    for expected in states.iter().rev().skip(1) {
+        assert_eq!(computer.step_back(1), 1);
+        assert_eq!(&computer.snapshot(), expected);
+    }
+    assert_eq!(computer.step_back(1), 0);
+
+    //Running again after stepping back gives the same results.
+    assert_eq!(run(&mut computer, 100), states);
+    assert_eq!(computer.step_back(30), 30);
+    assert_eq!(computer.snapshot(), states[70]);
+}
+
+#[test]
+fn stepping_back_repeats_random_numbers() {
+    let mut computer = computer_running("loop: RND V0, 0xFF\n RND V1, 0xFF\n JP loop");
+    let states = run(&mut computer, 30);
+    assert_eq!(computer.step_back(12), 12);
+    assert_eq!(computer.snapshot(), states[18]);
+    assert_eq!(run(&mut computer, 12), states[18..]);
+}
+
+#[test]
+fn stepping_back_in_real_time_undoes_the_frames_it_crosses() {
+    let mut computer = computer_running("loop: ADD V0, 1\n JP loop");
+    computer.timing_mode = TimingMode::RealTime;
+    computer.set_rewind_frames(10);
+    //In real time frames start in between instructions rather than during them.
+    let mut frame_starts = Vec::new();
+    for _ in 0..10 {
+        run(&mut computer, 10);
+        computer.vertical_blank();
+        frame_starts.push(computer.snapshot());
+    }
+
+    //Back into the frame that started after 70 instructions.
+    assert_eq!(computer.step_back(25), 25);
+    assert_eq!(computer.rewind(1), 1);
+    assert_eq!(computer.snapshot(), frame_starts[6]);
+}
+
+#[test]
+fn only_the_most_recent_instructions_are_kept() {
+    let mut computer = computer_running(PROGRAM);
+    computer.set_undo_limit(10);
+    let states = run(&mut computer, 50);
+    assert_eq!(computer.step_back(20), 10);
+    assert_eq!(computer.snapshot(), states[40]);
+}
+
+#[test]
+fn reverse_continue_stops_at_breakpoints() {
+    let mut computer = computer_running(PROGRAM);
+    run(&mut computer, 60);
+
+    computer.add_breakpoint(Breakpoint::Address {
+        address: 0x216,
+        condition: None,
+    });
+    let undone = computer.reverse_continue();
+    assert_eq!(computer.cpu.program_counter, 0x216);
+    assert!(undone < 13);
+    let hit = computer.breakpoint_hit().unwrap();
+    assert_eq!(hit.program_counter, 0x216);
+
+    //Going backwards again doesn't stop at the same place straight away.
+    let undone = computer.reverse_continue();
+    //The loop is 13 instructions long.
+    assert_eq!(undone, 13);
+    assert_eq!(computer.cpu.program_counter, 0x216);
+}
+
+#[test]
+fn reverse_continue_stops_at_watchpoints_and_conditions() {
+    let mut computer = computer_running(PROGRAM);
+    run(&mut computer, 60);
+    let v0 = computer.cpu.data_registers[0];
+
+    computer.add_breakpoint(Breakpoint::Watch {
+        start: 0x223,
+        end: 0x223,
+        access: Access::Write,
+    });
+    computer.reverse_continue();
+    //Stopped on the last store to the scratch bytes, the registers rather than the BCD digits.
+    assert_eq!(computer.cpu.program_counter, 0x212);
+    assert!(computer.breakpoint_hit().is_some());
+
+    let mut computer = computer_running(PROGRAM);
+    run(&mut computer, 60);
+    computer.add_breakpoint(Breakpoint::Condition(
+        format!("V0 == {}", v0.wrapping_sub(14)).parse().unwrap(),
+    ));
+    computer.reverse_continue();
+    assert_eq!(computer.cpu.data_registers[0], v0.wrapping_sub(14));
+    assert!(computer.breakpoint_hit().is_some());
+
+    //Without a breakpoint to stop at, everything in the log is undone.
+    let mut computer = computer_running(PROGRAM);
+    let states = run(&mut computer, 60);
+    assert_eq!(computer.reverse_continue(), 60);
+    assert_eq!(computer.snapshot(), states[0]);
+    assert_eq!(computer.breakpoint_hit(), None);
+}